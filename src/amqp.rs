@@ -3,40 +3,93 @@ use std::fs::File;
 use std::io::{Read};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{self, Command, Stdio};
 use std::str::FromStr;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::{fs, thread};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::{env, fs, thread};
 use std::time::Duration;
-use amiquip::{Auth, Channel, Connection, ConnectionOptions, ConnectionTuning, ConsumerMessage, ConsumerOptions, ExchangeDeclareOptions, ExchangeType, FieldTable, Publish, QueueDeclareOptions, QueueDeleteOptions};
+use amiquip::{AmqpProperties, AmqpValue, Auth, Channel, Connection, ConnectionOptions, ConnectionTuning, ConsumerMessage, ConsumerOptions, Delivery, ExchangeDeclareOptions, ExchangeType, FieldTable, Publish, QueueDeclareOptions, QueueDeleteOptions};
 use chrono::{Local};
 use crossbeam::channel::{Sender, unbounded};
 use native_tls::{Certificate, Identity, TlsConnector};
 use anyhow::{Result, Context, anyhow};
 use bevy_reflect::Uuid;
 use crate::Config;
-use crate::models::enums::ExchangeTypeSer;
 use crate::models::exchange_options::{ExchangeOptions};
 use crate::models::read_value::ReadValue;
+use crate::message_filter::MessageFilter;
+use crate::decoders::Decoder;
+use crate::compression::Compression;
+use crate::file_logger::LogFormat;
+use crate::projection::Projection;
+use crate::operations::{json_value, Operation};
+use crate::tap_queues::TapQueues;
+use crate::management::encode_segment;
 
 pub static PAUSE: AtomicBool = AtomicBool::new(false);
 
+/// Unique per item and per running instance, so two items or two instances never share a tap
+/// queue.
+pub const DEFAULT_QUEUE_NAME_TEMPLATE: &str = "{app}.{exchange}.{item}.{host}.{user}.{pid}.{random}";
+pub const QUEUE_NAME_PLACEHOLDERS: [&str; 7] = ["{app}", "{exchange}", "{item}", "{host}", "{user}", "{pid}", "{random}"];
+
+/// A subscribed item's thread.
+struct Subscription {
+    unsubscribe: Sender<()>,
+    /// Set once the queue is declared, server named queues only get their name then.
+    queue_name: Arc<Mutex<Option<String>>>
+}
+
 pub struct Ampq {
-    current_subscriptions: HashMap<String, Sender<()>>,
+    /// By item id.
+    current_subscriptions: HashMap<Uuid, Subscription>,
+    /// Deliveries dropped by each item's filter since it subscribed.
+    filtered_counts: HashMap<Uuid, Arc<AtomicUsize>>,
     connection: Connection,
     log_sender: Sender<String>,
     message_sender: Sender<ReadValue>,
-    queue_names: Vec<String>
+    queue_names: Vec<String>,
+    tap_queues: TapQueues,
+    queue_name_template: String,
+    server_named_queues: bool,
+    /// Random part of the queue names, the same for every queue of this instance.
+    session_id: String
 }
 
 impl Ampq {
     pub fn new(config: &Config, console_log_sender: Sender<String>, message_sender: Sender<ReadValue>) -> Result<Self> {
-        let connection;
+        let connection = Self::connect(config)?;
+
         if config.pfx_path.is_some() && config.pem_file.is_some() {
-            let pfx_path_ref = config.pfx_path.as_ref().with_context(|| format!("Failed to convert pfx_path to reference"))?;
+            console_log_sender.send(format!("Secure connection to: {}:{}", config.host, config.port))?;
+        }
+        else {
+            console_log_sender.send(format!("Connected to: {}:{}", config.host, config.port))?;
+        }
+
+        Ok(
+            Ampq {
+                message_sender,
+                connection,
+                log_sender: console_log_sender,
+                current_subscriptions: HashMap::new(),
+                filtered_counts: HashMap::new(),
+                queue_names: vec![],
+                tap_queues: TapQueues::new(config),
+                queue_name_template: config.queue_name_template.clone().unwrap_or_else(|| DEFAULT_QUEUE_NAME_TEMPLATE.to_string()),
+                server_named_queues: config.server_named_queues,
+                session_id: Uuid::new_v4().to_simple().to_string()[..8].to_string()
+            }
+        )
+    }
+
+    pub fn connect(config: &Config) -> Result<Connection> {
+        let connection = if config.pfx_path.is_some() && config.pem_file.is_some() {
+            let pfx_path_ref = config.pfx_path.as_ref().with_context(|| "Failed to convert pfx_path to reference")?;
             let identity = get_identity(pfx_path_ref.as_str())?;
 
-            let pem_file_ref = config.pem_file.as_ref().with_context(|| format!("Failed to convert pem_file_path to reference"))?;
+            let pem_file_ref = config.pem_file.as_ref().with_context(|| "Failed to convert pem_file_path to reference")?;
             let cert = get_certificate(pem_file_ref.as_str())?;
 
             let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str(config.host.as_str())?), config.port as u16);
@@ -48,9 +101,9 @@ impl Ampq {
                 .add_root_certificate(cert)
                 .build()?;
 
-            let domain_ref = config.domain.as_ref().with_context(|| format!("Failed to convert domain to reference"))?;
+            let domain_ref = config.domain.as_ref().with_context(|| "Failed to convert domain to reference")?;
 
-            connection = Connection::open_tls_stream(
+            Connection::open_tls_stream(
                 tls_connector,
                domain_ref.as_str(),
                 stream,
@@ -63,41 +116,45 @@ impl Ampq {
                     .channel_max(1024)
                     .virtual_host(config.vhost.clone().unwrap_or_default())
                     .connection_timeout(Some(Duration::from_millis(10_000))),
-                ConnectionTuning::default())?;
-
-            console_log_sender.send(format!("Secure connection to: {}:{}", config.host, config.port))?;
+                ConnectionTuning::default())?
         }
         else {
-            let connection_string = format!("{}://{}:{}@{}:{}/{}", config.protocol, config.username, config.password, config.host, config.port, config.vhost.clone().unwrap_or_default());
-            connection = Connection::insecure_open(connection_string.as_str())?;
-
-            console_log_sender.send(format!("Connected to: {}:{}", config.host, config.port))?;
-        }
+            //passwords from secret managers often hold characters such as @, / or %
+            let connection_string = format!("{}://{}:{}@{}:{}/{}", config.protocol, encode_segment(config.username.as_str()), encode_segment(config.password.as_str()), config.host, config.port, config.vhost.clone().unwrap_or_default());
+            Connection::insecure_open(connection_string.as_str())?
+        };
 
-        Ok(
-            Ampq {
-                message_sender,
-                connection,
-                log_sender: console_log_sender,
-                current_subscriptions: HashMap::new(),
-                queue_names: vec![]
-            }
-        )
+        Ok(connection)
     }
 
-    pub fn add_subscription(&mut self, exchange_name: String, exchange_type: ExchangeType, queue_routing_key: String, selected_id: Uuid) -> Result<()> {
+    /// `content_encoding` is the item's option, which wins over the property of each message.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_subscription(&mut self, exchange_name: String, exchange_type: ExchangeType, queue_routing_key: String, filter: Option<MessageFilter>, decoder: Option<Decoder>, content_encoding: String, projection: Option<Projection>, log_format: LogFormat, selected_id: Uuid) -> Result<()> {
         let thread_sender = self.message_sender.clone();
         let thread_channel = self.create_channel()?;
         let thread_log_sender = self.log_sender.clone();
-        let queue_name = self.create_queue_name(exchange_name.as_str());
+        let server_named = self.server_named_queues;
+        let projection = projection.map(Arc::new);
+
+        //server named queues are exclusive, the broker deletes them with the connection
+        let queue_name = if server_named { "".to_string() } else { self.create_queue_name(exchange_name.as_str(), selected_id) };
 
-        if self.queue_names.contains(&queue_name) == false {
+        if !server_named && !self.queue_names.contains(&queue_name) {
             self.queue_names.push(queue_name.clone());
+            self.tap_queues.add(queue_name.as_str()).unwrap_or_else(|e| {
+                self.log_sender.send(format!("Error tracking queue: {}", e)).ok();
+            });
         }
 
+        let thread_tap_queues = self.tap_queues.clone();
+        let thread_filtered_count = Arc::new(AtomicUsize::new(0));
+        self.filtered_counts.insert(selected_id, thread_filtered_count.clone());
+
         let (sender, receiver) = unbounded();
+        let declared_name = Arc::new(Mutex::new(None));
+        let thread_declared_name = declared_name.clone();
 
-        self.current_subscriptions.insert(exchange_name.clone(), sender);
+        self.current_subscriptions.insert(selected_id, Subscription { unsubscribe: sender, queue_name: declared_name });
 
         thread_log_sender.send(format!("Channel created: {}", thread_channel.channel_id())).ok();
 
@@ -112,29 +169,32 @@ impl Ampq {
             let exchange = match thread_channel.exchange_declare(exchange_type, exchange_name.clone(), exchange_declare_options) {
                 Ok(res) => res,
                 Err(err) => {
-                    thread_log_sender.send(format!("Exchange error: {}", err.to_string())).ok();
+                    thread_log_sender.send(format!("Exchange error: {}", err)).ok();
                     return;
                 }
             };
 
-            match thread_channel.queue_declare(queue_name.clone(), QueueDeclareOptions { exclusive: false, ..QueueDeclareOptions::default() }) {
+            match thread_channel.queue_declare(queue_name, QueueDeclareOptions { exclusive: server_named, ..QueueDeclareOptions::default() }) {
                 Ok(queue) => {
+                    let queue_name = queue.name().to_string();
+                    *thread_declared_name.lock().unwrap() = Some(queue_name.clone());
                     thread_log_sender.send(format!("Queue Created: {}", queue_name.clone())).ok();
 
                     queue.bind(&exchange, queue_routing_key, FieldTable::new()).unwrap_or_else(|e| {
-                        thread_log_sender.send(format!("Error binding to queue: {}", e.to_string())).ok();
+                        thread_log_sender.send(format!("Error binding to queue: {}", e)).ok();
                     });
 
                     match queue.consume(ConsumerOptions { no_ack: true, ..ConsumerOptions::default() }) {
                         Ok(consumer) => {
                             loop {
-                                if let Ok(_) = receiver.try_recv() {
+                                if receiver.try_recv().is_ok() {
                                     match queue.delete(QueueDeleteOptions::default()) {
                                         Ok(_) => {
                                             thread_log_sender.send(format!("Queue Deleted: {}", queue_name)).ok();
+                                            thread_tap_queues.remove(queue_name.as_str()).ok();
                                         },
                                         Err(e) => {
-                                            thread_log_sender.send(format!("Error deleting queue: {}", e.to_string())).ok();
+                                            thread_log_sender.send(format!("Error deleting queue: {}", e)).ok();
                                         }
                                     }
                                     break;
@@ -144,16 +204,50 @@ impl Ampq {
                                 if let Ok(message) = consumer_message {
                                     match message {
                                         ConsumerMessage::Delivery(delivery) => {
-                                            if PAUSE.load(Ordering::SeqCst) == false {
-                                                let body = String::from_utf8_lossy(&delivery.body);
+                                            if !PAUSE.load(Ordering::SeqCst) {
+                                                let properties = delivery_properties(&delivery);
+                                                let content_type = delivery.properties.content_type().clone();
+                                                let compression = Compression::for_message(content_encoding.as_str(), delivery.properties.content_encoding().as_deref()).ok().flatten();
+
+                                                let (body, decompress_error) = match compression {
+                                                    Some(res) => match res.decompress(&delivery.body) {
+                                                        Ok(decompressed) => (decompressed, None),
+                                                        Err(e) => (delivery.body, Some(format!("{}: {:#}", res.name(), e)))
+                                                    },
+                                                    None => (delivery.body, None)
+                                                };
+
+                                                let decoded = match (decompress_error, &decoder) {
+                                                    (Some(e), _) => Some(Err(e)),
+                                                    (None, Some(res)) => Some(res.decode(&body, content_type.as_deref()).map_err(|e| format!("{}: {:#}", res.name(), e))),
+                                                    (None, None) => content_type.as_deref().and_then(Decoder::for_content_type).map(|x| {
+                                                        x.decode(&body, None).map_err(|e| format!("{}: {:#}", x.name(), e))
+                                                    })
+                                                };
+
+                                                if let Some(filter) = &filter {
+                                                    let body = match &decoded {
+                                                        Some(Ok(res)) => res.to_string(),
+                                                        _ => String::from_utf8_lossy(&body).to_string()
+                                                    };
+                                                    if !filter.matches(&body) {
+                                                        thread_filtered_count.fetch_add(1, Ordering::SeqCst);
+                                                        continue;
+                                                    }
+                                                }
 
                                                 let now = Local::now();
 
                                                 thread_sender.send(ReadValue {
                                                     id: selected_id,
                                                     exchange_name: exchange_name.clone(),
-                                                    value: body.to_string(),
-                                                    timestamp: now.clone()
+                                                    routing_key: delivery.routing_key.clone(),
+                                                    properties,
+                                                    body,
+                                                    decoded,
+                                                    projection: projection.clone(),
+                                                    log_format,
+                                                    timestamp: now
                                                 }).ok();
                                             }
                                         }
@@ -165,12 +259,12 @@ impl Ampq {
                             }
                         }
                         Err(e) => {
-                            thread_log_sender.send(format!("Error creating consumer: {}", e.to_string())).ok();
+                            thread_log_sender.send(format!("Error creating consumer: {}", e)).ok();
                         }
                     };
                 }
                 Err(e) => {
-                    thread_log_sender.send(format!("Error deleting queue: {}", e.to_string())).ok();
+                    thread_log_sender.send(format!("Error deleting queue: {}", e)).ok();
                 }
             };
 
@@ -181,13 +275,6 @@ impl Ampq {
     }
 
     pub fn publish(&mut self, exchange_options: &ExchangeOptions) -> Result<()> {
-        let exchange_type = match &exchange_options.exchange_type {
-            ExchangeTypeSer::Direct => ExchangeType::Direct,
-            ExchangeTypeSer::Fanout => ExchangeType::Fanout,
-            ExchangeTypeSer::Topic => ExchangeType::Topic,
-            ExchangeTypeSer::Headers => ExchangeType::Headers,
-        };
-
         let exchange_declare_options = ExchangeDeclareOptions {
             durable: true,
             auto_delete: false,
@@ -196,37 +283,83 @@ impl Ampq {
         };
 
         let channel = self.create_channel()?;
-        let exchange = channel.exchange_declare(exchange_type, exchange_options.exchange_name.clone(), exchange_declare_options)?;
+        let exchange = channel.exchange_declare(exchange_options.exchange_type.into(), exchange_options.exchange_name.clone(), exchange_declare_options)?;
         let contents = fs::read_to_string(exchange_options.publish_file.clone())?;
-        exchange.publish(Publish::new(contents.as_bytes(), exchange_options.queue_routing_key.clone()))?;
 
-        self.log_sender.send(format!("Published contents of {}, to {}", exchange_options.publish_file.clone(), exchange_options.exchange_name.clone()))?;
+        match Compression::parse(exchange_options.publish_encoding.as_str())? {
+            Some(compression) => {
+                let body = compression.compress(contents.as_bytes())?;
+                let properties = AmqpProperties::default().with_content_encoding(compression.name().to_string());
+                exchange.publish(Publish::with_properties(&body, exchange_options.queue_routing_key.clone(), properties))?;
+
+                self.log_sender.send(format!("Published contents of {}, to {} ({}, {} of {} bytes)", exchange_options.publish_file.clone(), exchange_options.exchange_name.clone(), compression.name(), body.len(), contents.len()))?;
+            }
+            None => {
+                exchange.publish(Publish::new(contents.as_bytes(), exchange_options.queue_routing_key.clone()))?;
+
+                self.log_sender.send(format!("Published contents of {}, to {}", exchange_options.publish_file.clone(), exchange_options.exchange_name.clone()))?;
+            }
+        }
 
         Ok(())
     }
 
-    pub fn change_subscription(&mut self, exchange_options: &ExchangeOptions, selected_id: Uuid) {
-        match self.current_subscriptions.get(exchange_options.exchange_name.as_str()) {
+    /// Fails without subscribing when the item's filter, compression, fields or log format
+    /// cannot be parsed or its decoder cannot read its schema file.
+    pub fn change_subscription(&mut self, exchange_options: &ExchangeOptions, selected_id: Uuid) -> Result<()> {
+        match self.current_subscriptions.get(&selected_id) {
             None => {
-                //TODO implement from/into to let the enum itself handle this conversion
-                let exchange_type = match &exchange_options.exchange_type {
-                    ExchangeTypeSer::Direct => ExchangeType::Direct,
-                    ExchangeTypeSer::Fanout => ExchangeType::Fanout,
-                    ExchangeTypeSer::Topic => ExchangeType::Topic,
-                    ExchangeTypeSer::Headers => ExchangeType::Headers,
-                };
-                self.add_subscription(exchange_options.exchange_name.clone(), exchange_type, exchange_options.queue_routing_key.clone(), selected_id).ok();
+                let filter = MessageFilter::parse(exchange_options.filter.as_str())?;
+                let decoder = Decoder::from_options(exchange_options.decoder.as_str(), exchange_options.schema_file.as_str())?;
+                Compression::parse(exchange_options.content_encoding.as_str())?;
+                let projection = Projection::parse_option(exchange_options.fields.as_str())?;
+                let log_format = LogFormat::parse(exchange_options.log_format.as_str())?;
+                self.add_subscription(exchange_options.exchange_name.clone(), exchange_options.exchange_type.into(), exchange_options.queue_routing_key.clone(), filter, decoder, exchange_options.content_encoding.clone(), projection, log_format, selected_id)?;
             }
-            Some(unsubscribe_sender) => {
-                unsubscribe_sender.send(()).ok();
+            Some(subscription) => {
+                subscription.unsubscribe.send(()).ok();
+                //the subscription thread deletes its own queue
+                if let Some(queue_name) = self.queue_name(selected_id) {
+                    self.queue_names.retain(|x| *x != queue_name);
+                }
                 self.log_sender.send(format!("Unsubscribing from: {}", exchange_options.exchange_name.clone())).ok();
-                self.current_subscriptions.remove(&exchange_options.exchange_name);
+                self.current_subscriptions.remove(&selected_id);
+                self.filtered_counts.remove(&selected_id);
             }
         };
+
+        Ok(())
+    }
+
+    pub fn filtered_count(&self, selected_id: Uuid) -> usize {
+        self.filtered_counts.get(&selected_id).map(|x| x.load(Ordering::SeqCst)).unwrap_or(0)
     }
 
-    pub fn create_queue_name(&self, exchange_name: &str) -> String {
-        format!("{}.{}", env!("CARGO_PKG_NAME"), exchange_name)
+    /// Runs on its own channel, since the broker closes the channel when it refuses an operation.
+    pub fn run_operation(&mut self, operation: &Operation) -> Result<String> {
+        let channel = self.create_channel()?;
+        let result = operation.execute(&channel);
+        channel.close().ok();
+
+        result
+    }
+
+    /// The name of the queue an item's subscription declared, `None` before it is declared.
+    pub fn queue_name(&self, selected_id: Uuid) -> Option<String> {
+        self.current_subscriptions
+            .get(&selected_id)
+            .and_then(|x| x.queue_name.lock().unwrap().clone())
+    }
+
+    fn create_queue_name(&self, exchange_name: &str, selected_id: Uuid) -> String {
+        self.queue_name_template
+            .replace("{app}", env!("CARGO_PKG_NAME"))
+            .replace("{exchange}", exchange_name)
+            .replace("{item}", &selected_id.to_simple().to_string()[..8])
+            .replace("{host}", host_name().as_str())
+            .replace("{user}", user_name().as_str())
+            .replace("{pid}", process::id().to_string().as_str())
+            .replace("{random}", self.session_id.as_str())
     }
 
     pub fn create_channel(&mut self) -> Result<Channel> {
@@ -234,10 +367,13 @@ impl Ampq {
         Ok(channel)
     }
 
+    /// Safe to call more than once, e.g. from a signal and then from the normal exit.
     pub fn delete_remaining_queue(&mut self) -> Result<()> {
-        let channel = self.create_channel()?;
-        for queue_name in self.queue_names.iter() {
+        //the broker closes the channel when a delete fails, so each queue gets its own
+        for queue_name in std::mem::take(&mut self.queue_names) {
+            let channel = self.create_channel()?;
             self.delete_queue(queue_name.as_str(), &channel);
+            channel.close().ok();
         }
 
         Ok(())
@@ -245,12 +381,63 @@ impl Ampq {
 
     pub fn delete_queue(&self, queue_name: &str, channel: &Channel) {
         match channel.queue_delete(queue_name, QueueDeleteOptions::default()) {
-            Ok(_) => self.log_sender.send(format!("Queue Deleted: {}", queue_name)).ok(),
-            Err(e) => self.log_sender.send(format!("Error deleting queue: {}, error: {}", queue_name, e.to_string())).ok()
+            Ok(_) => {
+                self.tap_queues.remove(queue_name).ok();
+                self.log_sender.send(format!("Queue Deleted: {}", queue_name)).ok()
+            }
+            Err(e) => self.log_sender.send(format!("Error deleting queue: {}, error: {}", queue_name, e)).ok()
         };
     }
 }
 
+fn host_name() -> String {
+    env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+fn user_name() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| "user".to_string())
+}
+
+/// The properties set on a delivery, headers as a JSON object.
+fn delivery_properties(delivery: &Delivery) -> Vec<(String, String)> {
+    let properties = &delivery.properties;
+    let mut list = vec![];
+
+    let mut add = |name: &str, value: Option<String>| {
+        if let Some(res) = value {
+            list.push((name.to_string(), res));
+        }
+    };
+
+    add("content_type", properties.content_type().clone());
+    add("content_encoding", properties.content_encoding().clone());
+    add("headers", properties.headers().as_ref().map(|x| json_value(&AmqpValue::FieldTable(x.clone())).to_string()));
+    add("delivery_mode", properties.delivery_mode().map(|x| x.to_string()));
+    add("priority", properties.priority().map(|x| x.to_string()));
+    add("correlation_id", properties.correlation_id().clone());
+    add("reply_to", properties.reply_to().clone());
+    add("expiration", properties.expiration().clone());
+    add("message_id", properties.message_id().clone());
+    add("timestamp", properties.timestamp().map(|x| x.to_string()));
+    add("type", properties.type_().clone());
+    add("user_id", properties.user_id().clone());
+    add("app_id", properties.app_id().clone());
+    add("cluster_id", properties.cluster_id().clone());
+    add("redelivered", delivery.redelivered.then(|| "true".to_string()));
+
+    list
+}
+
 fn get_certificate(pem_file_path: &str) -> Result<Certificate> {
     let path = Path::new(pem_file_path);
     if path.exists() {
@@ -270,17 +457,17 @@ fn get_certificate(pem_file_path: &str) -> Result<Certificate> {
         Ok(cert)
     }
     else {
-        return Err(anyhow!("Pem file path does not exist: {}", pem_file_path));
+        Err(anyhow!("Pem file path does not exist: {}", pem_file_path))
     }
 }
 
 fn get_identity(pfx_path: &str) -> Result<Identity> {
     let mut file = File::open(pfx_path)
-        .with_context(|| format!("Error opening pfx file"))?;
+        .with_context(|| "Error opening pfx file".to_string())?;
 
     let mut identity = vec![];
     file.read_to_end(&mut identity)
-        .with_context(|| format!("Error reading pfx file"))?;
+        .with_context(|| "Error reading pfx file".to_string())?;
 
     let identity = Identity::from_pkcs12(&identity, "")
         .with_context(|| "Error getting Identity from pfx file".to_string())?;
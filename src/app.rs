@@ -1,16 +1,29 @@
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
+use bevy_reflect::Uuid;
 use crossbeam::channel::{Receiver, unbounded};
 use crossterm::event;
-use crossterm::event::{Event, KeyCode};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
 use tui::backend::Backend;
 use tui::Terminal;
 use crate::{Ampq, Config};
 use crate::ui::{EditType, Ui};
 use anyhow::Result;
+use crate::browser::{Browser, BrowserTab};
 use crate::file_logger::FileLogger;
+use crate::keymap::{Action, Keymap};
+use crate::message_diff::MessageDiff;
+use crate::message_list::copy_to_clipboard;
 use crate::models::enums::SelectedState;
+use crate::models::exchange_options::ExchangeOptions;
 use crate::models::read_value::ReadValue;
+use crate::operations::Operation;
+use crate::routing_tester::RoutingTester;
+use crate::theme::Themes;
+use crate::topology::Topology;
+
+/// Set by the SIGINT/SIGTERM handler so the app exits through `exit` and deletes its queues.
+pub static TERMINATE: AtomicBool = AtomicBool::new(false);
 
 #[derive(PartialEq)]
 pub enum Windows {
@@ -18,7 +31,15 @@ pub enum Windows {
     Options,
     OptionsStringInput,
     SelectionFilter,
-    MultiSelectInput
+    MultiSelectInput,
+    ConfirmDelete,
+    Browser,
+    Topology,
+    RoutingTester,
+    OperationInput,
+    ConfirmOperation,
+    MessageSearch,
+    MessageDiff
 }
 
 #[derive(PartialEq)]
@@ -35,8 +56,16 @@ pub struct App {
     pub file_logger: FileLogger,
     pub config: Config,
     pub selection_filter: String,
+    pub browser: Browser,
+    pub topology: Topology,
+    pub routing_tester: RoutingTester,
+    pub message_diff: MessageDiff,
+    pub themes: Themes,
+    pub keymap: Keymap,
+    pub operation_input: String,
 
     console_log_receiver: Receiver<String>,
+    pending_operation: Option<Operation>,
     ampq: Ampq,
     tick_rate: u64,
 }
@@ -49,6 +78,10 @@ impl App {
         let ampq = Ampq::new(&config, console_log_sender.clone(), message_sender.clone())?;
         let file_logger = FileLogger::new(console_log_sender.clone());
 
+        let mut themes = Themes::load(config.theme_file.as_deref(), config.themes.as_ref())?;
+        themes.start(config.theme.as_deref().unwrap_or("dark"))?;
+        let keymap = Keymap::load(config.keys.as_ref())?;
+
         Ok(
             App {
                 config,
@@ -60,7 +93,15 @@ impl App {
                 tick_rate: 100,
                 file_logger,
                 mode: Mode::Normal,
-                selection_filter: "".to_string()
+                selection_filter: "".to_string(),
+                browser: Browser::new(),
+                topology: Topology::new(),
+                routing_tester: RoutingTester::new(),
+                message_diff: MessageDiff::new(),
+                themes,
+                keymap,
+                operation_input: "".to_string(),
+                pending_operation: None
             }
         )
     }
@@ -78,6 +119,11 @@ impl App {
                 .checked_sub(last_tick.elapsed())
                 .unwrap_or_else(|| Duration::from_secs(0));
 
+            if TERMINATE.load(Ordering::SeqCst) {
+                self.exit();
+                return Ok(());
+            }
+
             if crossterm::event::poll(timeout)? {
                 let exit = self.handle_input(&mut ui)?;
                 if exit {
@@ -94,133 +140,232 @@ impl App {
 
     fn handle_input(&mut self, ui: &mut Ui) -> Result<bool> {
         if let Event::Key(key) = event::read()? {
+            //raw mode turns Ctrl+C into a key press instead of SIGINT
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                self.exit();
+                return Ok(true);
+            }
+
             match self.active_window {
                 Windows::Main => {
+                    let action = self.keymap.action(&self.mode, key.code);
                     match self.mode {
                         Mode::Normal => {
-                            match key.code {
-                                KeyCode::Char('q') => {
+                            match action {
+                                Some(Action::Quit) => {
                                     self.exit();
                                     return Ok(true);
                                 },
-                                KeyCode::Char('k') => ui.main_index_up(), //VIM binding
-                                KeyCode::Char('j') => ui.main_index_down(), //VIM binding
-                                KeyCode::Esc => {
-                                    self.exit();
-                                    return Ok(true);
+                                Some(Action::Up) => ui.main_index_up(),
+                                Some(Action::Down) => ui.main_index_down(),
+                                Some(Action::ShrinkSelector) => ui.left_resize(),
+                                Some(Action::GrowSelector) => ui.right_resize(),
+                                Some(Action::Logs) => ui.show_logs = !ui.show_logs,
+                                Some(Action::Subscribe) => {
+                                    let selected_id = ui.get_selected_item_id();
+                                    self.toggle_subscription(selected_id);
                                 },
-                                KeyCode::Up => ui.main_index_up(),
-                                KeyCode::Down => ui.main_index_down(),
-                                KeyCode::Left => ui.left_resize(),
-                                KeyCode::Right => ui.right_resize(),
-                                KeyCode::Char('l') => ui.show_logs = !ui.show_logs,
-                                KeyCode::Enter => {
+                                Some(Action::Edit) => {
+                                    self.active_window = Windows::Options;
                                     let selected_id = ui.get_selected_item_id();
-                                    match &mut self.config.items
-                                        .iter_mut()
+                                    match self.config.items
+                                        .iter()
                                         .find(|x| x.id == selected_id) {
-                                        None => self.console_logs.push(format!("Cannot find selected item in config.items")),
-                                        Some(selected_item) => {
-                                            if selected_item.selected_state == SelectedState::Unselected {
-                                                selected_item.selected_state = SelectedState::PendingSubscription;
-                                            }
-                                            else if selected_item.selected_state == SelectedState::Subscribed {
-                                                selected_item.selected_state = SelectedState::Unselected;
+                                            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
+                                            Some(selected_item) => {
+                                                ui.show_options_popup( selected_item.clone())
                                             }
-                                            else if selected_item.selected_state == SelectedState::PendingSubscription {
-                                                selected_item.selected_state = SelectedState::Unselected;
-                                                match self.ampq.create_channel() {
-                                                    Ok(channel) => {
-                                                        let queue_name = self.ampq.create_queue_name(selected_item.exchange_name.as_str());
-                                                        self.ampq.delete_queue(queue_name.as_str(), &channel);
-                                                    }
-                                                    Err(e) => {
-                                                        self.console_logs.push(format!("Error creating channel: {}", e.to_string()));
-                                                    }
+                                        };
+                                },
+                                Some(Action::Add) => {
+                                    self.active_window = Windows::Options;
+                                    ui.show_options_popup(ExchangeOptions::default());
+                                },
+                                Some(Action::Clone) => {
+                                    let selected_id = ui.get_selected_item_id();
+                                    match self.config.items
+                                        .iter()
+                                        .find(|x| x.id == selected_id) {
+                                            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
+                                            Some(selected_item) => {
+                                                let mut clone = selected_item.clone();
+                                                clone.id = Uuid::new_v4();
+                                                clone.selected_state = SelectedState::Unselected;
+                                                if !clone.alias.is_empty() {
+                                                    clone.alias = format!("{} (copy)", clone.alias);
                                                 }
-                                            }
 
-                                            self.ampq.change_subscription(&selected_item, selected_id);
-                                        }
-                                    };
+                                                self.active_window = Windows::Options;
+                                                ui.show_options_popup(clone);
+                                            }
+                                        };
                                 },
-                                KeyCode::Char('e') => {
-                                    self.active_window = Windows::Options;
+                                Some(Action::Delete) => {
                                     let selected_id = ui.get_selected_item_id();
                                     match self.config.items
                                         .iter()
                                         .find(|x| x.id == selected_id) {
-                                            None => self.console_logs.push(format!("Cannot find selected item in config.items")),
+                                            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
                                             Some(selected_item) => {
-                                                ui.show_options_popup( selected_item.clone())
+                                                self.active_window = Windows::ConfirmDelete;
+                                                ui.show_confirm_popup(format!("Delete {}? (y/n)", selected_item.display_name()));
+                                            }
+                                        };
+                                },
+                                Some(Action::Browse) => {
+                                    self.active_window = Windows::Browser;
+                                    if self.browser.snapshot.is_none() {
+                                        self.browser.refresh(&self.config);
+                                    }
+                                },
+                                Some(Action::Topology) => {
+                                    let selected_id = ui.get_selected_item_id();
+                                    match self.config.items
+                                        .iter()
+                                        .find(|x| x.id == selected_id) {
+                                            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
+                                            Some(selected_item) => {
+                                                self.active_window = Windows::Topology;
+                                                self.topology.open(selected_item.exchange_name.as_str(), &self.config);
                                             }
                                         };
                                 },
-                                KeyCode::Char('p') => {
+                                Some(Action::RoutingTester) => {
+                                    let selected_id = ui.get_selected_item_id();
+                                    match self.config.items
+                                        .iter()
+                                        .find(|x| x.id == selected_id) {
+                                            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
+                                            Some(selected_item) => {
+                                                self.active_window = Windows::RoutingTester;
+                                                self.routing_tester.open(selected_item);
+                                            }
+                                        };
+                                },
+                                Some(Action::Operation) => self.active_window = Windows::OperationInput,
+                                Some(Action::MessageList) => ui.toggle_message_list(false),
+                                Some(Action::Grid) => self.toggle_column_view(ui, false),
+                                Some(Action::Tiles) => ui.toggle_tiles(),
+                                Some(Action::Theme) => {
+                                    let name = self.themes.next().to_string();
+                                    self.console_logs.push(format!("Theme: {}", name));
+                                    self.config.theme = Some(name);
+                                },
+                                Some(Action::Pause) => {
                                     self.mode = Mode::Scroll;
+                                    ui.messages.hold();
+                                    if let Some(column_view) = ui.column_view.as_mut() {
+                                        column_view.hold(ui.messages.records());
+                                    }
                                     let current = crate::amqp::PAUSE.load(Ordering::SeqCst);
                                     let new_value = !current;
                                     self.console_logs.push(format!("PAUSED: {}", new_value));
                                     crate::amqp::PAUSE.store(new_value, Ordering::SeqCst);
                                 },
-                                KeyCode::Char('f') => {
+                                Some(Action::Filter) => {
                                     self.active_window = Windows::SelectionFilter;
                                 }
-                                KeyCode::Char('/') => {
-                                    self.active_window = Windows::SelectionFilter;
-                                }
-                                KeyCode::Char('s') => {
+                                Some(Action::Save) => {
                                     match self.config.save_config() {
-                                        Ok(_) => self.console_logs.push(format!("Config File Saved: {}", self.config.path.as_str())),
-                                        Err(e) => self.console_logs.push(format!("Error Saving Config File: {}", e.to_string()))
+                                        Ok(_) if self.config.keeps_layout() => self.console_logs.push(format!("Config File Saved: {}", self.config.path.as_str())),
+                                        Ok(_) => self.console_logs.push(format!("Config File Saved: {} (YAML comments and layout are not kept)", self.config.path.as_str())),
+                                        Err(e) => self.console_logs.push(format!("Error Saving Config File: {}", e))
                                     }
                                 }
-                                KeyCode::Char('n') => self.send_publish_to_amqp(ui),
-                                KeyCode::Char('P') => self.send_publish_to_amqp(ui),
+                                Some(Action::Publish) => self.send_publish_to_amqp(ui),
+                                None => self.sort_column_view(ui, key.code),
                                 _ => {}
                             }
                         }
                         Mode::Scroll => {
-                            match key.code {
-                                KeyCode::Char('q') => {
+                            match action {
+                                Some(Action::Quit) => {
                                     self.exit();
                                     return Ok(true);
                                 },
-                                KeyCode::Char('k') => ui.scroll_up(), //VIM binding
-                                KeyCode::Char('j') => ui.scroll_down(), //VIM binding
-                                KeyCode::Esc => {
-                                    self.exit();
-                                    return Ok(true);
+                                Some(Action::Up) => ui.scroll_up(),
+                                Some(Action::Down) => ui.scroll_down(),
+                                Some(Action::PageUp) => ui.scroll_up_page(),
+                                Some(Action::PageDown) => ui.scroll_down_page(),
+                                Some(Action::ShrinkSelector) => ui.left_resize(),
+                                Some(Action::GrowSelector) => ui.right_resize(),
+                                Some(Action::Logs) => ui.show_logs = !ui.show_logs,
+                                Some(Action::Search) => {
+                                    self.active_window = Windows::MessageSearch;
+                                    ui.start_search();
+                                },
+                                Some(Action::NextMatch) => self.search_jump(ui, true),
+                                Some(Action::PreviousMatch) => self.search_jump(ui, false),
+                                Some(Action::MessageList) => ui.toggle_message_list(true),
+                                Some(Action::Grid) => self.toggle_column_view(ui, true),
+                                Some(Action::Tiles) => ui.toggle_tiles(),
+                                Some(Action::ViewMode) if ui.show_message_list => {
+                                    if let Some(view_mode) = ui.messages.next_view_mode() {
+                                        self.console_logs.push(format!("Showing message as {:?}", view_mode));
+                                    }
+                                },
+                                Some(Action::DetailFocus) if ui.show_message_list => ui.messages.toggle_detail_focus(),
+                                Some(Action::Fold) if ui.show_message_list && ui.messages.detail_focus => ui.messages.toggle_fold(),
+                                Some(Action::FoldAll) if ui.show_message_list => ui.messages.fold_all(true),
+                                Some(Action::UnfoldAll) if ui.show_message_list => ui.messages.fold_all(false),
+                                Some(Action::Mark) if ui.show_message_list => {
+                                    match ui.messages.toggle_mark() {
+                                        Some(true) => self.console_logs.push(format!("Marked message, press {} to compare", self.keymap.key_name(Action::Diff))),
+                                        Some(false) => self.console_logs.push("Unmarked message".to_string()),
+                                        None => self.console_logs.push("No message to mark".to_string())
+                                    }
+                                },
+                                Some(Action::Diff) if ui.show_message_list => {
+                                    match ui.messages.marked_pair() {
+                                        Some((older, newer)) => {
+                                            self.message_diff.open(older, newer);
+                                            self.active_window = Windows::MessageDiff;
+                                        }
+                                        None => self.console_logs.push(format!("Mark two messages with {}, or mark one and select another, to compare them", self.keymap.key_name(Action::Mark)))
+                                    }
+                                },
+                                Some(Action::Copy) if ui.show_message_list => {
+                                    match ui.messages.selected() {
+                                        None => self.console_logs.push("No message to copy".to_string()),
+                                        Some(record) => match copy_to_clipboard(record.body_lines().join("\n").as_str()) {
+                                            Ok(_) => self.console_logs.push(format!("Copied message from {} to the clipboard", record.name)),
+                                            Err(e) => self.console_logs.push(format!("Cannot copy message: {}", e))
+                                        }
+                                    }
+                                },
+                                Some(Action::FilterView) => {
+                                    if ui.search.is_active() {
+                                        ui.toggle_filter_view();
+                                    }
+                                    else {
+                                        self.console_logs.push(format!("Search with {} before using the filter view", self.keymap.key_name(Action::Search)));
+                                    }
                                 },
-                                KeyCode::Up => ui.scroll_up(),
-                                KeyCode::Down => ui.scroll_down(),
-                                KeyCode::PageUp => ui.scroll_up_page(),
-                                KeyCode::PageDown => ui.scroll_down_page(),
-                                KeyCode::Left => ui.left_resize(),
-                                KeyCode::Right => ui.right_resize(),
-                                KeyCode::Char('l') => ui.show_logs = !ui.show_logs,
-                                KeyCode::Char('p') => {
+                                Some(Action::Pause) => {
                                     self.mode = Mode::Normal;
+                                    ui.messages.follow();
+                                    if let Some(column_view) = ui.column_view.as_mut() {
+                                        column_view.follow();
+                                    }
                                     let current = crate::amqp::PAUSE.load(Ordering::SeqCst);
                                     let new_value = !current;
                                     self.console_logs.push(format!("PAUSED: {}", new_value));
                                     crate::amqp::PAUSE.store(new_value, Ordering::SeqCst);
                                 }
+                                None => self.sort_column_view(ui, key.code),
                                 _ => {}
                             }
                         }
                     }
                 }
-                Windows::Options => match key.code {
-                    KeyCode::Esc => {
+                Windows::Options => match (self.keymap.popup_action(key.code, false), key.code) {
+                    (Some(Action::Close), _) => {
                         self.active_window = Windows::Main;
                         ui.hide_options_popup();
                     }
-                    KeyCode::Down => ui.options_index_down(),
-                    KeyCode::Up => ui.options_index_up(),
-                    KeyCode::Char('j') => ui.options_index_down(), //VIM binding
-                    KeyCode::Char('k') => ui.options_index_up(), //VIM binding
-                    KeyCode::Char('e') => {
+                    (Some(Action::Down), _) => ui.options_index_down(),
+                    (Some(Action::Up), _) => ui.options_index_up(),
+                    (Some(Action::Edit), _) => {
                         match ui.options_change_value() {
                             EditType::None => {}
                             EditType::String(res) => {
@@ -233,55 +378,188 @@ impl App {
                             }
                         }
                     },
-                    KeyCode::Enter => {
+                    (_, KeyCode::Enter) => {
+                        let options_exchange = ui.options_exchange.clone();
+                        if options_exchange.exchange_name.is_empty() {
+                            self.console_logs.push("exchange_name is required".to_string());
+                            return Ok(false);
+                        }
+
                         self.active_window = Windows::Main;
-                        let selected_id = ui.get_selected_item_id();
-                        match self.config.items
+                        ui.hide_options_popup();
+
+                        //items opened with (A)dd or (C)lone are not in the list yet
+                        let selected_id = options_exchange.id;
+                        let resubscribe = match self.config.items
                             .iter_mut()
                             .find(|x| x.id == selected_id) {
-                            None => self.console_logs.push(format!("Cannot find selected item in config.items")),
+                            None => {
+                                self.console_logs.push(format!("Added: {}", options_exchange.display_name()));
+                                ui.select_item(selected_id);
+                                self.config.items.push(options_exchange);
+                                false
+                            }
                             Some(selected_item) => {
-                                *selected_item = ui.options_exchange.clone();
-                                ui.hide_options_popup();
+                                //the popup edited a copy, the item may have been (un)subscribed since it opened
+                                let selected_state = selected_item.selected_state.clone();
+                                let resubscribe = selected_state != SelectedState::Unselected && !selected_item.subscribes_like(&options_exchange);
+                                *selected_item = ExchangeOptions { selected_state, ..options_exchange };
+                                resubscribe
+                            }
+                        };
+
+                        //a running subscription keeps the options it was made with, so it is made again
+                        if resubscribe {
+                            self.toggle_subscription(selected_id);
+                            self.toggle_subscription(selected_id);
+                        }
+                    },
+                    _ => {}
+                }
+                Windows::Browser => match (self.keymap.popup_action(key.code, false), key.code) {
+                    (Some(Action::Close), _) => self.active_window = Windows::Main,
+                    (Some(Action::NextTab), _) => self.browser.next_tab(),
+                    (Some(Action::PreviousTab), _) => self.browser.previous_tab(),
+                    (Some(Action::Up), _) => self.browser.index_up(),
+                    (Some(Action::Down), _) => self.browser.index_down(),
+                    (Some(Action::Refresh), _) => self.browser.refresh(&self.config),
+                    (_, KeyCode::Enter) if self.browser.tab == BrowserTab::Vhosts => self.browser.select_vhost(&self.config),
+                    (Some(Action::Add), _) | (_, KeyCode::Enter) => {
+                        match self.browser.selected_items(&self.config) {
+                            Ok(items) => self.add_items(items, ui),
+                            Err(e) => self.console_logs.push(format!("Cannot add to selector: {}", e))
+                        }
+                    },
+                    _ => {}
+                }
+                Windows::Topology => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (Some(Action::Close), _) => self.active_window = Windows::Main,
+                    (Some(Action::Up), _) => self.topology.scroll_up(),
+                    (Some(Action::Down), _) => self.topology.scroll_down(),
+                    (_, KeyCode::Enter) => self.topology.refresh(&self.config),
+                    (_, KeyCode::Char(c)) => self.topology.routing_key.push(c),
+                    (_, KeyCode::Backspace) => {
+                        self.topology.routing_key.pop();
+                    },
+                    _ => {}
+                }
+                //the keys that quit and open the diff close it too
+                Windows::MessageDiff => match (self.keymap.popup_action(key.code, false), self.keymap.action(&Mode::Scroll, key.code)) {
+                    (Some(Action::Close), _) | (_, Some(Action::Quit | Action::Diff)) => self.active_window = Windows::Main,
+                    (Some(Action::Up), _) => self.message_diff.scroll_up(1),
+                    (Some(Action::Down), _) => self.message_diff.scroll_down(1),
+                    (Some(Action::PageUp), _) => self.message_diff.scroll_up(10),
+                    (Some(Action::PageDown), _) => self.message_diff.scroll_down(10),
+                    _ => {}
+                }
+                Windows::RoutingTester => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (Some(Action::Close), _) => self.active_window = Windows::Main,
+                    (_, KeyCode::Tab | KeyCode::BackTab) => self.routing_tester.next_field(),
+                    (_, KeyCode::Enter) => self.routing_tester.add_routing_key(),
+                    (_, KeyCode::Char(c)) => self.routing_tester.push_char(c),
+                    (_, KeyCode::Backspace) => self.routing_tester.pop_char(),
+                    _ => {}
+                }
+                Windows::OperationInput => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (Some(Action::Close), _) => self.active_window = Windows::Main,
+                    (_, KeyCode::Char(c)) => self.operation_input.push(c),
+                    (_, KeyCode::Backspace) => {
+                        self.operation_input.pop();
+                    },
+                    (_, KeyCode::Enter) => {
+                        let args: Vec<&str> = self.operation_input.split_whitespace().collect();
+                        match Operation::parse(&args) {
+                            Ok(operation) => {
+                                self.active_window = Windows::ConfirmOperation;
+                                ui.show_confirm_popup(format!("{}? (y/n)", operation.describe()));
+                                self.pending_operation = Some(operation);
+                            }
+                            Err(e) => self.console_logs.push(format!("Operation Error: {}", e.to_string().replace('\n', " "))),
+                        }
+                    },
+                    _ => {}
+                }
+                Windows::MessageSearch => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (Some(Action::Close), _) => {
+                        self.active_window = Windows::Main;
+                        ui.cancel_search();
+                    },
+                    (_, KeyCode::Enter) => self.active_window = Windows::Main,
+                    (_, KeyCode::Char(c)) => {
+                        ui.search.push_char(c);
+                        ui.update_search();
+                    },
+                    (_, KeyCode::Backspace) => {
+                        ui.search.pop_char();
+                        ui.update_search();
+                    },
+                    _ => {}
+                }
+                Windows::ConfirmOperation => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (_, KeyCode::Char('y') | KeyCode::Char('Y')) => {
+                        self.active_window = Windows::Main;
+                        ui.hide_confirm_popup();
+                        if let Some(operation) = self.pending_operation.take() {
+                            match self.ampq.run_operation(&operation) {
+                                Ok(res) => {
+                                    self.console_logs.push(res);
+                                    self.operation_input.clear();
+                                }
+                                Err(e) => self.console_logs.push(format!("Operation Error: {}", e))
                             }
                         }
                     },
+                    (Some(Action::Close), _) | (_, KeyCode::Char('n') | KeyCode::Char('N')) => {
+                        self.active_window = Windows::OperationInput;
+                        ui.hide_confirm_popup();
+                        self.pending_operation = None;
+                    },
+                    _ => {}
+                }
+                Windows::ConfirmDelete => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (_, KeyCode::Char('y') | KeyCode::Char('Y')) => {
+                        self.active_window = Windows::Main;
+                        ui.hide_confirm_popup();
+                        let selected_id = ui.get_selected_item_id();
+                        self.delete_item(selected_id);
+                    },
+                    (Some(Action::Close), _) | (_, KeyCode::Char('n') | KeyCode::Char('N')) => {
+                        self.active_window = Windows::Main;
+                        ui.hide_confirm_popup();
+                    },
                     _ => {}
                 }
-                Windows::OptionsStringInput => match key.code {
-                    KeyCode::Enter => {
+                Windows::OptionsStringInput => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (_, KeyCode::Enter) => {
                         self.active_window = Windows::Options;
                         ui.set_option_value_string()
                     },
-                    KeyCode::Char(c) => ui.string_input.push(c),
-                    KeyCode::Backspace => {
+                    (_, KeyCode::Char(c)) => ui.string_input.push(c),
+                    (_, KeyCode::Backspace) => {
                         ui.string_input.pop();
                     },
-                    KeyCode::Esc => {
+                    (Some(Action::Close), _) => {
                         self.active_window = Windows::Options;
                         ui.hide_string_input()
                     },
                     _ => {}
                 }
-                Windows::SelectionFilter => match key.code {
-                    KeyCode::Enter => self.active_window = Windows::Main,
-                    KeyCode::Char(c) => self.selection_filter.push(c),
-                    KeyCode::Backspace => {
+                Windows::SelectionFilter => match (self.keymap.popup_action(key.code, true), key.code) {
+                    (Some(Action::Close), _) | (_, KeyCode::Enter) => self.active_window = Windows::Main,
+                    (_, KeyCode::Char(c)) => self.selection_filter.push(c),
+                    (_, KeyCode::Backspace) => {
                         self.selection_filter.pop();
                     },
-                    KeyCode::Esc => self.active_window = Windows::Main,
                     _ => {}
                 }
-                Windows::MultiSelectInput => match key.code {
-                    KeyCode::Down => ui.multi_select_index_down(),
-                    KeyCode::Up => ui.multi_select_index_up(),
-                    KeyCode::Char('j') => ui.multi_select_index_down(), //VIM binding
-                    KeyCode::Char('k') => ui.multi_select_index_up(), //VIM binding
-                    KeyCode::Enter => {
+                Windows::MultiSelectInput => match (self.keymap.popup_action(key.code, false), key.code) {
+                    (Some(Action::Down), _) => ui.multi_select_index_down(),
+                    (Some(Action::Up), _) => ui.multi_select_index_up(),
+                    (_, KeyCode::Enter) => {
                         ui.multi_select_change_value();
                         self.active_window = Windows::Options
                     },
-                    KeyCode::Esc => {
+                    (Some(Action::Close), _) => {
                         ui.hide_multi_select_input();
                         self.active_window = Windows::Options
                     },
@@ -293,16 +571,117 @@ impl App {
         Ok(false)
     }
 
+    fn toggle_subscription(&mut self, selected_id: Uuid) {
+        match self.config.items
+            .iter_mut()
+            .find(|x| x.id == selected_id) {
+            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
+            Some(selected_item) => {
+                if selected_item.selected_state == SelectedState::Unselected {
+                    selected_item.selected_state = SelectedState::PendingSubscription;
+                }
+                else if selected_item.selected_state == SelectedState::Subscribed {
+                    selected_item.selected_state = SelectedState::Unselected;
+                }
+                else if selected_item.selected_state == SelectedState::PendingSubscription {
+                    selected_item.selected_state = SelectedState::Unselected;
+                    //a server named queue that is not declared yet has nothing to delete
+                    if let Some(queue_name) = self.ampq.queue_name(selected_id) {
+                        match self.ampq.create_channel() {
+                            Ok(channel) => self.ampq.delete_queue(queue_name.as_str(), &channel),
+                            Err(e) => self.console_logs.push(format!("Error creating channel: {}", e))
+                        }
+                    }
+                }
+
+                if let Err(e) = self.ampq.change_subscription(selected_item, selected_id) {
+                    selected_item.selected_state = SelectedState::Unselected;
+                    self.console_logs.push(format!("Cannot subscribe to {}: {}", selected_item.display_name(), e));
+                }
+            }
+        };
+    }
+
+    fn search_jump(&mut self, ui: &mut Ui, older: bool) {
+        if !ui.search.is_active() {
+            self.console_logs.push("Search with / first".to_string());
+        }
+        else if !ui.search_jump(older) {
+            self.console_logs.push(format!("No match for {}", ui.search.query));
+        }
+    }
+
+    fn toggle_column_view(&mut self, ui: &mut Ui, paused: bool) {
+        let selected_id = ui.get_selected_item_id();
+        match self.config.items.iter().find(|x| x.id == selected_id) {
+            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
+            Some(selected_item) => {
+                if let Err(e) = ui.toggle_column_view(selected_item, paused) {
+                    self.console_logs.push(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Digits sort the column view by the column with that number.
+    fn sort_column_view(&mut self, ui: &mut Ui, code: KeyCode) {
+        if let (Some(column_view), KeyCode::Char(c)) = (ui.column_view.as_mut(), code) {
+            if c.is_ascii_digit() {
+                column_view.sort_by((c as usize).saturating_sub('1' as usize));
+            }
+        }
+    }
+
+    pub fn filtered_count(&self, selected_id: Uuid) -> usize {
+        self.ampq.filtered_count(selected_id)
+    }
+
+    fn add_items(&mut self, items: Vec<ExchangeOptions>, ui: &mut Ui) {
+        for item in items {
+            let exists = self.config.items
+                .iter()
+                .any(|x| x.exchange_name == item.exchange_name && x.queue_routing_key == item.queue_routing_key);
+
+            if exists {
+                self.console_logs.push(format!("Already in selector: {} {}", item.exchange_name, item.queue_routing_key));
+                continue;
+            }
+
+            self.console_logs.push(format!("Added: {} {}", item.exchange_name, item.queue_routing_key));
+            ui.select_item(item.id);
+            self.config.items.push(item);
+        }
+    }
+
+    fn delete_item(&mut self, selected_id: Uuid) {
+        let subscribed = match self.config.items.iter().find(|x| x.id == selected_id) {
+            None => {
+                self.console_logs.push("Cannot find selected item in config.items".to_string());
+                return;
+            }
+            Some(selected_item) => selected_item.selected_state != SelectedState::Unselected
+        };
+
+        if subscribed {
+            self.toggle_subscription(selected_id);
+        }
+
+        if let Some(position) = self.config.items.iter().position(|x| x.id == selected_id) {
+            let removed = self.config.items.remove(position);
+            self.console_logs.push(format!("Deleted: {}", removed.display_name()));
+        }
+    }
+
     fn send_publish_to_amqp(&mut self, ui: &mut Ui) {
         let selected_id = ui.get_selected_item_id();
         match self.config.items
             .iter()
             .find(|x| x.id == selected_id) {
-            None => self.console_logs.push(format!("Cannot find selected item in config.items")),
+            None => self.console_logs.push("Cannot find selected item in config.items".to_string()),
             Some(selected_item) => {
                 self.ampq.publish(selected_item)
                     .unwrap_or_else(|e| {
-                        self.console_logs.push(format!("Error publishing message: {}", e.to_string()));
+                        self.console_logs.push(format!("Error publishing message: {}", e));
                     });
             }
         };
@@ -313,13 +692,19 @@ impl App {
             self.console_logs.push(res);
         }
 
-        if crate::amqp::PAUSE.load(Ordering::SeqCst) == false {
+        self.browser.poll();
+        self.topology.poll();
+
+        if !crate::amqp::PAUSE.load(Ordering::SeqCst) {
             self.file_logger.tick(self.tick_rate);
         }
     }
 
     fn exit(&mut self) {
         self.ampq.delete_remaining_queue().ok();
-        self.config.save_config().ok();
+        //YAML loses its comments here too, but not the items added, cloned or deleted
+        if self.config.save_on_exit {
+            self.config.save_config().ok();
+        }
     }
 }
\ No newline at end of file
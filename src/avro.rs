@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::fs;
+use anyhow::{Result, Context, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Map, Value};
+
+/// An Avro schema read from a `.avsc` file, enough to decode the binary encoding of a single
+/// datum into JSON. Logical types are shown as their underlying type.
+pub struct AvroSchema {
+    root: Schema,
+    /// Records, enums and fixed types by full and short name, for references to them.
+    named: HashMap<String, Schema>
+}
+
+#[derive(Clone)]
+enum Schema {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Record(Vec<(String, Schema)>),
+    Enum(Vec<String>),
+    Array(Box<Schema>),
+    Map(Box<Schema>),
+    Union(Vec<Schema>),
+    Fixed(usize),
+    Reference(String)
+}
+
+/// Marks the single object encoding, followed by an 8 byte schema fingerprint.
+const SINGLE_OBJECT_MARKER: [u8; 2] = [0xc3, 0x01];
+
+/// Items of arrays whose items take no bytes, e.g. `null`, that one message can hold. Any other
+/// item takes at least a byte, so the message's length already limits them.
+const MAX_EMPTY_ITEMS: usize = 1_000_000;
+
+impl AvroSchema {
+    pub fn read(path: &str) -> Result<Self> {
+        let contents = fs::read_to_string(path).with_context(|| format!("Reading {}", path))?;
+        let json: Value = serde_json::from_str(contents.as_str()).with_context(|| format!("Parsing {}", path))?;
+
+        Self::from_json(&json).with_context(|| format!("Reading Avro schema {}", path))
+    }
+
+    fn from_json(json: &Value) -> Result<Self> {
+        let mut named = HashMap::new();
+        let root = parse(json, None, &mut named)?;
+
+        Ok(AvroSchema { root, named })
+    }
+
+    pub fn decode(&self, body: &[u8]) -> Result<Value> {
+        let mut input = body;
+        if input.starts_with(&SINGLE_OBJECT_MARKER) && input.len() >= 10 {
+            input = &input[10..];
+        }
+
+        let mut empty_items = MAX_EMPTY_ITEMS;
+        let value = self.decode_value(&self.root, &mut input, &mut empty_items)?;
+        if !input.is_empty() {
+            return Err(anyhow!("{} bytes left after the datum, the schema does not match", input.len()));
+        }
+
+        Ok(value)
+    }
+
+    /// `empty_items` is what is left of `MAX_EMPTY_ITEMS`.
+    fn decode_value(&self, schema: &Schema, input: &mut &[u8], empty_items: &mut usize) -> Result<Value> {
+        let value = match schema {
+            Schema::Null => Value::Null,
+            Schema::Boolean => Value::Bool(take(input, 1)?[0] != 0),
+            Schema::Int | Schema::Long => Value::from(read_long(input)?),
+            Schema::Float => Value::from(f32::from_le_bytes(take(input, 4)?.try_into()?)),
+            Schema::Double => Value::from(f64::from_le_bytes(take(input, 8)?.try_into()?)),
+            Schema::Bytes => {
+                let length = read_length(input)?;
+                Value::String(STANDARD.encode(take(input, length)?))
+            }
+            Schema::String => {
+                let length = read_length(input)?;
+                Value::String(String::from_utf8_lossy(take(input, length)?).to_string())
+            }
+            Schema::Record(fields) => {
+                let mut map = Map::new();
+                for (name, field) in fields {
+                    map.insert(name.clone(), self.decode_value(field, input, empty_items)?);
+                }
+                Value::Object(map)
+            }
+            Schema::Enum(symbols) => {
+                let index = read_long(input)?;
+                let symbol = symbols.get(index as usize).ok_or_else(|| anyhow!("Enum index {} out of range", index))?;
+                Value::String(symbol.clone())
+            }
+            Schema::Array(items) => {
+                let mut list = vec![];
+                let empty = self.is_empty(items, 0);
+                while let Some(count) = read_block(input, !empty)? {
+                    if empty {
+                        *empty_items = empty_items.checked_sub(count).ok_or_else(|| anyhow!("More than {} empty array items", MAX_EMPTY_ITEMS))?;
+                    }
+                    for _ in 0..count {
+                        list.push(self.decode_value(items, input, empty_items)?);
+                    }
+                }
+                Value::Array(list)
+            }
+            Schema::Map(values) => {
+                let mut map = Map::new();
+                //every entry has at least the length of its key
+                while let Some(count) = read_block(input, true)? {
+                    for _ in 0..count {
+                        let length = read_length(input)?;
+                        let key = String::from_utf8_lossy(take(input, length)?).to_string();
+                        map.insert(key, self.decode_value(values, input, empty_items)?);
+                    }
+                }
+                Value::Object(map)
+            }
+            Schema::Union(branches) => {
+                let index = read_long(input)?;
+                let branch = branches.get(index as usize).ok_or_else(|| anyhow!("Union index {} out of range", index))?;
+                self.decode_value(branch, input, empty_items)?
+            }
+            Schema::Fixed(size) => Value::String(STANDARD.encode(take(input, *size)?)),
+            Schema::Reference(name) => {
+                let schema = self.named.get(name).ok_or_else(|| anyhow!("Unknown type {}", name))?;
+                self.decode_value(schema, input, empty_items)?
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Whether values of `schema` are written as no bytes at all.
+    fn is_empty(&self, schema: &Schema, depth: usize) -> bool {
+        match schema {
+            Schema::Null | Schema::Fixed(0) => true,
+            Schema::Record(fields) => fields.iter().all(|x| self.is_empty(&x.1, depth + 1)),
+            //a record cannot hold itself without a union or array in between, which take bytes
+            Schema::Reference(name) if depth < 32 => self.named.get(name).map(|x| self.is_empty(x, depth + 1)).unwrap_or(false),
+            _ => false
+        }
+    }
+}
+
+fn parse(json: &Value, namespace: Option<&str>, named: &mut HashMap<String, Schema>) -> Result<Schema> {
+    match json {
+        Value::String(name) => Ok(primitive(name).unwrap_or_else(|| Schema::Reference(full_name(name, namespace)))),
+        Value::Array(branches) => Ok(Schema::Union(branches.iter().map(|x| parse(x, namespace, named)).collect::<Result<Vec<Schema>>>()?)),
+        Value::Object(object) => {
+            let type_name = match object.get("type") {
+                Some(Value::String(res)) => res.as_str(),
+                //e.g. {"type": {"type": "array", ...}}
+                Some(other) => return parse(other, namespace, named),
+                None => return Err(anyhow!("Schema without a type: {}", json))
+            };
+
+            if let Some(res) = primitive(type_name) {
+                return Ok(res);
+            }
+
+            let name = object.get("name").and_then(|x| x.as_str());
+            let namespace = object.get("namespace").and_then(|x| x.as_str()).or(namespace);
+
+            let schema = match type_name {
+                "record" | "error" => {
+                    let fields = object.get("fields").and_then(|x| x.as_array()).ok_or_else(|| anyhow!("Record {} without fields", name.unwrap_or_default()))?;
+                    let inner_namespace = name.filter(|x| x.contains('.')).map(|x| x[..x.rfind('.').unwrap()].to_string());
+                    let inner_namespace = inner_namespace.as_deref().or(namespace);
+
+                    let mut list = vec![];
+                    for field in fields {
+                        let field_name = field.get("name").and_then(|x| x.as_str()).ok_or_else(|| anyhow!("Field without a name: {}", field))?;
+                        let field_type = field.get("type").ok_or_else(|| anyhow!("Field {} without a type", field_name))?;
+                        list.push((field_name.to_string(), parse(field_type, inner_namespace, named)?));
+                    }
+                    Schema::Record(list)
+                }
+                "enum" => {
+                    let symbols = object.get("symbols").and_then(|x| x.as_array()).ok_or_else(|| anyhow!("Enum {} without symbols", name.unwrap_or_default()))?;
+                    Schema::Enum(symbols.iter().filter_map(|x| x.as_str()).map(|x| x.to_string()).collect())
+                }
+                "array" => Schema::Array(Box::new(parse(object.get("items").ok_or_else(|| anyhow!("Array without items"))?, namespace, named)?)),
+                "map" => Schema::Map(Box::new(parse(object.get("values").ok_or_else(|| anyhow!("Map without values"))?, namespace, named)?)),
+                "fixed" => Schema::Fixed(object.get("size").and_then(|x| x.as_u64()).ok_or_else(|| anyhow!("Fixed {} without a size", name.unwrap_or_default()))? as usize),
+                other => return Err(anyhow!("Unknown type {}", other))
+            };
+
+            if let Some(name) = name {
+                named.insert(full_name(name, namespace), schema.clone());
+                named.insert(name.rsplit('.').next().unwrap_or(name).to_string(), schema.clone());
+            }
+
+            Ok(schema)
+        }
+        _ => Err(anyhow!("Not a schema: {}", json))
+    }
+}
+
+fn primitive(name: &str) -> Option<Schema> {
+    match name {
+        "null" => Some(Schema::Null),
+        "boolean" => Some(Schema::Boolean),
+        "int" => Some(Schema::Int),
+        "long" => Some(Schema::Long),
+        "float" => Some(Schema::Float),
+        "double" => Some(Schema::Double),
+        "bytes" => Some(Schema::Bytes),
+        "string" => Some(Schema::String),
+        _ => None
+    }
+}
+
+fn full_name(name: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(res) if !name.contains('.') && !res.is_empty() => format!("{}.{}", res, name),
+        _ => name.to_string()
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], length: usize) -> Result<&'a [u8]> {
+    if input.len() < length {
+        return Err(anyhow!("Unexpected end of the message"));
+    }
+
+    let (taken, rest) = input.split_at(length);
+    *input = rest;
+
+    Ok(taken)
+}
+
+/// A zigzag encoded variable length integer.
+fn read_long(input: &mut &[u8]) -> Result<i64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = take(input, 1)?[0];
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+
+        shift += 7;
+        if shift > 63 {
+            return Err(anyhow!("Integer longer than 64 bits"));
+        }
+    }
+
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
+
+fn read_length(input: &mut &[u8]) -> Result<usize> {
+    let length = read_long(input)?;
+    usize::try_from(length).map_err(|_| anyhow!("Negative length {}", length))
+}
+
+/// The item count of the next block of an array or map, `None` at the end. A negative count is
+/// followed by the size of the block in bytes. When each item takes at least a byte, counts
+/// larger than what is left of the message are refused before anything is decoded.
+fn read_block(input: &mut &[u8], items_take_bytes: bool) -> Result<Option<usize>> {
+    let count = read_long(input)?;
+
+    if count == 0 {
+        return Ok(None);
+    }
+    if count < 0 {
+        read_long(input)?;
+    }
+
+    let count = usize::try_from(count.unsigned_abs()).map_err(|_| anyhow!("Block of {} items is too large", count))?;
+    if items_take_bytes && count > input.len() {
+        return Err(anyhow!("Block of {} items in {} bytes, the message is malformed", count, input.len()));
+    }
+
+    Ok(Some(count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema(json: Value) -> AvroSchema {
+        AvroSchema::from_json(&json).unwrap()
+    }
+
+    fn long(value: i64) -> Vec<u8> {
+        let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+        let mut bytes = vec![];
+        loop {
+            if zigzag < 0x80 {
+                bytes.push(zigzag as u8);
+                return bytes;
+            }
+            bytes.push((zigzag as u8 & 0x7f) | 0x80);
+            zigzag >>= 7;
+        }
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        [long(value.len() as i64), value.as_bytes().to_vec()].concat()
+    }
+
+    #[test]
+    fn record_with_primitives() {
+        let schema = schema(json!({"type": "record", "name": "Order", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "status", "type": "string"},
+            {"name": "paid", "type": "boolean"},
+            {"name": "total", "type": "double"}
+        ]}));
+        let body = [long(-300), string("FAILED"), vec![1], 2.5f64.to_le_bytes().to_vec()].concat();
+
+        assert_eq!(schema.decode(&body).unwrap(), json!({"id": -300, "status": "FAILED", "paid": true, "total": 2.5}));
+        assert!(schema.decode(&[body.clone(), vec![0]].concat()).err().unwrap().to_string().starts_with("1 bytes left"));
+        assert!(schema.decode(&body[..body.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn union_and_enum() {
+        let schema = schema(json!({"type": "record", "name": "Event", "fields": [
+            {"name": "note", "type": ["null", "string"]},
+            {"name": "level", "type": {"type": "enum", "name": "Level", "symbols": ["LOW", "HIGH"]}}
+        ]}));
+
+        assert_eq!(schema.decode(&[long(1), string("hi"), long(1)].concat()).unwrap(), json!({"note": "hi", "level": "HIGH"}));
+        assert_eq!(schema.decode(&[long(0), long(0)].concat()).unwrap(), json!({"note": null, "level": "LOW"}));
+        assert_eq!(schema.decode(&[long(2)].concat()).err().unwrap().to_string(), "Union index 2 out of range");
+        assert_eq!(schema.decode(&[long(0), long(5)].concat()).err().unwrap().to_string(), "Enum index 5 out of range");
+    }
+
+    #[test]
+    fn map_and_array_blocks() {
+        let schema = schema(json!({"type": "map", "values": {"type": "array", "items": "int"}}));
+        //one block with a positive count, the next with a negative count and its size in bytes
+        let body = [long(1), string("a"), long(2), long(1), long(2), long(0),
+            long(-1), long(4), string("b"), long(-1), long(1), long(3), long(0),
+            long(0)].concat();
+
+        assert_eq!(schema.decode(&body).unwrap(), json!({"a": [1, 2], "b": [3]}));
+    }
+
+    #[test]
+    fn named_references_and_namespaces() {
+        let schema = schema(json!({"type": "record", "name": "Node", "namespace": "com.example", "fields": [
+            {"name": "value", "type": "int"},
+            {"name": "next", "type": ["null", "Node"]},
+            {"name": "id", "type": {"type": "fixed", "name": "Id", "size": 2}},
+            {"name": "other", "type": "com.example.Id"}
+        ]}));
+        let body = [long(1), long(1), long(2), long(0), vec![0, 1], vec![2, 3], vec![4, 5], vec![6, 7]].concat();
+
+        assert_eq!(schema.decode(&body).unwrap(), json!({"value": 1, "next": {"value": 2, "next": null, "id": "AAE=", "other": "AgM="}, "id": "BAU=", "other": "Bgc="}));
+    }
+
+    #[test]
+    fn single_object_encoding() {
+        let schema = schema(json!("string"));
+        let body = [SINGLE_OBJECT_MARKER.to_vec(), vec![0; 8], string("x")].concat();
+
+        assert_eq!(schema.decode(&body).unwrap(), json!("x"));
+    }
+
+    #[test]
+    fn huge_block_counts_are_refused() {
+        let schema_of_ints = schema(json!({"type": "array", "items": "int"}));
+        assert!(schema_of_ints.decode(&[long(i64::MAX / 2), long(1)].concat()).err().unwrap().to_string().starts_with("Block of"));
+
+        let schema_of_maps = schema(json!({"type": "map", "values": "null"}));
+        assert!(schema_of_maps.decode(&long(-1_000_000_000)).is_err());
+
+        //items that take no bytes are limited in total, even spread over nested arrays
+        let schema_of_nulls = schema(json!({"type": "array", "items": {"type": "array", "items": "null"}}));
+        assert_eq!(schema_of_nulls.decode(&[long(1), long(10), long(0), long(0)].concat()).unwrap(), json!([vec![Value::Null; 10]]));
+        let nested = [long(3), long(400_000), long(0), long(400_000), long(0), long(400_000), long(0), long(0)].concat();
+        assert_eq!(schema_of_nulls.decode(&nested).err().unwrap().to_string(), format!("More than {} empty array items", MAX_EMPTY_ITEMS));
+    }
+}
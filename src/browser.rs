@@ -0,0 +1,284 @@
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::Span;
+use tui::widgets::{Block, Borders, Clear, Row, Table, TableState};
+use crate::{Config, theme};
+use crate::management::SnapshotRequest;
+use crate::models::enums::ExchangeTypeSer;
+use crate::models::exchange_options::ExchangeOptions;
+use crate::models::management::ManagementSnapshot;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum BrowserTab {
+    Vhosts,
+    Exchanges,
+    Queues,
+    Bindings
+}
+
+impl BrowserTab {
+    pub fn iterator() -> impl Iterator<Item = BrowserTab> {
+        [BrowserTab::Vhosts, BrowserTab::Exchanges, BrowserTab::Queues, BrowserTab::Bindings].iter().copied()
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            BrowserTab::Vhosts => "Vhosts",
+            BrowserTab::Exchanges => "Exchanges",
+            BrowserTab::Queues => "Queues",
+            BrowserTab::Bindings => "Bindings"
+        }
+    }
+}
+
+/// Lists what the broker's management HTTP API knows about, so items can be added to the
+/// selector without typing exchange names into the config file.
+pub struct Browser {
+    pub tab: BrowserTab,
+    pub snapshot: Option<ManagementSnapshot>,
+    pub status: String,
+
+    index: usize,
+    vhost: Option<String>,
+    request: Option<SnapshotRequest>
+}
+
+impl Browser {
+    pub fn new() -> Self {
+        Browser {
+            tab: BrowserTab::Exchanges,
+            snapshot: None,
+            status: "".to_string(),
+            index: 0,
+            vhost: None,
+            request: None
+        }
+    }
+
+    /// Starts fetching in the background, the result is picked up by `poll`.
+    pub fn refresh(&mut self, config: &Config) {
+        match SnapshotRequest::spawn(config, self.vhost.clone()) {
+            Ok(request) => {
+                self.request = Some(request);
+                self.status = "Requesting...".to_string();
+            }
+            Err(e) => self.status = e.to_string()
+        }
+    }
+
+    pub fn poll(&mut self) {
+        let result = match self.request.as_ref().and_then(|x| x.try_take()) {
+            Some(res) => res,
+            None => return
+        };
+
+        self.request = None;
+
+        match result {
+            Ok(snapshot) => {
+                self.status = format!("Vhost {}: {} exchanges, {} queues, {} bindings", snapshot.vhost, snapshot.exchanges.len(), snapshot.queues.len(), snapshot.bindings.len());
+                self.snapshot = Some(snapshot);
+                self.index = 0;
+            }
+            Err(e) => self.status = format!("Error: {}", e)
+        }
+    }
+
+    pub fn next_tab(&mut self) {
+        let tabs: Vec<BrowserTab> = BrowserTab::iterator().collect();
+        let position = tabs.iter().position(|x| *x == self.tab).unwrap_or(0);
+        self.tab = tabs[(position + 1) % tabs.len()];
+        self.index = 0;
+    }
+
+    pub fn previous_tab(&mut self) {
+        let tabs: Vec<BrowserTab> = BrowserTab::iterator().collect();
+        let position = tabs.iter().position(|x| *x == self.tab).unwrap_or(0);
+        self.tab = tabs[(position + tabs.len() - 1) % tabs.len()];
+        self.index = 0;
+    }
+
+    pub fn index_up(&mut self) {
+        if self.index > 0 {
+            self.index -= 1;
+        }
+    }
+
+    pub fn index_down(&mut self) {
+        if self.index + 1 < self.row_count() {
+            self.index += 1;
+        }
+    }
+
+    /// Browses the vhost under the cursor on the Vhosts tab.
+    pub fn select_vhost(&mut self, config: &Config) {
+        let vhost = match self.snapshot.as_ref().and_then(|x| x.vhosts.get(self.index)) {
+            Some(res) => res.name.clone(),
+            None => return
+        };
+
+        self.vhost = Some(vhost);
+        self.tab = BrowserTab::Exchanges;
+        self.refresh(config);
+    }
+
+    /// Selector items for the row under the cursor. A queue adds one item per exchange it is bound to.
+    pub fn selected_items(&self, config: &Config) -> Result<Vec<ExchangeOptions>> {
+        let snapshot = self.snapshot.as_ref().ok_or_else(|| anyhow!("Nothing loaded yet"))?;
+
+        let config_vhost = match config.vhost.as_deref() {
+            Some(res) if !res.is_empty() => res,
+            _ => "/"
+        };
+        if snapshot.vhost != config_vhost {
+            return Err(anyhow!("Items can only be added from the configured vhost {}", config_vhost));
+        }
+
+        let exchange_type = |name: &str| -> Result<ExchangeTypeSer> {
+            snapshot.exchanges
+                .iter()
+                .find(|x| x.name == name)
+                .and_then(|x| x.exchange_type_ser())
+                .ok_or_else(|| anyhow!("Unsupported exchange: {}", name))
+        };
+
+        let item = |exchange_name: &str, queue_routing_key: String| -> Result<ExchangeOptions> {
+            Ok(ExchangeOptions {
+                exchange_name: exchange_name.to_string(),
+                exchange_type: exchange_type(exchange_name)?,
+                queue_routing_key,
+                ..ExchangeOptions::default()
+            })
+        };
+
+        match self.tab {
+            BrowserTab::Vhosts => Err(anyhow!("Select an exchange, queue or binding to add")),
+            BrowserTab::Exchanges => {
+                let exchange = snapshot.exchanges.get(self.index).ok_or_else(|| anyhow!("Nothing selected"))?;
+                let queue_routing_key = match exchange.exchange_type_ser() {
+                    Some(ExchangeTypeSer::Topic) => "#".to_string(),
+                    _ => "".to_string()
+                };
+
+                Ok(vec![item(exchange.name.as_str(), queue_routing_key)?])
+            }
+            BrowserTab::Queues => {
+                let queue = snapshot.queues.get(self.index).ok_or_else(|| anyhow!("Nothing selected"))?;
+
+                //the default exchange binds every queue by name and cannot be subscribed to
+                let items = snapshot.bindings
+                    .iter()
+                    .filter(|x| x.destination_type == "queue" && x.destination == queue.name && !x.source.is_empty())
+                    .map(|x| item(x.source.as_str(), x.routing_key.clone()))
+                    .collect::<Result<Vec<ExchangeOptions>>>()?;
+
+                if items.is_empty() {
+                    return Err(anyhow!("Queue {} is not bound to any exchange", queue.name));
+                }
+
+                Ok(items)
+            }
+            BrowserTab::Bindings => {
+                let binding = snapshot.bindings.get(self.index).ok_or_else(|| anyhow!("Nothing selected"))?;
+                if binding.source.is_empty() {
+                    return Err(anyhow!("The default exchange cannot be subscribed to"));
+                }
+
+                Ok(vec![item(binding.source.as_str(), binding.routing_key.clone())?])
+            }
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        match &self.snapshot {
+            None => 0,
+            Some(snapshot) => match self.tab {
+                BrowserTab::Vhosts => snapshot.vhosts.len(),
+                BrowserTab::Exchanges => snapshot.exchanges.len(),
+                BrowserTab::Queues => snapshot.queues.len(),
+                BrowserTab::Bindings => snapshot.bindings.len()
+            }
+        }
+    }
+
+    pub fn draw<B: Backend>(&self, frame: &mut Frame<B>, area: Rect) {
+        let (header, rows, widths): (Vec<&str>, Vec<Vec<String>>, Vec<Constraint>) = match &self.snapshot {
+            None => (vec![""], vec![], vec![Constraint::Percentage(100)]),
+            Some(snapshot) => match self.tab {
+                BrowserTab::Vhosts => (
+                    vec!["", "Name"],
+                    snapshot.vhosts
+                        .iter()
+                        .map(|x| vec![if x.name == snapshot.vhost { "*".to_string() } else { "".to_string() }, x.name.clone()])
+                        .collect(),
+                    vec![Constraint::Length(1), Constraint::Percentage(100)]
+                ),
+                BrowserTab::Exchanges => (
+                    vec!["Name", "Type"],
+                    snapshot.exchanges
+                        .iter()
+                        .map(|x| vec![if x.name.is_empty() { "(default)".to_string() } else { x.name.clone() }, x.exchange_type.clone()])
+                        .collect(),
+                    vec![Constraint::Percentage(70), Constraint::Percentage(30)]
+                ),
+                BrowserTab::Queues => (
+                    vec!["Name", "Messages", "Consumers", "In/s", "Out/s"],
+                    snapshot.queues
+                        .iter()
+                        .map(|x| vec![
+                            x.name.clone(),
+                            x.messages.map(|x| x.to_string()).unwrap_or_default(),
+                            x.consumers.map(|x| x.to_string()).unwrap_or_default(),
+                            format!("{:.1}", x.publish_rate()),
+                            format!("{:.1}", x.deliver_rate()),
+                        ])
+                        .collect(),
+                    vec![Constraint::Percentage(52), Constraint::Percentage(12), Constraint::Percentage(12), Constraint::Percentage(12), Constraint::Percentage(12)]
+                ),
+                BrowserTab::Bindings => (
+                    vec!["Source", "Destination", "Type", "Routing Key", "Arguments"],
+                    snapshot.bindings
+                        .iter()
+                        .map(|x| vec![
+                            if x.source.is_empty() { "(default)".to_string() } else { x.source.clone() },
+                            x.destination.clone(),
+                            x.destination_type.clone(),
+                            x.routing_key.clone(),
+                            match &x.arguments {
+                                Value::Object(res) if res.is_empty() => "".to_string(),
+                                Value::Null => "".to_string(),
+                                res => res.to_string()
+                            },
+                        ])
+                        .collect(),
+                    vec![Constraint::Percentage(25), Constraint::Percentage(25), Constraint::Percentage(10), Constraint::Percentage(20), Constraint::Percentage(20)]
+                ),
+            }
+        };
+
+        let tabs = BrowserTab::iterator()
+            .map(|x| if x == self.tab { format!("[{}]", x.title()) } else { x.title().to_string() })
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let title = format!("Browse {} | {}", tabs, self.status);
+
+        let table = Table::new(rows.into_iter().map(Row::new).collect::<Vec<Row>>())
+            .header(Row::new(header).style(Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD)))
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .highlight_style(Style::default().fg(theme::current().input))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled(title, Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
+            .widths(&widths)
+            .column_spacing(1);
+
+        let mut table_state = TableState::default();
+        table_state.select(Some(self.index));
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(table, area, &mut table_state);
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{Result, anyhow};
+use crate::operations::{self, Operation};
+
+pub enum CliCommand {
+    /// Start the TUI, optionally with the path to a config file.
+    Run { config_path: Option<String> },
+    ConfigValidate { config_path: Option<String> },
+    ConfigSchema,
+    /// Change exchanges, queues or bindings on the broker in the config file, after confirming.
+    Operation { operation: Operation, config_path: Option<String>, assume_yes: bool },
+    /// Write a RabbitMQ definitions file from the config's items, or from the broker.
+    DefinitionsExport { path: String, from_broker: bool, config_path: Option<String> },
+    /// Declare everything in a RabbitMQ definitions file, after confirming.
+    DefinitionsImport { path: String, config_path: Option<String>, assume_yes: bool },
+    /// Delete tap queues left behind by instances that did not exit normally, after confirming.
+    Cleanup { config_path: Option<String>, assume_yes: bool },
+    Help
+}
+
+pub fn parse_args(args: &[String]) -> Result<CliCommand> {
+    let args: Vec<&str> = args.iter().map(|x| x.as_str()).collect();
+
+    match args.as_slice() {
+        [] => Ok(CliCommand::Run { config_path: None }),
+        ["-h"] | ["--help"] | ["help"] => Ok(CliCommand::Help),
+        ["config", "validate"] => Ok(CliCommand::ConfigValidate { config_path: None }),
+        ["config", "validate", path] => Ok(CliCommand::ConfigValidate { config_path: Some(path.to_string()) }),
+        ["config", "schema"] => Ok(CliCommand::ConfigSchema),
+        ["config", ..] => Err(anyhow!("Unknown config command, expected: config validate [PATH] | config schema")),
+        [kind @ ("exchange" | "queue"), ..] => parse_operation(kind, &args),
+        ["definitions", ..] => parse_definitions(&args[1..]),
+        ["cleanup", ..] => {
+            let (rest, config_path, assume_yes) = split_common_options(&args[1..])?;
+            match rest.as_slice() {
+                [] => Ok(CliCommand::Cleanup { config_path, assume_yes }),
+                _ => Err(anyhow!("Unexpected arguments for cleanup: {}", rest.join(" ")))
+            }
+        }
+        [path] => Ok(CliCommand::Run { config_path: Some(path.to_string()) }),
+        _ => Err(anyhow!("Unexpected arguments: {}\n\n{}", args.join(" "), usage())),
+    }
+}
+
+fn parse_operation(kind: &str, args: &[&str]) -> Result<CliCommand> {
+    let (operation_args, config_path, assume_yes) = split_common_options(args)?;
+
+    let operation = Operation::parse(&operation_args)
+        .map_err(|e| anyhow!("{} command: {}", kind, e))?;
+
+    Ok(CliCommand::Operation { operation, config_path, assume_yes })
+}
+
+fn parse_definitions(args: &[&str]) -> Result<CliCommand> {
+    let (args, config_path, assume_yes) = split_common_options(args)?;
+
+    match args.as_slice() {
+        ["export", path] => Ok(CliCommand::DefinitionsExport { path: path.to_string(), from_broker: false, config_path }),
+        ["export", path, "--broker"] | ["export", "--broker", path] => Ok(CliCommand::DefinitionsExport { path: path.to_string(), from_broker: true, config_path }),
+        ["import", path] => Ok(CliCommand::DefinitionsImport { path: path.to_string(), config_path, assume_yes }),
+        _ => Err(anyhow!("Unknown definitions command, expected: definitions export FILE [--broker] | definitions import FILE")),
+    }
+}
+
+/// Takes out `--config PATH` and `--yes`, which can appear anywhere.
+fn split_common_options<'a>(args: &[&'a str]) -> Result<(Vec<&'a str>, Option<String>, bool)> {
+    let mut config_path = None;
+    let mut assume_yes = false;
+    let mut rest: Vec<&str> = vec![];
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match *arg {
+            "--config" => config_path = Some(iter.next().ok_or_else(|| anyhow!("--config needs a PATH"))?.to_string()),
+            "-y" | "--yes" => assume_yes = true,
+            _ => rest.push(arg)
+        }
+    }
+
+    Ok((rest, config_path, assume_yes))
+}
+
+pub fn usage() -> String {
+    let name = env!("CARGO_PKG_NAME");
+
+    [
+        "Usage:".to_string(),
+        format!("  {} [CONFIG]                 Start listening, with an optional config file path", name),
+        format!("  {} config validate [CONFIG] Report every problem in a config file", name),
+        format!("  {} config schema            Print the JSON Schema of the config file", name),
+        format!("  {} definitions export FILE [--broker] [--config CONFIG]", name),
+        "      Write the exchanges of the config's items, or everything on the broker's vhost, as RabbitMQ definitions".to_string(),
+        format!("  {} definitions import FILE [--config CONFIG] [--yes]", name),
+        "      Declare the exchanges, queues and bindings in a RabbitMQ definitions file".to_string(),
+        format!("  {} cleanup [--config CONFIG] [--yes]", name),
+        "      Delete queues left on the broker by instances that were killed".to_string(),
+        "".to_string(),
+        format!("Broker operations, asking for confirmation unless --yes is given ({} OPERATION [--config CONFIG] [--yes]):", name),
+        operations::usage().lines().map(|x| format!("  {}", x)).collect::<Vec<String>>().join("\n"),
+    ].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliCommand> {
+        parse_args(&args.iter().map(|x| x.to_string()).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn run_with_optional_config() {
+        assert!(matches!(parse(&[]).unwrap(), CliCommand::Run { config_path: None }));
+        assert!(matches!(parse(&["my.toml"]).unwrap(), CliCommand::Run { config_path: Some(res) } if res == "my.toml"));
+        assert!(matches!(parse(&["--help"]).unwrap(), CliCommand::Help));
+    }
+
+    #[test]
+    fn config_commands() {
+        assert!(matches!(parse(&["config", "validate"]).unwrap(), CliCommand::ConfigValidate { config_path: None }));
+        assert!(matches!(parse(&["config", "validate", "a.yaml"]).unwrap(), CliCommand::ConfigValidate { config_path: Some(res) } if res == "a.yaml"));
+        assert!(matches!(parse(&["config", "schema"]).unwrap(), CliCommand::ConfigSchema));
+        assert!(parse(&["config", "check"]).is_err());
+    }
+
+    #[test]
+    fn common_options_can_appear_anywhere() {
+        let command = parse(&["definitions", "--yes", "import", "defs.json", "--config", "c.json"]).unwrap();
+        assert!(matches!(command, CliCommand::DefinitionsImport { path, config_path: Some(config), assume_yes: true } if path == "defs.json" && config == "c.json"));
+
+        let command = parse(&["definitions", "export", "--broker", "defs.json"]).unwrap();
+        assert!(matches!(command, CliCommand::DefinitionsExport { path, from_broker: true, config_path: None } if path == "defs.json"));
+
+        assert!(matches!(parse(&["cleanup", "-y"]).unwrap(), CliCommand::Cleanup { config_path: None, assume_yes: true }));
+        assert!(parse(&["cleanup", "now"]).is_err());
+        assert!(parse(&["cleanup", "--config"]).is_err());
+    }
+
+    #[test]
+    fn operations() {
+        let command = parse(&["queue", "purge", "orders", "--yes"]).unwrap();
+        assert!(matches!(command, CliCommand::Operation { config_path: None, assume_yes: true, .. }));
+        assert!(parse(&["exchange", "explode", "orders"]).is_err());
+    }
+
+    #[test]
+    fn unexpected_arguments() {
+        assert!(parse(&["a.json", "b.json"]).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use anyhow::{Result, anyhow};
+use bevy_reflect::Uuid;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::Span;
+use tui::widgets::{Block, Borders, Cell, Row, Table, TableState};
+use crate::message_list::MessageRecord;
+use crate::models::exchange_options::ExchangeOptions;
+use crate::projection::Projection;
+use crate::theme;
+
+/// Columns wider than this are cut off.
+const MAX_COLUMN_WIDTH: usize = 30;
+
+/// One item's JSON messages as a table: the time, the routing key and a column for each of the
+/// item's fields, sortable by any column.
+pub struct ColumnView {
+    pub item_id: Uuid,
+    name: String,
+    fields: Vec<String>,
+    /// Index into the columns, the time and routing key first.
+    sort_column: usize,
+    descending: bool,
+    /// Row in sorted order; the last one while `None`.
+    selected: Option<usize>
+}
+
+impl ColumnView {
+    /// Fails for items without fields, which have nothing to make columns of.
+    pub fn new(item: &ExchangeOptions) -> Result<Self> {
+        let projection = Projection::parse_option(item.fields.as_str())?
+            .ok_or_else(|| anyhow!("Set fields for {} to see its messages as columns", item.display_name()))?;
+
+        Ok(ColumnView {
+            item_id: item.id,
+            name: item.display_name().to_string(),
+            fields: projection.names(),
+            sort_column: 0,
+            descending: false,
+            selected: None
+        })
+    }
+
+    /// Sorts by `column`, or the other way around when it is already sorted by it.
+    pub fn sort_by(&mut self, column: usize) {
+        if column >= self.fields.len() + 2 {
+            return;
+        }
+
+        if column == self.sort_column {
+            self.descending = !self.descending;
+        }
+        else {
+            self.sort_column = column;
+            self.descending = false;
+        }
+    }
+
+    /// Stops following new messages and selects the last row.
+    pub fn hold(&mut self, records: &VecDeque<MessageRecord>) {
+        self.selected = self.rows(records).len().checked_sub(1);
+    }
+
+    pub fn follow(&mut self) {
+        self.selected = None;
+    }
+
+    pub fn select_up(&mut self, rows: usize) {
+        self.selected = self.selected.map(|x| x.saturating_sub(rows));
+    }
+
+    pub fn select_down(&mut self, rows: usize, records: &VecDeque<MessageRecord>) {
+        let last = self.rows(records).len().saturating_sub(1);
+        self.selected = self.selected.map(|x| (x + rows).min(last));
+    }
+
+    pub fn draw<B: Backend>(&mut self, frame: &mut Frame<B>, area: Rect, records: &VecDeque<MessageRecord>) {
+        let rows = self.rows(records);
+
+        let mut headers = vec!["Time".to_string(), "Routing Key".to_string()];
+        headers.extend(self.fields.iter().cloned());
+
+        let widths: Vec<Constraint> = headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| {
+                let widest = rows.iter().map(|x| x.1[i].as_deref().unwrap_or("-").chars().count()).max().unwrap_or_default();
+                //room for the column number and sort arrow
+                Constraint::Length(widest.max(header.chars().count() + 4).min(MAX_COLUMN_WIDTH) as u16)
+            })
+            .collect();
+
+        let header_cells: Vec<Cell> = headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| {
+                let arrow = match (i == self.sort_column, self.descending) {
+                    (true, false) => " ▲",
+                    (true, true) => " ▼",
+                    _ => ""
+                };
+                let number = if i < 9 { format!("{} ", i + 1) } else { "".to_string() };
+                Cell::from(format!("{}{}{}", number, header, arrow))
+            })
+            .collect();
+
+        let table_rows: Vec<Row> = rows
+            .iter()
+            .map(|(_, cells)| Row::new(cells.iter().map(|x| Cell::from(x.clone().unwrap_or_else(|| "-".to_string())))))
+            .collect();
+
+        let mut state = TableState::default();
+        state.select(self.selected.or_else(|| rows.len().checked_sub(1)));
+
+        let title = format!("{} ({} messages)", self.name, rows.len());
+        let table = Table::new(table_rows)
+            .header(Row::new(header_cells).style(Style::default().fg(theme::current().accent)))
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled(title, Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
+            .highlight_style(Style::default().fg(theme::current().input))
+            .highlight_symbol(">")
+            .widths(&widths)
+            .column_spacing(2);
+
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+
+    /// The item's JSON messages as cells in sorted order, with the index of their record.
+    fn rows(&self, records: &VecDeque<MessageRecord>) -> Vec<(usize, Vec<Option<String>>)> {
+        let mut rows: Vec<(usize, Vec<Option<String>>)> = records
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.item_id == self.item_id)
+            .filter_map(|(i, record)| {
+                let fields = record.fields.as_ref()?;
+
+                let mut cells = vec![Some(record.timestamp.format("%H:%M:%S%.3f").to_string()), Some(record.routing_key.clone())];
+                //by name, the item's fields can have changed since the message came
+                cells.extend(self.fields.iter().map(|name| fields.iter().find(|x| x.0 == *name).and_then(|x| x.1.clone())));
+
+                Some((i, cells))
+            })
+            .collect();
+
+        let column = self.sort_column;
+        rows.sort_by(|a, b| match column {
+            //records are kept in the order they came in
+            0 if self.descending => b.0.cmp(&a.0),
+            0 => a.0.cmp(&b.0),
+            _ => compare_cells(&a.1[column], &b.1[column], self.descending)
+        });
+
+        rows
+    }
+}
+
+/// Numbers by value and anything else as text, with missing values last either way.
+fn compare_cells(a: &Option<String>, b: &Option<String>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let ordering = match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                _ => a.cmp(b)
+            };
+            if descending { ordering.reverse() } else { ordering }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal
+    }
+}
@@ -0,0 +1,142 @@
+use std::io::{Read, Write};
+use anyhow::{Result, anyhow};
+use flate2::Compression as Level;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+pub const COMPRESSION_NAMES: [&str; 3] = ["gzip", "zstd", "deflate"];
+
+/// The most a message body is decompressed to.
+pub const MAX_DECOMPRESSED: u64 = 64 * 1024 * 1024;
+
+/// A content encoding that compresses the body.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    /// zlib, as HTTP uses it; raw deflate streams are read too.
+    Deflate
+}
+
+impl Compression {
+    /// The codec for a `content_encoding` or `publish_encoding` option. Empty, `identity` and
+    /// `none` leave the body as it is.
+    pub fn parse(name: &str) -> Result<Option<Self>> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "identity" | "none" => Ok(None),
+            other => Self::from_header(other).map(Some).ok_or_else(|| anyhow!("Unknown compression {}, expected one of {}", other, COMPRESSION_NAMES.join(", ")))
+        }
+    }
+
+    /// The codec for a `content_encoding` property. Other encodings, like a character set some
+    /// producers put there, are not compression and give `None`.
+    pub fn from_header(content_encoding: &str) -> Option<Self> {
+        match content_encoding.trim().to_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Compression::Gzip),
+            "zstd" => Some(Compression::Zstd),
+            "deflate" | "zlib" => Some(Compression::Deflate),
+            _ => None
+        }
+    }
+
+    /// The item's `content_encoding` option wins over the message's property, so messages can be
+    /// decompressed when the producer does not set it, or left alone with `identity`.
+    pub fn for_message(item_encoding: &str, content_encoding: Option<&str>) -> Result<Option<Self>> {
+        if item_encoding.trim().is_empty() {
+            Ok(content_encoding.and_then(Self::from_header))
+        }
+        else {
+            Self::parse(item_encoding)
+        }
+    }
+
+    pub fn compress(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let compressed = match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Level::default());
+                encoder.write_all(body)?;
+                encoder.finish()?
+            }
+            Compression::Zstd => zstd::encode_all(body, 0)?,
+            Compression::Deflate => {
+                let mut encoder = ZlibEncoder::new(vec![], Level::default());
+                encoder.write_all(body)?;
+                encoder.finish()?
+            }
+        };
+
+        Ok(compressed)
+    }
+
+    /// Refuses bodies that decompress to more than `MAX_DECOMPRESSED` bytes, so a small message
+    /// cannot fill the memory.
+    pub fn decompress(&self, body: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => read_limited(GzDecoder::new(body)),
+            Compression::Zstd => read_limited(zstd::Decoder::new(body)?),
+            Compression::Deflate => read_limited(ZlibDecoder::new(body)).or_else(|_| read_limited(DeflateDecoder::new(body)))
+        }
+    }
+
+    /// The `content_encoding` property for bodies compressed with it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Deflate => "deflate"
+        }
+    }
+}
+
+fn read_limited(reader: impl Read) -> Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    reader.take(MAX_DECOMPRESSED + 1).read_to_end(&mut decompressed)?;
+
+    if decompressed.len() as u64 > MAX_DECOMPRESSED {
+        return Err(anyhow!("Decompresses to more than {} MiB, showing the compressed body", MAX_DECOMPRESSED / 1024 / 1024));
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let body = b"{\"order\": 42}".repeat(100);
+        for compression in [Compression::Gzip, Compression::Zstd, Compression::Deflate] {
+            let compressed = compression.compress(&body).unwrap();
+            assert!(compressed.len() < body.len());
+            assert_eq!(compression.decompress(&compressed).unwrap(), body);
+        }
+    }
+
+    #[test]
+    fn raw_deflate_is_read_too() {
+        let mut encoder = flate2::write::DeflateEncoder::new(vec![], Level::default());
+        encoder.write_all(b"raw").unwrap();
+
+        assert_eq!(Compression::Deflate.decompress(&encoder.finish().unwrap()).unwrap(), b"raw");
+    }
+
+    #[test]
+    fn bodies_that_decompress_too_far_are_refused() {
+        let bomb = Compression::Zstd.compress(&vec![0; MAX_DECOMPRESSED as usize + 1]).unwrap();
+        assert!(Compression::Zstd.decompress(&bomb).err().unwrap().to_string().starts_with("Decompresses to more than 64 MiB"));
+
+        let limit = Compression::Gzip.compress(&vec![0; MAX_DECOMPRESSED as usize]).unwrap();
+        assert_eq!(Compression::Gzip.decompress(&limit).unwrap().len() as u64, MAX_DECOMPRESSED);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Compression::parse(" GZIP ").unwrap(), Some(Compression::Gzip));
+        assert_eq!(Compression::parse("identity").unwrap(), None);
+        assert!(Compression::parse("brotli").is_err());
+        assert_eq!(Compression::from_header("utf-8"), None);
+        assert_eq!(Compression::for_message("", Some("x-gzip")).unwrap(), Some(Compression::Gzip));
+        assert_eq!(Compression::for_message("none", Some("gzip")).unwrap(), None);
+    }
+}
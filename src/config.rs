@@ -1,24 +1,70 @@
 use std::fs;
-use std::fs::{File};
+use std::fs::File;
+use std::io::Write;
 use std::path::Path;
+use serde::Serialize as _;
 use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use serde_json::ser::PrettyFormatter;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
 use anyhow::{Result, Context, anyhow};
 use bevy_reflect::Uuid;
 use crate::models::enums::SelectedState;
 use crate::models::exchange_options::{ExchangeOptions, ExchangeOptionsSer};
+use crate::projection::split_fields;
+use crate::secrets::PasswordSource;
+use crate::validate;
+
+pub const CONFIG_FILE_STEM: &str = "amqp-client-cli";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().map(|x| x.to_string_lossy().to_lowercase()).as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json
+        }
+    }
+
+    pub fn extensions() -> impl Iterator<Item = &'static str> {
+        ["json", "toml", "yaml", "yml"].iter().copied()
+    }
+}
 
 #[derive(Deserialize, Serialize, Debug, Default)]
 struct ConfigSer {
     pub host: String,
     pub port: u64,
     pub username: String,
-    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_command: Option<String>,
     pub vhost: Option<String>,
     pub pfx_path: Option<String>,
     pub pem_file: Option<String>,
     pub domain: Option<String>,
     pub items: Vec<ExchangeOptionsSer>,
     pub protocol: Option<String>,
+    pub save_on_exit: Option<bool>,
+    pub management_url: Option<String>,
+    pub queue_name_template: Option<String>,
+    pub server_named_queues: Option<bool>,
+    pub theme: Option<String>,
+    pub theme_file: Option<String>,
+    pub themes: Option<Map<String, Value>>,
+    pub keys: Option<Map<String, Value>>,
 }
 
 pub struct Config {
@@ -26,6 +72,7 @@ pub struct Config {
     pub port: u64,
     pub username: String,
     pub password: String,
+    pub password_source: PasswordSource,
     pub vhost: Option<String>,
     pub pfx_path: Option<String>,
     pub pem_file: Option<String>,
@@ -33,120 +80,480 @@ pub struct Config {
     pub items: Vec<ExchangeOptions>,
     pub path: String,
     pub protocol: String,
+    pub save_on_exit: bool,
+    /// RabbitMQ management HTTP API, e.g. `http://127.0.0.1:15672`, used to browse the broker.
+    pub management_url: Option<String>,
+    /// Name of the queue created for each subscription, see `amqp::DEFAULT_QUEUE_NAME_TEMPLATE`.
+    pub queue_name_template: Option<String>,
+    /// Let the broker name exclusive queues instead of using `queue_name_template`.
+    pub server_named_queues: bool,
+    /// Name of the theme drawn with, see `Themes`.
+    pub theme: Option<String>,
+    /// A file of named themes, JSON, TOML or YAML like the config.
+    pub theme_file: Option<String>,
+    /// Named themes, each an object of colours with the theme it starts from as `base`.
+    pub themes: Option<Map<String, Value>>,
+    /// Keys for the main window's actions, see `Keymap`.
+    pub keys: Option<Map<String, Value>>,
+
+    /// The file as it was read, so saving can keep unknown fields, key order and indentation.
+    document: Map<String, Value>,
+    /// The text of the file as it was read, which TOML configs are saved by editing in place so
+    /// their comments and layout are kept.
+    source: String,
+    format: ConfigFormat,
+    indent: String,
 }
 
 impl Config {
+    /// A config with default broker settings that has not been written to `file_path` yet.
+    pub fn new(file_path: &Path) -> Config {
+        Config {
+            host: "127.0.0.1".to_string(),
+            port: 5672,
+            username: "guest".to_string(),
+            password: "".to_string(),
+            password_source: PasswordSource::Prompt,
+            vhost: None,
+            pfx_path: None,
+            pem_file: None,
+            domain: None,
+            items: vec![],
+            path: file_path.to_string_lossy().to_string(),
+            protocol: "amqp".to_string(),
+            save_on_exit: true,
+            management_url: None,
+            queue_name_template: None,
+            server_named_queues: false,
+            theme: None,
+            theme_file: None,
+            themes: None,
+            keys: None,
+            document: Map::new(),
+            source: "".to_string(),
+            format: ConfigFormat::from_path(file_path),
+            indent: "  ".to_string()
+        }
+    }
+
     pub fn read_config(file_path: &Path) -> Result<Config> {
         if file_path.exists() && file_path.is_file() {
             let file_str = fs::read_to_string(file_path)?;
 
-            if file_str.len() > 0 {
-                let config_ser: ConfigSer = serde_json::from_str(file_str.as_ref()).with_context(|| "parsing qamqp-client-cli.json")?;
+            if !file_str.is_empty() {
+                let format = ConfigFormat::from_path(file_path);
+
+                let document = validate::parse_document(file_str.as_str(), format)
+                    .map_err(|e| anyhow!("parsing {}\n  {}", file_path.to_string_lossy(), e))?;
+
+                let config_ser: ConfigSer = match serde_json::from_value(Value::Object(document.clone())) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        let errors = validate::validate(file_str.as_str(), format);
+                        if errors.is_empty() {
+                            return Err(anyhow!("parsing {}\n  {}", file_path.to_string_lossy(), e));
+                        }
+
+                        let report = errors.iter().map(|x| format!("  {}", x)).collect::<Vec<String>>().join("\n");
+                        return Err(anyhow!("parsing {}\n{}", file_path.to_string_lossy(), report));
+                    }
+                };
+
+                let item_documents = match document.get("items") {
+                    Some(Value::Array(res)) => res.clone(),
+                    _ => vec![]
+                };
 
                 let mut exchanges: Vec<ExchangeOptions> = vec![];
 
-                for exchange_ser in config_ser.items {
+                for (exchange_ser, item_document) in config_ser.items.into_iter().zip(item_documents) {
                     let exchange = ExchangeOptions {
                         id: Uuid::new_v4(),
                         exchange_name: exchange_ser.exchange_name,
-                        exchange_type: exchange_ser.exchange_type.clone(),
+                        exchange_type: exchange_ser.exchange_type,
                         queue_routing_key: exchange_ser.queue_routing_key.unwrap_or_default(),
                         alias: exchange_ser.alias.unwrap_or_default(),
                         pretty: exchange_ser.pretty.unwrap_or_default(),
                         log_file: exchange_ser.log_file.unwrap_or_default(),
                         publish_file: exchange_ser.publish_file.unwrap_or_default(),
-                        selected_state: SelectedState::Unselected
+                        filter: exchange_ser.filter.unwrap_or_default(),
+                        decoder: exchange_ser.decoder.unwrap_or_default(),
+                        schema_file: exchange_ser.schema_file.unwrap_or_default(),
+                        content_encoding: exchange_ser.content_encoding.unwrap_or_default(),
+                        publish_encoding: exchange_ser.publish_encoding.unwrap_or_default(),
+                        fields: exchange_ser.fields.map(|x| x.join(", ")).unwrap_or_default(),
+                        log_format: exchange_ser.log_format.unwrap_or_default(),
+                        color: exchange_ser.color.unwrap_or_default(),
+                        selected_state: SelectedState::Unselected,
+                        document: match item_document {
+                            Value::Object(res) => res,
+                            _ => Map::new()
+                        }
                     };
 
                     exchanges.push(exchange);
                 }
 
+                let password_source = PasswordSource::from_fields(config_ser.password, config_ser.password_env, config_ser.password_file, config_ser.password_command)?;
+                let password = password_source.resolve(config_ser.username.as_str(), config_ser.host.as_str())?;
+
                 let config = Config {
                     host: config_ser.host,
                     port: config_ser.port,
                     username: config_ser.username,
-                    password: config_ser.password,
+                    password,
+                    password_source,
                     vhost: config_ser.vhost,
                     pfx_path: config_ser.pfx_path,
                     pem_file: config_ser.pem_file,
                     domain: config_ser.domain,
                     items: exchanges,
                     path: file_path.to_string_lossy().to_string(),
-                    protocol: config_ser.protocol.unwrap_or("amqp".to_owned())
+                    protocol: config_ser.protocol.unwrap_or_else(|| "amqp".to_owned()),
+                    save_on_exit: config_ser.save_on_exit.unwrap_or(true),
+                    management_url: config_ser.management_url,
+                    queue_name_template: config_ser.queue_name_template,
+                    server_named_queues: config_ser.server_named_queues.unwrap_or(false),
+                    theme: config_ser.theme,
+                    theme_file: config_ser.theme_file,
+                    themes: config_ser.themes,
+                    keys: config_ser.keys,
+                    document,
+                    indent: detect_indent(file_str.as_str()),
+                    source: file_str,
+                    format
                 };
 
                 return Ok(config);
             }
         }
 
-        return Err(anyhow!("Cannot read config file: {:?}", file_path));
+        Err(anyhow!("Cannot read config file: {:?}", file_path))
     }
-    
+
+    /// Whether saving keeps the file's comments and layout, which YAML configs lose because they
+    /// are written out again from their values.
+    pub fn keeps_layout(&self) -> bool {
+        self.format != ConfigFormat::Yaml
+    }
+
     pub fn save_config(&self) -> Result<()> {
-        let path = Path::new(self.path.as_str());
-        
-        if path.exists() == false {
-            File::create(path)?;
+        let mut document = self.document.clone();
+
+        set_field(&mut document, "host", Value::from(self.host.clone()), false);
+        set_field(&mut document, "port", Value::from(self.port), false);
+        set_field(&mut document, "username", Value::from(self.username.clone()), false);
+
+        //only the configured indirection is written back, never the resolved password
+        match &self.password_source {
+            PasswordSource::Plain(res) => set_field(&mut document, "password", Value::from(res.clone()), false),
+            PasswordSource::Env(res) => set_field(&mut document, "password_env", Value::from(res.clone()), false),
+            PasswordSource::File(res) => set_field(&mut document, "password_file", Value::from(res.clone()), false),
+            PasswordSource::Command(res) => set_field(&mut document, "password_command", Value::from(res.clone()), false),
+            PasswordSource::Prompt => {}
         }
-        
-        let mut exchanges_ser: Vec<ExchangeOptionsSer> = vec![];
+
+        set_optional_field(&mut document, "vhost", &self.vhost);
+        set_optional_field(&mut document, "pfx_path", &self.pfx_path);
+        set_optional_field(&mut document, "pem_file", &self.pem_file);
+        set_optional_field(&mut document, "domain", &self.domain);
+        set_field(&mut document, "protocol", Value::from(self.protocol.clone()), self.protocol == "amqp");
+        set_field(&mut document, "save_on_exit", Value::from(self.save_on_exit), self.save_on_exit);
+        set_optional_field(&mut document, "management_url", &self.management_url);
+        set_optional_field(&mut document, "queue_name_template", &self.queue_name_template);
+        set_field(&mut document, "server_named_queues", Value::from(self.server_named_queues), !self.server_named_queues);
+        set_optional_field(&mut document, "theme", &self.theme);
+        set_optional_field(&mut document, "theme_file", &self.theme_file);
+
+        let mut items: Vec<Value> = vec![];
 
         for item in self.items.iter() {
-            let mut alias = None;
-            if item.alias.len() > 0 {
-                alias = Some(item.alias.clone())
-            }
+            let mut item_document = item.document.clone();
+
+            set_field(&mut item_document, "exchange_name", Value::from(item.exchange_name.clone()), false);
+            set_field(&mut item_document, "exchange_type", serde_json::to_value(item.exchange_type)?, false);
+            set_field(&mut item_document, "queue_routing_key", Value::from(item.queue_routing_key.clone()), item.queue_routing_key.is_empty());
+            set_field(&mut item_document, "alias", Value::from(item.alias.clone()), item.alias.is_empty());
+            set_field(&mut item_document, "pretty", Value::from(item.pretty), !item.pretty);
+            set_field(&mut item_document, "log_file", Value::from(item.log_file.clone()), item.log_file.is_empty());
+            set_field(&mut item_document, "publish_file", Value::from(item.publish_file.clone()), item.publish_file.is_empty());
+            set_field(&mut item_document, "filter", Value::from(item.filter.clone()), item.filter.is_empty());
+            set_field(&mut item_document, "decoder", Value::from(item.decoder.clone()), item.decoder.is_empty());
+            set_field(&mut item_document, "schema_file", Value::from(item.schema_file.clone()), item.schema_file.is_empty());
+            set_field(&mut item_document, "content_encoding", Value::from(item.content_encoding.clone()), item.content_encoding.is_empty());
+            set_field(&mut item_document, "publish_encoding", Value::from(item.publish_encoding.clone()), item.publish_encoding.is_empty());
+            set_field(&mut item_document, "fields", Value::from(split_fields(item.fields.as_str())), item.fields.is_empty());
+            set_field(&mut item_document, "log_format", Value::from(item.log_format.clone()), item.log_format.is_empty());
+            set_field(&mut item_document, "color", Value::from(item.color.clone()), item.color.is_empty());
 
-            let pretty;
-            match item.pretty {
-                true => pretty = Some(true),
-                false => pretty = Some(false)
+            items.push(Value::Object(item_document));
+        }
+
+        document.insert("items".to_string(), Value::Array(items));
+
+        let contents = match self.format {
+            ConfigFormat::Json => {
+                let mut json = vec![];
+                let formatter = PrettyFormatter::with_indent(self.indent.as_bytes());
+                let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+                Value::Object(document).serialize(&mut serializer)?;
+                json.push(b'\n');
+                json
             }
+            ConfigFormat::Toml => {
+                let mut toml_document: DocumentMut = self.source.parse()?;
+                merge_toml_table(toml_document.as_table_mut(), &document, Some(&self.document));
+                toml_document.to_string().into_bytes()
+            }
+            ConfigFormat::Yaml => serde_yaml::to_string(&document)?.into_bytes(),
+        };
 
-            let mut log_file = None;
-            if item.log_file.len() > 0 {
-                log_file = Some(item.log_file.clone());
+        write_atomic(Path::new(self.path.as_str()), contents.as_slice())
+    }
+}
+
+/// Sets `key` unless `is_default` and the file never had it, so defaults are not added to a
+/// file that left them out and an explicit `null` stays `null` instead of becoming `""`.
+fn set_field(document: &mut Map<String, Value>, key: &str, value: Value, is_default: bool) {
+    match document.get_mut(key) {
+        Some(existing) => {
+            if !(is_default && existing.is_null()) {
+                *existing = value;
+            }
+        }
+        None => {
+            if !is_default {
+                document.insert(key.to_string(), value);
             }
+        }
+    }
+}
+
+fn set_optional_field(document: &mut Map<String, Value>, key: &str, value: &Option<String>) {
+    match value {
+        Some(res) => set_field(document, key, Value::from(res.clone()), false),
+        None => set_field(document, key, Value::Null, true)
+    }
+}
+
+/// Writes to a temporary file next to `path` and renames it over the original, so an
+/// interrupted save leaves the previous file in place. A symlinked file is replaced where the
+/// link points, and the new file keeps the permissions of the old one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    //files that do not exist yet are written where they are asked for
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid path: {:?}", path))?
+        .to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
 
-            let mut publish_file = None;
-            if item.publish_file.len() > 0 {
-                publish_file = Some(item.publish_file.clone());
+    let mut file = File::create(&temp_path)
+        .with_context(|| format!("Creating temporary file: {:?}", temp_path))?;
+    if let Ok(metadata) = fs::metadata(&path) {
+        fs::set_permissions(&temp_path, metadata.permissions())
+            .with_context(|| format!("Setting permissions of temporary file: {:?}", temp_path))?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+
+    fs::rename(&temp_path, &path)
+        .with_context(|| format!("Replacing file: {:?}", path))?;
+
+    Ok(())
+}
+
+/// Changes `table` to hold `values`, leaving everything that is the same as in `original`, the
+/// values the file was read with, as it was written. TOML has no null, so unset values are
+/// left out.
+fn merge_toml_table(table: &mut Table, values: &Map<String, Value>, original: Option<&Map<String, Value>>) {
+    let removed: Vec<String> = table
+        .iter()
+        .map(|x| x.0.to_string())
+        .filter(|x| values.get(x).map(|x| x.is_null()).unwrap_or(true))
+        .collect();
+    for key in removed {
+        table.remove(key.as_str());
+    }
+
+    for (key, value) in values.iter().filter(|x| !x.1.is_null()) {
+        let original = original.and_then(|x| x.get(key));
+        match table.get_mut(key) {
+            Some(item) => merge_toml_item(item, value, original),
+            None => {
+                table.insert(key, toml_item(value));
             }
+        }
+    }
+}
+
+fn merge_toml_item(item: &mut Item, value: &Value, original: Option<&Value>) {
+    if original == Some(value) {
+        return;
+    }
 
-            let mut queue_routing_key = None;
-            if item.queue_routing_key.len() > 0 {
-                queue_routing_key = Some(item.queue_routing_key.clone());
+    match (item, value) {
+        (Item::Table(table), Value::Object(map)) => merge_toml_table(table, map, original.and_then(|x| x.as_object())),
+        (Item::ArrayOfTables(tables), Value::Array(values)) if values.iter().all(|x| x.is_object()) => {
+            let originals = original.and_then(|x| x.as_array());
+            for (i, value) in values.iter().enumerate() {
+                let map = value.as_object().unwrap();
+                match tables.get_mut(i) {
+                    Some(table) => merge_toml_table(table, map, originals.and_then(|x| x.get(i)).and_then(|x| x.as_object())),
+                    None => tables.push(toml_table(map))
+                }
+            }
+            while tables.len() > values.len() {
+                tables.remove(tables.len() - 1);
             }
+        }
+        //a changed value keeps the comment after it
+        (Item::Value(existing), _) if !value.is_object() => {
+            let decor = existing.decor().clone();
+            *existing = toml_value(value);
+            *existing.decor_mut() = decor;
+        }
+        (item, _) => *item = toml_item(value)
+    }
+}
 
-            exchanges_ser.push(ExchangeOptionsSer {
-                exchange_name: item.exchange_name.clone(),
-                exchange_type: item.exchange_type.clone(),
-                queue_routing_key,
-                alias,
-                pretty,
-                log_file,
-                publish_file
-            });
+/// Objects become tables and lists of objects arrays of tables, like `toml::to_string_pretty`
+/// writes them.
+fn toml_item(value: &Value) -> Item {
+    match value {
+        Value::Object(map) => Item::Table(toml_table(map)),
+        Value::Array(values) if !values.is_empty() && values.iter().all(|x| x.is_object()) => {
+            let mut tables = ArrayOfTables::new();
+            for map in values.iter().filter_map(|x| x.as_object()) {
+                tables.push(toml_table(map));
+            }
+            Item::ArrayOfTables(tables)
         }
-        
-        let config_ser = ConfigSer {
-            host: self.host.clone(),
-            port: self.port,
-            username: self.username.clone(),
-            password: self.password.clone(),
-            vhost: self.vhost.clone(),
-            pfx_path: self.pfx_path.clone(),
-            pem_file: self.pem_file.clone(),
-            domain: self.domain.clone(),
-            items: exchanges_ser,
-            protocol: self.protocol.clone().into(),
-        };
-        
-        let json = serde_json::to_string_pretty(&config_ser)?;
+        _ => Item::Value(toml_value(value))
+    }
+}
 
-        fs::remove_file(self.path.as_str())?;
-        fs::write(self.path.as_str(), json)?;
+fn toml_table(map: &Map<String, Value>) -> Table {
+    let mut table = Table::new();
+    merge_toml_table(&mut table, map, None);
+    table
+}
 
-        Ok(())
+fn toml_value(value: &Value) -> toml_edit::Value {
+    match value {
+        Value::Bool(res) => (*res).into(),
+        Value::Number(res) => match res.as_i64() {
+            Some(res) => res.into(),
+            None => res.as_f64().unwrap_or_default().into()
+        },
+        Value::String(res) => res.as_str().into(),
+        Value::Array(values) => toml_edit::Value::Array(values.iter().filter(|x| !x.is_null()).map(toml_value).collect()),
+        Value::Object(map) => toml_edit::Value::InlineTable(map.iter().filter(|x| !x.1.is_null()).map(|(k, v)| (k.as_str(), toml_value(v))).collect()),
+        Value::Null => "".into()
     }
-}
\ No newline at end of file
+}
+
+fn detect_indent(file_str: &str) -> String {
+    file_str
+        .lines()
+        .skip(1)
+        .map(|line| line.chars().take_while(|c| *c == ' ' || *c == '\t').collect::<String>())
+        .find(|indent| !indent.is_empty())
+        .unwrap_or_else(|| "  ".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save_and_read(file_name: &str, source: &str, change: impl Fn(&mut Config)) -> String {
+        let path = std::env::temp_dir().join(format!("amqp-client-cli-test-{}-{}", std::process::id(), file_name));
+        fs::write(&path, source).unwrap();
+
+        let mut config = Config::read_config(&path).unwrap();
+        change(&mut config);
+        config.save_config().unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).ok();
+        saved
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_writes_keep_permissions_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let target = std::env::temp_dir().join(format!("amqp-client-cli-test-{}-target.json", std::process::id()));
+        let link = std::env::temp_dir().join(format!("amqp-client-cli-test-{}-link.json", std::process::id()));
+        fs::write(&target, "{}").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        fs::remove_file(&link).ok();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"{\"saved\": true}").unwrap();
+
+        let is_link = fs::symlink_metadata(&link).unwrap().file_type().is_symlink();
+        let mode = fs::metadata(&target).unwrap().permissions().mode() & 0o777;
+        let saved = fs::read_to_string(&target).unwrap();
+        fs::remove_file(&link).ok();
+        fs::remove_file(&target).ok();
+
+        assert!(is_link);
+        assert_eq!(mode, 0o600);
+        assert_eq!(saved, "{\"saved\": true}");
+    }
+
+    #[test]
+    fn toml_save_keeps_comments_and_layout() {
+        let source = r#"# broker
+host = "localhost"  # local
+port = 5672
+username = "guest"
+password = "guest"
+custom = { a = 1 }
+
+# listen to orders
+[[items]]
+exchange_name = "orders"
+exchange_type = "Topic"
+queue_routing_key = "order.*"  # every order
+"#;
+        let saved = save_and_read("keep.toml", source, |_| {});
+        assert_eq!(saved, source);
+
+        let saved = save_and_read("change.toml", source, |config| {
+            config.host = "broker".to_string();
+            config.items[0].queue_routing_key = "order.created".to_string();
+            config.items[0].pretty = true;
+        });
+        assert!(saved.starts_with("# broker\nhost = \"broker\"  # local\n"), "{}", saved);
+        assert!(saved.contains("# listen to orders\n[[items]]"), "{}", saved);
+        assert!(saved.contains("queue_routing_key = \"order.created\"  # every order\npretty = true\n"), "{}", saved);
+        assert!(saved.contains("custom = { a = 1 }"), "{}", saved);
+    }
+
+    #[test]
+    fn toml_save_adds_and_removes_items() {
+        let source = "host = \"localhost\"\nport = 5672\nusername = \"guest\"\npassword = \"guest\"\n\n[[items]]\nexchange_name = \"a\"\nexchange_type = \"Topic\"\n\n[[items]]\nexchange_name = \"b\"\nexchange_type = \"Fanout\"\nalias = \"bee\"\n";
+        let saved = save_and_read("items.toml", source, |config| {
+            config.items.remove(0);
+            config.items[0].alias = "".to_string();
+            let mut added = config.items[0].clone();
+            added.exchange_name = "c".to_string();
+            config.items.push(added);
+        });
+
+        let document: Map<String, Value> = toml::from_str(saved.as_str()).unwrap();
+        let names: Vec<&str> = document["items"].as_array().unwrap().iter().map(|x| x["exchange_name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["b", "c"]);
+        assert!(!saved.contains("bee"), "{}", saved);
+    }
+
+    #[test]
+    fn json_save_keeps_unknown_fields_and_indent() {
+        let source = "{\n    \"host\": \"localhost\",\n    \"port\": 5672,\n    \"username\": \"guest\",\n    \"password\": \"guest\",\n    \"extra\": [1, 2],\n    \"items\": []\n}\n";
+        let saved = save_and_read("keep.json", source, |config| config.port = 5673);
+        assert_eq!(saved, source.replace("5672", "5673").replace("[1, 2]", "[\n        1,\n        2\n    ]"));
+    }
+}
@@ -0,0 +1,402 @@
+use std::path::Path;
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ciborium::value::Value as CborValue;
+use protobuf::reflect::{FileDescriptor, MessageDescriptor, ReflectFieldRef, ReflectValueRef};
+use protobuf::MessageDyn;
+use rmpv::Value as MessagePackValue;
+use serde_json::{Map, Value};
+use crate::avro::AvroSchema;
+
+pub const DECODER_NAMES: [&str; 4] = ["msgpack", "cbor", "protobuf", "avro"];
+
+/// Turns bodies in a binary format into JSON, so they can be shown, filtered and logged like
+/// JSON messages. Byte strings become base64.
+pub enum Decoder {
+    MessagePack,
+    Cbor,
+    /// The message type comes from the decoder name (`protobuf:package.Message`), the
+    /// content type (`application/x-protobuf; messageType=package.Message`) or, when the schema
+    /// file has only one message, from the file.
+    Protobuf { files: Vec<FileDescriptor>, message: Option<String> },
+    Avro(AvroSchema)
+}
+
+impl Decoder {
+    /// The decoder an item is set to. Without a `decoder` it follows from the extension of
+    /// the `schema_file` (`.proto` or `.avsc`), and with neither the item has none.
+    pub fn from_options(decoder: &str, schema_file: &str) -> Result<Option<Self>> {
+        let (name, message) = match decoder.split_once(':') {
+            Some((name, message)) => (name.trim().to_lowercase(), Some(message.trim().to_string())),
+            None => (decoder.trim().to_lowercase(), None)
+        };
+
+        let name = match name.as_str() {
+            "" if schema_file.is_empty() => return Ok(None),
+            "" => match Path::new(schema_file).extension().and_then(|x| x.to_str()) {
+                Some("proto") => "protobuf".to_string(),
+                Some("avsc") => "avro".to_string(),
+                _ => return Err(anyhow!("Cannot tell the decoder from schema_file {}, set decoder to protobuf or avro", schema_file))
+            },
+            "messagepack" => "msgpack".to_string(),
+            _ => name
+        };
+
+        let decoder = match name.as_str() {
+            "msgpack" => Decoder::MessagePack,
+            "cbor" => Decoder::Cbor,
+            "protobuf" if schema_file.is_empty() => return Err(anyhow!("The protobuf decoder needs a .proto schema_file")),
+            "protobuf" => Decoder::Protobuf { files: read_proto(schema_file)?, message },
+            "avro" if schema_file.is_empty() => return Err(anyhow!("The avro decoder needs a .avsc schema_file")),
+            "avro" => Decoder::Avro(AvroSchema::read(schema_file)?),
+            other => return Err(anyhow!("Unknown decoder {}, expected one of {}", other, DECODER_NAMES.join(", ")))
+        };
+
+        if let Decoder::Protobuf { files, message: Some(res) } = &decoder {
+            find_message(files, res)?;
+        }
+
+        Ok(Some(decoder))
+    }
+
+    /// The built in decoder for a content type, for items without one.
+    pub fn for_content_type(content_type: &str) -> Option<Self> {
+        match media_type(content_type).as_str() {
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Decoder::MessagePack),
+            "application/cbor" => Some(Decoder::Cbor),
+            _ => None
+        }
+    }
+
+    pub fn decode(&self, body: &[u8], content_type: Option<&str>) -> Result<Value> {
+        match self {
+            Decoder::MessagePack => {
+                let mut input = body;
+                let value = rmpv::decode::read_value(&mut input)?;
+                if !input.is_empty() {
+                    return Err(anyhow!("{} bytes left after the MessagePack value", input.len()));
+                }
+                Ok(message_pack_json(&value))
+            }
+            Decoder::Cbor => {
+                let mut input = body;
+                let value: CborValue = ciborium::de::from_reader(&mut input)?;
+                if !input.is_empty() {
+                    return Err(anyhow!("{} bytes left after the CBOR value", input.len()));
+                }
+                Ok(cbor_json(&value))
+            }
+            Decoder::Protobuf { files, message } => {
+                let message_name = message.clone().or_else(|| content_type.and_then(|x| content_type_parameter(x, &["messagetype", "proto"])));
+                let descriptor = match message_name {
+                    Some(res) => find_message(files, res.as_str())?,
+                    None => only_message(files)?
+                };
+                let message = descriptor.parse_from_bytes(body)?;
+                Ok(protobuf_json(&*message))
+            }
+            Decoder::Avro(schema) => schema.decode(body)
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Decoder::MessagePack => "MessagePack",
+            Decoder::Cbor => "CBOR",
+            Decoder::Protobuf { .. } => "Protobuf",
+            Decoder::Avro(_) => "Avro"
+        }
+    }
+}
+
+/// `application/json` of `application/json; charset=utf-8`.
+fn media_type(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or_default().trim().to_lowercase()
+}
+
+fn content_type_parameter(content_type: &str, names: &[&str]) -> Option<String> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|x| x.split_once('='))
+        .find(|x| names.contains(&x.0.trim().to_lowercase().as_str()))
+        .map(|x| x.1.trim().trim_matches('"').to_string())
+}
+
+/// The `.proto` file and everything it imports, found next to it.
+fn read_proto(schema_file: &str) -> Result<Vec<FileDescriptor>> {
+    let path = Path::new(schema_file);
+    let directory = path.parent().filter(|x| !x.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+
+    //the imports come before the file itself, which is what `only_message` looks at
+    let parsed = protobuf_parse::Parser::new()
+        .pure()
+        .include(directory)
+        .input(path)
+        .parse_and_typecheck()
+        .map_err(|e| anyhow!("Reading {}: {:#}", schema_file, e))?;
+
+    Ok(FileDescriptor::new_dynamic_fds(parsed.file_descriptors, &[])?)
+}
+
+fn find_message(files: &[FileDescriptor], name: &str) -> Result<MessageDescriptor> {
+    let name = format!(".{}", name.trim_start_matches('.'));
+
+    //nested messages too, e.g. package.Order.Line
+    files
+        .iter()
+        .find_map(|x| x.message_by_full_name(name.as_str()))
+        .ok_or_else(|| anyhow!("Message type {} is not in the schema file", &name[1..]))
+}
+
+fn only_message(files: &[FileDescriptor]) -> Result<MessageDescriptor> {
+    let messages: Vec<MessageDescriptor> = files.last().map(|x| x.messages().collect()).unwrap_or_default();
+
+    match messages.len() {
+        1 => Ok(messages[0].clone()),
+        _ => Err(anyhow!("The schema file has {} messages, set decoder to protobuf:<message type>", messages.len()))
+    }
+}
+
+fn message_pack_json(value: &MessagePackValue) -> Value {
+    match value {
+        MessagePackValue::Nil => Value::Null,
+        MessagePackValue::Boolean(res) => Value::Bool(*res),
+        MessagePackValue::Integer(res) => match res.as_i64() {
+            Some(number) => Value::from(number),
+            None => Value::from(res.as_u64().unwrap_or_default())
+        },
+        MessagePackValue::F32(res) => Value::from(*res),
+        MessagePackValue::F64(res) => Value::from(*res),
+        MessagePackValue::String(res) => Value::String(String::from_utf8_lossy(res.as_bytes()).to_string()),
+        MessagePackValue::Binary(res) => Value::String(STANDARD.encode(res)),
+        MessagePackValue::Array(res) => Value::Array(res.iter().map(message_pack_json).collect()),
+        MessagePackValue::Map(res) => Value::Object(res.iter().map(|(k, v)| (key_string(message_pack_json(k)), message_pack_json(v))).collect()),
+        MessagePackValue::Ext(kind, data) => {
+            let mut map = Map::new();
+            map.insert("ext_type".to_string(), Value::from(*kind));
+            map.insert("data".to_string(), Value::String(STANDARD.encode(data)));
+            Value::Object(map)
+        }
+    }
+}
+
+fn cbor_json(value: &CborValue) -> Value {
+    match value {
+        CborValue::Null => Value::Null,
+        CborValue::Bool(res) => Value::Bool(*res),
+        CborValue::Integer(res) => {
+            let number = i128::from(*res);
+            match (i64::try_from(number), u64::try_from(number)) {
+                (Ok(signed), _) => Value::from(signed),
+                (_, Ok(unsigned)) => Value::from(unsigned),
+                _ => Value::String(number.to_string())
+            }
+        }
+        CborValue::Float(res) => Value::from(*res),
+        CborValue::Text(res) => Value::String(res.clone()),
+        CborValue::Bytes(res) => Value::String(STANDARD.encode(res)),
+        CborValue::Array(res) => Value::Array(res.iter().map(cbor_json).collect()),
+        CborValue::Map(res) => Value::Object(res.iter().map(|(k, v)| (key_string(cbor_json(k)), cbor_json(v))).collect()),
+        //tags such as dates only describe the value inside them
+        CborValue::Tag(_, inner) => cbor_json(inner),
+        _ => Value::Null
+    }
+}
+
+fn key_string(key: Value) -> String {
+    match key {
+        Value::String(res) => res,
+        other => other.to_string()
+    }
+}
+
+/// The fields that are set, by their name in the `.proto` file.
+fn protobuf_json(message: &dyn MessageDyn) -> Value {
+    let mut map = Map::new();
+
+    for field in message.descriptor_dyn().fields() {
+        let value = match field.get_reflect(message) {
+            ReflectFieldRef::Optional(res) => match res.value() {
+                Some(value) => protobuf_value_json(value),
+                None => continue
+            },
+            ReflectFieldRef::Repeated(res) if res.is_empty() => continue,
+            ReflectFieldRef::Repeated(res) => Value::Array(res.into_iter().map(protobuf_value_json).collect()),
+            ReflectFieldRef::Map(res) if res.is_empty() => continue,
+            ReflectFieldRef::Map(res) => Value::Object((&res).into_iter().map(|(k, v)| (key_string(protobuf_value_json(k)), protobuf_value_json(v))).collect())
+        };
+
+        map.insert(field.name().to_string(), value);
+    }
+
+    Value::Object(map)
+}
+
+fn protobuf_value_json(value: ReflectValueRef) -> Value {
+    match value {
+        ReflectValueRef::U32(res) => Value::from(res),
+        ReflectValueRef::U64(res) => Value::from(res),
+        ReflectValueRef::I32(res) => Value::from(res),
+        ReflectValueRef::I64(res) => Value::from(res),
+        ReflectValueRef::F32(res) => Value::from(res),
+        ReflectValueRef::F64(res) => Value::from(res),
+        ReflectValueRef::Bool(res) => Value::Bool(res),
+        ReflectValueRef::String(res) => Value::String(res.to_string()),
+        ReflectValueRef::Bytes(res) => Value::String(STANDARD.encode(res)),
+        ReflectValueRef::Enum(descriptor, number) => match descriptor.value_by_number(number) {
+            Some(res) => Value::String(res.name().to_string()),
+            None => Value::from(number)
+        },
+        ReflectValueRef::Message(res) => protobuf_json(&*res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use serde_json::json;
+
+    fn decoder_name(decoder: Result<Option<Decoder>>) -> Option<&'static str> {
+        decoder.unwrap().map(|x| x.name())
+    }
+
+    fn proto_file(name: &str) -> String {
+        let directory = std::env::temp_dir().join(format!("amqp-client-cli-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("kind.proto"), "syntax = \"proto3\";\npackage shop;\nenum Kind { UNKNOWN = 0; RETAIL = 1; }\n").unwrap();
+
+        let path = directory.join("order.proto");
+        fs::write(&path, r#"
+            syntax = "proto3";
+            package shop;
+            import "kind.proto";
+
+            message Order {
+                message Line {
+                    string sku = 1;
+                    uint32 quantity = 2;
+                }
+                int64 id = 1;
+                string status = 2;
+                repeated Line lines = 3;
+                Kind kind = 4;
+                bytes note = 5;
+            }
+
+            message Ping {}
+        "#).unwrap();
+
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn decoder_from_the_options() {
+        assert_eq!(decoder_name(Decoder::from_options("", "")), None);
+        assert_eq!(decoder_name(Decoder::from_options(" MessagePack ", "")), Some("MessagePack"));
+        assert_eq!(decoder_name(Decoder::from_options("cbor", "")), Some("CBOR"));
+
+        assert_eq!(Decoder::from_options("", "schema.json").err().unwrap().to_string(), "Cannot tell the decoder from schema_file schema.json, set decoder to protobuf or avro");
+        assert_eq!(Decoder::from_options("protobuf", "").err().unwrap().to_string(), "The protobuf decoder needs a .proto schema_file");
+        assert_eq!(Decoder::from_options("yaml", "").err().unwrap().to_string(), "Unknown decoder yaml, expected one of msgpack, cbor, protobuf, avro");
+    }
+
+    #[test]
+    fn decoder_from_the_content_type() {
+        assert_eq!(Decoder::for_content_type("application/x-msgpack; charset=binary").map(|x| x.name()), Some("MessagePack"));
+        assert_eq!(Decoder::for_content_type("application/vnd.msgpack").map(|x| x.name()), Some("MessagePack"));
+        assert_eq!(Decoder::for_content_type("Application/CBOR").map(|x| x.name()), Some("CBOR"));
+        assert!(Decoder::for_content_type("application/json").is_none());
+        assert!(Decoder::for_content_type("").is_none());
+    }
+
+    #[test]
+    fn message_pack_round_trip() {
+        let value = MessagePackValue::Map(vec![
+            (MessagePackValue::from("id"), MessagePackValue::from(u64::MAX)),
+            (MessagePackValue::from(7), MessagePackValue::Array(vec![MessagePackValue::from(-2), MessagePackValue::from(1.5), MessagePackValue::Nil])),
+            (MessagePackValue::from("raw"), MessagePackValue::Binary(vec![1, 2, 3])),
+            (MessagePackValue::from("ext"), MessagePackValue::Ext(5, vec![0xff]))
+        ]);
+        let mut body = vec![];
+        rmpv::encode::write_value(&mut body, &value).unwrap();
+
+        assert_eq!(Decoder::MessagePack.decode(&body, None).unwrap(), json!({
+            "id": u64::MAX,
+            "7": [-2, 1.5, null],
+            "raw": "AQID",
+            "ext": {"ext_type": 5, "data": "/w=="}
+        }));
+
+        body.push(0xc0);
+        assert_eq!(Decoder::MessagePack.decode(&body, None).err().unwrap().to_string(), "1 bytes left after the MessagePack value");
+        assert!(Decoder::MessagePack.decode(&body[..3], None).is_err());
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let value = CborValue::Map(vec![
+            (CborValue::Text("id".to_string()), CborValue::Integer((-300).into())),
+            (CborValue::Integer(1.into()), CborValue::Bytes(vec![1, 2, 3])),
+            (CborValue::Text("at".to_string()), CborValue::Tag(1, Box::new(CborValue::Integer(1700000000.into())))),
+            (CborValue::Text("big".to_string()), CborValue::Integer(ciborium::value::Integer::try_from(-(u64::MAX as i128)).unwrap())),
+            (CborValue::Text("ok".to_string()), CborValue::Array(vec![CborValue::Bool(true), CborValue::Float(0.25), CborValue::Null]))
+        ]);
+        let mut body = vec![];
+        ciborium::ser::into_writer(&value, &mut body).unwrap();
+
+        assert_eq!(Decoder::Cbor.decode(&body, None).unwrap(), json!({
+            "id": -300,
+            "1": "AQID",
+            "at": 1700000000,
+            "big": "-18446744073709551615",
+            "ok": [true, 0.25, null]
+        }));
+
+        body.extend([0xf6, 0xf6]);
+        assert_eq!(Decoder::Cbor.decode(&body, None).err().unwrap().to_string(), "2 bytes left after the CBOR value");
+    }
+
+    #[test]
+    fn protobuf_with_the_message_type_in_the_decoder_name() {
+        let schema_file = proto_file("named");
+        let decoder = Decoder::from_options("protobuf:shop.Order", schema_file.as_str()).unwrap().unwrap();
+
+        //id 150, status "PAID", one line {sku "a", quantity 2}, kind RETAIL and note [0xff]
+        let body = [
+            vec![0x08, 0x96, 0x01],
+            vec![0x12, 0x04], b"PAID".to_vec(),
+            vec![0x1a, 0x05, 0x0a, 0x01, b'a', 0x10, 0x02],
+            vec![0x20, 0x01],
+            vec![0x2a, 0x01, 0xff]
+        ].concat();
+
+        assert_eq!(decoder.decode(&body, None).unwrap(), json!({
+            "id": 150,
+            "status": "PAID",
+            "lines": [{"sku": "a", "quantity": 2}],
+            "kind": "RETAIL",
+            "note": "/w=="
+        }));
+
+        //fields left at their default are not sent, so they are left out
+        assert_eq!(decoder.decode(&[0x08, 0x01], None).unwrap(), json!({"id": 1}));
+        assert!(decoder.decode(&[0x12, 0x09, b'x'], None).is_err());
+    }
+
+    #[test]
+    fn protobuf_message_type_from_the_content_type() {
+        let schema_file = proto_file("content-type");
+        let decoder = Decoder::from_options("", schema_file.as_str()).unwrap().unwrap();
+        assert_eq!(decoder.name(), "Protobuf");
+
+        assert_eq!(decoder.decode(&[0x08, 0x02], Some("application/x-protobuf; messageType=\"shop.Order\"")).unwrap(), json!({"id": 2}));
+        assert_eq!(decoder.decode(&[0x10, 0x02], Some("application/protobuf; proto=shop.Order.Line")).unwrap(), json!({"quantity": 2}));
+        assert_eq!(decoder.decode(&[], None).err().unwrap().to_string(), "The schema file has 2 messages, set decoder to protobuf:<message type>");
+        assert_eq!(decoder.decode(&[], Some("application/x-protobuf; messageType=shop.Nope")).err().unwrap().to_string(), "Message type shop.Nope is not in the schema file");
+
+        assert_eq!(Decoder::from_options("protobuf:Order", schema_file.as_str()).err().unwrap().to_string(), "Message type Order is not in the schema file");
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::path::Path;
+use anyhow::{Result, Context, anyhow};
+use serde_json::Map;
+use crate::Config;
+use crate::amqp::DEFAULT_QUEUE_NAME_TEMPLATE;
+use crate::management::ManagementClient;
+use crate::models::definitions::{BindingDefinition, Definitions, ExchangeDefinition, QueueDefinition, VhostDefinition};
+use crate::models::enums::ExchangeTypeSer;
+use crate::operations::{Destination, Operation};
+use crate::tap_queues::queue_name_prefix;
+
+/// The exchanges the selector items subscribe to, declared the same way `Ampq` declares them.
+/// Their queues and bindings are left out, the tap queues are named per session and deleted
+/// when unsubscribing, so `fetch` is needed for the whole topology.
+pub fn from_config(config: &Config) -> Definitions {
+    let mut exchanges: Vec<ExchangeDefinition> = vec![];
+
+    for item in config.items.iter() {
+        if exchanges.iter().any(|x| x.name == item.exchange_name) {
+            continue;
+        }
+
+        exchanges.push(ExchangeDefinition {
+            name: item.exchange_name.clone(),
+            vhost: None,
+            exchange_type: format!("{:?}", item.exchange_type).to_lowercase(),
+            durable: true,
+            auto_delete: false,
+            internal: false,
+            arguments: Map::new()
+        });
+    }
+
+    Definitions {
+        vhosts: vec![VhostDefinition { name: config_vhost(config) }],
+        exchanges,
+        ..Definitions::default()
+    }
+}
+
+/// Everything declared on the config's vhost, through the management HTTP API, except the tap
+/// queues and their bindings.
+pub fn fetch(config: &Config) -> Result<Definitions> {
+    let management_url = match &config.management_url {
+        Some(res) if !res.is_empty() => res.as_str(),
+        _ => return Err(anyhow!("management_url is not set in the config file"))
+    };
+
+    let client = ManagementClient::new(management_url, config.username.as_str(), config.password.as_str(), config.vhost.as_deref())?;
+    let mut definitions = client.definitions()?;
+
+    if definitions.vhosts.is_empty() {
+        definitions.vhosts.push(VhostDefinition { name: config_vhost(config) });
+    }
+
+    //tap queues, this program's own included, only exist while something is subscribed
+    let prefix = tap_queue_prefix(config);
+    if !prefix.is_empty() {
+        definitions.queues.retain(|x| !x.name.starts_with(prefix.as_str()));
+        definitions.bindings.retain(|x| x.destination_type != "queue" || !x.destination.starts_with(prefix.as_str()));
+    }
+
+    Ok(definitions)
+}
+
+pub fn read(path: &Path) -> Result<Definitions> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let definitions = serde_json::from_str(contents.as_str()).with_context(|| format!("Parsing {}", path.display()))?;
+
+    Ok(definitions)
+}
+
+pub fn write(definitions: &Definitions, path: &Path) -> Result<()> {
+    let mut contents = serde_json::to_string_pretty(definitions)?;
+    contents.push('\n');
+    fs::write(path, contents).with_context(|| format!("Writing {}", path.display()))?;
+
+    Ok(())
+}
+
+/// The declarations that recreate `definitions` on the config's vhost, exchanges first so the
+/// bindings have something to bind to. Entries that cannot or should not be declared are
+/// returned as messages instead, along with the bindings that would point at them.
+pub fn operations(definitions: &Definitions, config: &Config) -> (Vec<Operation>, Vec<String>) {
+    let vhost = config_vhost(config);
+    let prefix = tap_queue_prefix(config);
+
+    let mut operations = vec![];
+    let mut skipped = vec![];
+    let mut skipped_exchanges: Vec<&str> = vec![];
+    let mut skipped_queues: Vec<&str> = vec![];
+
+    //files exported from the management UI hold every vhost, only the config's one is declared
+    let mut on_vhost = |kind: &str, name: &str, entry_vhost: &Option<String>| match entry_vhost.as_deref() {
+        Some(res) if res != vhost => {
+            skipped.push(format!("Skipping {} {} of vhost {}", kind, name, res));
+            false
+        }
+        _ => true
+    };
+
+    let exchanges: Vec<&ExchangeDefinition> = definitions.exchanges.iter().filter(|x| on_vhost("exchange", x.name.as_str(), &x.vhost)).collect();
+    let queues: Vec<&QueueDefinition> = definitions.queues.iter().filter(|x| on_vhost("queue", x.name.as_str(), &x.vhost)).collect();
+    let bindings: Vec<&BindingDefinition> = definitions.bindings.iter().filter(|x| on_vhost("binding of", format!("{} to {}", x.destination, x.source).as_str(), &x.vhost)).collect();
+
+    for exchange in exchanges {
+        if exchange.name.is_empty() || exchange.name.starts_with("amq.") {
+            continue;
+        }
+
+        match ExchangeTypeSer::iterator().find(|x| format!("{:?}", x).eq_ignore_ascii_case(exchange.exchange_type.as_str())) {
+            None => {
+                skipped.push(format!("Skipping exchange {} of unsupported type {}", exchange.name, exchange.exchange_type));
+                skipped_exchanges.push(exchange.name.as_str());
+            }
+            Some(exchange_type) => operations.push(Operation::DeclareExchange {
+                name: exchange.name.clone(),
+                exchange_type,
+                durable: exchange.durable,
+                auto_delete: exchange.auto_delete,
+                internal: exchange.internal,
+                arguments: exchange.arguments.clone()
+            })
+        }
+    }
+
+    for queue in queues {
+        //server named queues get a new name every time they are declared
+        if queue.name.starts_with("amq.gen-") {
+            skipped.push(format!("Skipping server named queue {}", queue.name));
+            skipped_queues.push(queue.name.as_str());
+            continue;
+        }
+
+        if !prefix.is_empty() && queue.name.starts_with(prefix.as_str()) {
+            skipped.push(format!("Skipping tap queue {}", queue.name));
+            skipped_queues.push(queue.name.as_str());
+            continue;
+        }
+
+        operations.push(Operation::DeclareQueue {
+            name: queue.name.clone(),
+            durable: queue.durable,
+            exclusive: false,
+            auto_delete: queue.auto_delete,
+            arguments: queue.arguments.clone()
+        });
+    }
+
+    for binding in bindings {
+        //every queue is bound to the default exchange by the broker itself
+        if binding.source.is_empty() {
+            continue;
+        }
+
+        let skipped_destination = match binding.destination_type.as_str() {
+            "queue" => skipped_queues.contains(&binding.destination.as_str()),
+            _ => skipped_exchanges.contains(&binding.destination.as_str())
+        };
+        if skipped_destination || skipped_exchanges.contains(&binding.source.as_str()) {
+            let skipped_name = if skipped_destination { &binding.destination } else { &binding.source };
+            skipped.push(format!("Skipping binding of {} to {}, {} is skipped", binding.destination, binding.source, skipped_name));
+            continue;
+        }
+
+        match destination(binding) {
+            Some(destination) => operations.push(Operation::Bind {
+                destination,
+                source: binding.source.clone(),
+                routing_key: binding.routing_key.clone(),
+                arguments: binding.arguments.clone()
+            }),
+            None => skipped.push(format!("Skipping binding to {} of unknown type {}", binding.destination, binding.destination_type))
+        }
+    }
+
+    (operations, skipped)
+}
+
+fn destination(binding: &BindingDefinition) -> Option<Destination> {
+    match binding.destination_type.as_str() {
+        "queue" => Some(Destination::Queue(binding.destination.clone())),
+        "exchange" => Some(Destination::Exchange(binding.destination.clone())),
+        _ => None
+    }
+}
+
+/// The start of the tap queue names, empty when `queue_name_template` starts with a placeholder.
+fn tap_queue_prefix(config: &Config) -> String {
+    queue_name_prefix(config.queue_name_template.as_deref().unwrap_or(DEFAULT_QUEUE_NAME_TEMPLATE))
+}
+
+fn config_vhost(config: &Config) -> String {
+    match config.vhost.as_deref() {
+        Some(res) if !res.is_empty() => res.to_string(),
+        _ => "/".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFINITIONS: &str = r##"{
+        "exchanges": [
+            {"name": "orders", "vhost": "/", "type": "topic", "durable": true},
+            {"name": "amq.topic", "vhost": "/", "type": "topic"},
+            {"name": "delayed", "vhost": "/", "type": "x-delayed-message"},
+            {"name": "audit", "vhost": "staging", "type": "fanout"}
+        ],
+        "queues": [
+            {"name": "order-log", "durable": true},
+            {"name": "amq.gen-Xa2", "vhost": "/"},
+            {"name": "amqp-client-cli.orders.1.host.guest.42.ab12", "vhost": "/"}
+        ],
+        "bindings": [
+            {"source": "orders", "vhost": "/", "destination": "order-log", "destination_type": "queue", "routing_key": "order.*"},
+            {"source": "", "vhost": "/", "destination": "order-log", "destination_type": "queue", "routing_key": "order-log"},
+            {"source": "orders", "vhost": "/", "destination": "amq.gen-Xa2", "destination_type": "queue", "routing_key": "#"},
+            {"source": "orders", "vhost": "/", "destination": "amqp-client-cli.orders.1.host.guest.42.ab12", "destination_type": "queue", "routing_key": "#"},
+            {"source": "delayed", "vhost": "/", "destination": "order-log", "destination_type": "queue"},
+            {"source": "orders", "vhost": "/", "destination": "archive", "destination_type": "stream"},
+            {"source": "audit", "vhost": "staging", "destination": "audit-log", "destination_type": "queue"}
+        ]
+    }"##;
+
+    fn describe(definitions: &Definitions, config: &Config) -> (Vec<String>, Vec<String>) {
+        let (operations, skipped) = operations(definitions, config);
+        (operations.iter().map(|x| x.describe()).collect(), skipped)
+    }
+
+    #[test]
+    fn declares_exchanges_then_queues_then_bindings() {
+        let definitions: Definitions = serde_json::from_str(DEFINITIONS).unwrap();
+        let (operations, _) = describe(&definitions, &Config::new(Path::new("amqp-client-cli.json")));
+
+        assert_eq!(operations, vec![
+            "Declare Topic exchange orders",
+            "Declare queue order-log",
+            "Bind queue order-log to exchange orders with routing key \"order.*\""
+        ]);
+    }
+
+    #[test]
+    fn reports_what_is_skipped() {
+        let definitions: Definitions = serde_json::from_str(DEFINITIONS).unwrap();
+        let (_, skipped) = describe(&definitions, &Config::new(Path::new("amqp-client-cli.json")));
+
+        assert_eq!(skipped, vec![
+            "Skipping exchange audit of vhost staging",
+            "Skipping binding of audit-log to audit of vhost staging",
+            "Skipping exchange delayed of unsupported type x-delayed-message",
+            "Skipping server named queue amq.gen-Xa2",
+            "Skipping tap queue amqp-client-cli.orders.1.host.guest.42.ab12",
+            "Skipping binding of amq.gen-Xa2 to orders, amq.gen-Xa2 is skipped",
+            "Skipping binding of amqp-client-cli.orders.1.host.guest.42.ab12 to orders, amqp-client-cli.orders.1.host.guest.42.ab12 is skipped",
+            "Skipping binding of order-log to delayed, delayed is skipped",
+            "Skipping binding to archive of unknown type stream"
+        ]);
+    }
+
+    #[test]
+    fn only_the_config_vhost_is_declared() {
+        let definitions: Definitions = serde_json::from_str(DEFINITIONS).unwrap();
+        let mut config = Config::new(Path::new("amqp-client-cli.json"));
+        config.vhost = Some("staging".to_string());
+        config.queue_name_template = Some("{item}".to_string());
+
+        let (operations, skipped) = describe(&definitions, &config);
+
+        //entries without a vhost are on every vhost, and without a prefix no queue is a tap queue
+        assert_eq!(operations, vec![
+            "Declare Fanout exchange audit",
+            "Declare queue order-log",
+            "Bind queue audit-log to exchange audit with routing key \"\""
+        ]);
+        assert!(skipped.contains(&"Skipping queue amqp-client-cli.orders.1.host.guest.42.ab12 of vhost /".to_string()));
+    }
+
+    #[test]
+    fn config_items_give_their_exchanges_once() {
+        let mut config = Config::new(Path::new("amqp-client-cli.json"));
+        for (name, exchange_type) in [("orders", ExchangeTypeSer::Topic), ("orders", ExchangeTypeSer::Topic), ("events", ExchangeTypeSer::Fanout)] {
+            config.items.push(crate::models::exchange_options::ExchangeOptions { exchange_name: name.to_string(), exchange_type, ..Default::default() });
+        }
+
+        let (operations, skipped) = describe(&from_config(&config), &config);
+        assert_eq!(operations, vec!["Declare Topic exchange orders", "Declare Fanout exchange events"]);
+        assert!(skipped.is_empty());
+    }
+}
@@ -3,12 +3,55 @@ use std::fs::{File, OpenOptions};
 use crossbeam::channel::Sender;
 use std::io::Write;
 use std::path::Path;
-use anyhow::{Result};
+use anyhow::{Result, anyhow};
+
+/// How an item's messages are written to its log file: the way the messages pane shows them, or
+/// one row per message.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LogFormat {
+    Text,
+    Csv,
+    Tsv
+}
+
+impl LogFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "text" => Ok(LogFormat::Text),
+            "csv" => Ok(LogFormat::Csv),
+            "tsv" => Ok(LogFormat::Tsv),
+            other => Err(anyhow!("Unknown log format {}, expected text, csv or tsv", other))
+        }
+    }
+
+    /// `cells` as a row, quoted for CSV where needed. TSV has no quoting, so tabs and line breaks
+    /// in the cells become spaces.
+    pub fn row(&self, cells: &[String]) -> String {
+        match self {
+            LogFormat::Text => cells.join(" | "),
+            LogFormat::Csv => cells
+                .iter()
+                .map(|x| {
+                    if x.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", x.replace('"', "\"\""))
+                    }
+                    else {
+                        x.clone()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(","),
+            LogFormat::Tsv => cells.iter().map(|x| x.replace(['\t', '\n', '\r'], " ")).collect::<Vec<String>>().join("\t")
+        }
+    }
+}
 
 pub struct FileLogger {
     wait_time_ms: u64,
     since_last_write_ms: u64,
     file_paths: HashMap<String, Vec<String>>,
+    /// The first line of each file, written when the file is new or empty.
+    headers: HashMap<String, String>,
     console_log_sender: Sender<String>
 }
 
@@ -18,6 +61,7 @@ impl FileLogger {
             wait_time_ms: 1000,
             since_last_write_ms: 0,
             file_paths: Default::default(),
+            headers: Default::default(),
             console_log_sender,
         }
     }
@@ -32,6 +76,12 @@ impl FileLogger {
         }
     }
 
+    /// Starts `file_path` with `header` when it has nothing in it yet, e.g. the column names of
+    /// a CSV file.
+    pub fn set_header(&mut self, file_path: &str, header: String) {
+        self.headers.insert(file_path.to_string(), header);
+    }
+
     fn write(&mut self) -> Result<()> {
         for file_data in self.file_paths.iter() {
             if Path::new(file_data.0).exists() == false {
@@ -43,6 +93,12 @@ impl FileLogger {
                 .append(true)
                 .open(file_data.0)?;
 
+            if let Some(header) = self.headers.get(file_data.0) {
+                if file.metadata()?.len() == 0 {
+                    writeln!(file, "{}", header)?;
+                }
+            }
+
             for line in file_data.1 {
                 writeln!(file, "{}", line.to_string())?;
             }
@@ -0,0 +1,180 @@
+use serde_json::Value;
+use tui::style::Style;
+use tui::text::Span;
+use crate::theme;
+
+/// A JSON value laid out like `serde_json::to_string_pretty`, with objects and arrays that can be
+/// folded to one line.
+pub struct JsonView {
+    lines: Vec<JsonLine>
+}
+
+struct JsonLine {
+    text: String,
+    depth: usize,
+    /// For the line that opens an object or array: the line that closes it.
+    end: Option<usize>,
+    /// The keys or items in the object or array this line opens.
+    count: usize,
+    folded: bool
+}
+
+impl JsonView {
+    pub fn new(value: &Value) -> Self {
+        let mut view = JsonView { lines: vec![] };
+        view.add(value, None, 0, false);
+
+        view
+    }
+
+    /// Indices of the lines shown, skipping what folded objects and arrays hold.
+    pub fn visible(&self) -> Vec<usize> {
+        let mut visible = vec![];
+        let mut i = 0;
+
+        while i < self.lines.len() {
+            visible.push(i);
+            i = match self.lines[i].end {
+                Some(end) if self.lines[i].folded => end + 1,
+                _ => i + 1
+            };
+        }
+
+        visible
+    }
+
+    /// Line `index` with colours, a folded object or array on one line with its size.
+    pub fn spans(&self, index: usize) -> Vec<Span<'static>> {
+        let line = &self.lines[index];
+        let mut spans = highlight_line(line.text.as_str());
+
+        if let (Some(end), true) = (line.end, line.folded) {
+            let closing = self.lines[end].text.trim_start();
+            let noun = match (closing.starts_with('}'), line.count) {
+                (true, 1) => "key",
+                (true, _) => "keys",
+                (false, 1) => "item",
+                (false, _) => "items"
+            };
+            spans.push(Span::styled(format!(" … {} {} ", line.count, noun), Style::default().fg(theme::current().pending)));
+            spans.push(Span::raw(closing.to_string()));
+        }
+
+        spans
+    }
+
+    /// Folds or unfolds the object or array line `index` opens, or the innermost one it is in.
+    /// Returns the line that opens it, which stays in view.
+    pub fn toggle(&mut self, index: usize) -> usize {
+        let opening = (0..=index.min(self.lines.len().saturating_sub(1)))
+            .rev()
+            .find(|x| self.lines[*x].end.map(|end| end >= index).unwrap_or(false));
+
+        match opening {
+            Some(res) => {
+                self.lines[res].folded = !self.lines[res].folded;
+                res
+            }
+            None => index
+        }
+    }
+
+    /// Folds everything below the top level, or unfolds everything.
+    pub fn fold_all(&mut self, folded: bool) {
+        for line in self.lines.iter_mut().filter(|x| x.end.is_some()) {
+            line.folded = folded && line.depth > 0;
+        }
+    }
+
+    fn add(&mut self, value: &Value, key: Option<&str>, depth: usize, comma: bool) {
+        let indent = "  ".repeat(depth);
+        let key = key.map(|x| format!("{}: ", Value::from(x))).unwrap_or_default();
+        let comma = if comma { "," } else { "" };
+
+        let (open, close, children): (char, char, Vec<(Option<&str>, &Value)>) = match value {
+            Value::Object(res) if !res.is_empty() => ('{', '}', res.iter().map(|(k, v)| (Some(k.as_str()), v)).collect()),
+            Value::Array(res) if !res.is_empty() => ('[', ']', res.iter().map(|x| (None, x)).collect()),
+            _ => {
+                self.push(format!("{}{}{}{}", indent, key, value, comma), depth);
+                return;
+            }
+        };
+
+        let start = self.lines.len();
+        self.push(format!("{}{}{}", indent, key, open), depth);
+
+        let count = children.len();
+        for (i, (child_key, child)) in children.into_iter().enumerate() {
+            self.add(child, child_key, depth + 1, i + 1 < count);
+        }
+
+        self.push(format!("{}{}{}", indent, close, comma), depth);
+        self.lines[start].end = Some(self.lines.len() - 1);
+        self.lines[start].count = count;
+    }
+
+    fn push(&mut self, text: String, depth: usize) {
+        self.lines.push(JsonLine { text, depth, end: None, count: 0, folded: false });
+    }
+}
+
+/// One line of pretty printed JSON with keys, strings, numbers and literals coloured. Anything
+/// else is left as it is, so text that is not JSON only gets odd colours.
+pub fn highlight_line(line: &str) -> Vec<Span<'static>> {
+    let mut spans = vec![];
+    let mut plain = String::new();
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '"' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(chars.len());
+
+            let is_key = chars[i..].iter().find(|x| !x.is_whitespace()) == Some(&':');
+            let color = if is_key { theme::current().json_key } else { theme::current().json_string };
+            push(&mut spans, &mut plain, chars[start..i].iter().collect(), Style::default().fg(color));
+        }
+        else if c == '-' || c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || "+-.eE".contains(chars[i])) {
+                i += 1;
+            }
+            push(&mut spans, &mut plain, chars[start..i].iter().collect(), Style::default().fg(theme::current().json_number));
+        }
+        else if c.is_ascii_alphabetic() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            match word.as_str() {
+                "true" | "false" | "null" => push(&mut spans, &mut plain, word, Style::default().fg(theme::current().json_literal)),
+                _ => plain.push_str(word.as_str())
+            }
+        }
+        else {
+            plain.push(c);
+            i += 1;
+        }
+    }
+
+    if !plain.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+
+    spans
+}
+
+fn push(spans: &mut Vec<Span<'static>>, plain: &mut String, text: String, style: Style) {
+    if !plain.is_empty() {
+        spans.push(Span::raw(std::mem::take(plain)));
+    }
+    spans.push(Span::styled(text, style));
+}
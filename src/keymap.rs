@@ -0,0 +1,392 @@
+use anyhow::{Result, anyhow};
+use crossterm::event::KeyCode;
+use itertools::Itertools;
+use serde_json::{Map, Value};
+use crate::app::Mode;
+
+/// What a key does in the main window or a popup window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    ShrinkSelector,
+    GrowSelector,
+    Logs,
+    Subscribe,
+    Edit,
+    Add,
+    Clone,
+    Delete,
+    Browse,
+    Topology,
+    RoutingTester,
+    Operation,
+    MessageList,
+    Grid,
+    Tiles,
+    Theme,
+    Pause,
+    Filter,
+    Save,
+    Publish,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    FilterView,
+    ViewMode,
+    DetailFocus,
+    Fold,
+    FoldAll,
+    UnfoldAll,
+    Mark,
+    Diff,
+    Copy,
+    Close,
+    NextTab,
+    PreviousTab,
+    Refresh
+}
+
+/// Where an action works: the main window in either of its modes, or the popup windows.
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Normal,
+    Scroll,
+    Popup
+}
+
+const NORMAL: &[Context] = &[Context::Normal];
+const SCROLL: &[Context] = &[Context::Scroll];
+const POPUP: &[Context] = &[Context::Popup];
+const BOTH: &[Context] = &[Context::Normal, Context::Scroll];
+
+/// Each action with its name in the config, where it works and its default keys, the first of
+/// which is shown in the header.
+const ACTIONS: [(Action, &str, &[Context], &[&str]); 41] = [
+    (Action::Quit, "quit", BOTH, &["q", "Esc"]),
+    (Action::Up, "up", &[Context::Normal, Context::Scroll, Context::Popup], &["Up", "k"]),
+    (Action::Down, "down", &[Context::Normal, Context::Scroll, Context::Popup], &["Down", "j"]),
+    (Action::PageUp, "page_up", &[Context::Scroll, Context::Popup], &["PageUp"]),
+    (Action::PageDown, "page_down", &[Context::Scroll, Context::Popup], &["PageDown"]),
+    (Action::ShrinkSelector, "shrink_selector", BOTH, &["Left"]),
+    (Action::GrowSelector, "grow_selector", BOTH, &["Right"]),
+    (Action::Logs, "logs", BOTH, &["l"]),
+    (Action::Subscribe, "subscribe", NORMAL, &["Enter"]),
+    (Action::Edit, "edit", &[Context::Normal, Context::Popup], &["e"]),
+    (Action::Add, "add", &[Context::Normal, Context::Popup], &["a"]),
+    (Action::Clone, "clone", NORMAL, &["c"]),
+    (Action::Delete, "delete", NORMAL, &["d"]),
+    (Action::Browse, "browse", NORMAL, &["b"]),
+    (Action::Topology, "topology", NORMAL, &["t"]),
+    (Action::RoutingTester, "routing_tester", NORMAL, &["r"]),
+    (Action::Operation, "operation", NORMAL, &["o"]),
+    (Action::MessageList, "message_list", BOTH, &["m"]),
+    (Action::Grid, "grid", BOTH, &["g"]),
+    (Action::Tiles, "tiles", BOTH, &["w"]),
+    (Action::Theme, "theme", NORMAL, &["T"]),
+    (Action::Pause, "pause", BOTH, &["p"]),
+    (Action::Filter, "filter", NORMAL, &["f", "/"]),
+    (Action::Save, "save", NORMAL, &["s"]),
+    (Action::Publish, "publish", NORMAL, &["P", "n"]),
+    (Action::Search, "search", SCROLL, &["/"]),
+    (Action::NextMatch, "next_match", SCROLL, &["n"]),
+    (Action::PreviousMatch, "previous_match", SCROLL, &["N"]),
+    (Action::FilterView, "filter_view", SCROLL, &["f"]),
+    (Action::ViewMode, "view_mode", SCROLL, &["v"]),
+    (Action::DetailFocus, "detail_focus", SCROLL, &["Tab"]),
+    (Action::Fold, "fold", SCROLL, &["Space", "Enter"]),
+    (Action::FoldAll, "fold_all", SCROLL, &["z"]),
+    (Action::UnfoldAll, "unfold_all", SCROLL, &["Z"]),
+    (Action::Mark, "mark", SCROLL, &["x"]),
+    (Action::Diff, "diff", SCROLL, &["d"]),
+    (Action::Copy, "copy", SCROLL, &["y"]),
+    (Action::Close, "close", POPUP, &["Esc"]),
+    (Action::NextTab, "next_tab", POPUP, &["Right", "Tab"]),
+    (Action::PreviousTab, "previous_tab", POPUP, &["Left", "BackTab"]),
+    (Action::Refresh, "refresh", POPUP, &["r"])
+];
+
+/// The keys of the main window and the popups. The config's `keys` replace the default keys of
+/// the actions it names, e.g. `"keys": { "pause": ["p", "F9"], "publish": "Shift+P" }`.
+pub struct Keymap {
+    keys: Vec<(Action, Vec<KeyCode>)>
+}
+
+impl Keymap {
+    /// Fails on unknown actions or keys, and on a key bound to two actions in the same mode.
+    pub fn load(keys: Option<&Map<String, Value>>) -> Result<Self> {
+        let mut keymap = Keymap {
+            keys: ACTIONS
+                .iter()
+                .map(|(action, _, _, defaults)| (*action, defaults.iter().map(|x| parse_key(x).unwrap()).collect()))
+                .collect()
+        };
+
+        for (name, value) in keys.into_iter().flatten() {
+            let action = ACTIONS
+                .iter()
+                .find(|x| x.1 == name)
+                .map(|x| x.0)
+                .ok_or_else(|| anyhow!("Unknown action {}, expected one of {}", name, ACTIONS.iter().map(|x| x.1).join(", ")))?;
+
+            let names: Vec<&str> = match value {
+                Value::String(res) => vec![res.as_str()],
+                Value::Array(res) => res
+                    .iter()
+                    .map(|x| x.as_str().ok_or_else(|| anyhow!("{}: keys must be strings, found {}", name, x)))
+                    .collect::<Result<Vec<&str>>>()?,
+                other => return Err(anyhow!("{}: must be a key or a list of keys, found {}", name, other))
+            };
+
+            let codes = names.iter().map(|x| parse_key(x).map_err(|e| anyhow!("{}: {}", name, e))).collect::<Result<Vec<KeyCode>>>()?;
+            if let Some(res) = keymap.keys.iter_mut().find(|x| x.0 == action) {
+                res.1 = codes;
+            }
+        }
+
+        keymap.check_conflicts()?;
+
+        Ok(keymap)
+    }
+
+    /// The action `code` is bound to in `mode`.
+    pub fn action(&self, mode: &Mode, code: KeyCode) -> Option<Action> {
+        let context = match mode {
+            Mode::Normal => Context::Normal,
+            Mode::Scroll => Context::Scroll
+        };
+
+        self.find(context, code)
+    }
+
+    /// The action `code` is bound to in popups. Popups that are `typing` text keep characters
+    /// for the text.
+    pub fn popup_action(&self, code: KeyCode, typing: bool) -> Option<Action> {
+        if typing && matches!(code, KeyCode::Char(_)) {
+            return None;
+        }
+
+        self.find(Context::Popup, code)
+    }
+
+    fn find(&self, context: Context, code: KeyCode) -> Option<Action> {
+        self.keys
+            .iter()
+            .find(|(action, codes)| works_in(*action, context) && codes.contains(&code))
+            .map(|x| x.0)
+    }
+
+    /// The first key of `action` as the header shows it, e.g. `(Shift+P)` or `↑`.
+    pub fn key_name(&self, action: Action) -> String {
+        self.first_key(action).map(display_key).unwrap_or_else(|| "(unbound)".to_string())
+    }
+
+    /// `action` in the header with its first key, e.g. `(E)dit` when the label starts with the
+    /// key's letter, otherwise `(Tab) Message Detail`. Unbound actions are left out.
+    pub fn hint(&self, action: Action, label: &str) -> Option<String> {
+        let code = self.first_key(action)?;
+
+        match (code, label.chars().next()) {
+            (KeyCode::Char(c), Some(first)) if c.is_ascii_lowercase() && first.to_ascii_lowercase() == c => {
+                Some(format!("({}){}", first.to_ascii_uppercase(), &label[first.len_utf8()..]))
+            }
+            _ => Some(format!("{} {}", display_key(code), label))
+        }
+    }
+
+    /// Two actions that share a label, e.g. `(Pg Up) (Pg Dn) Page`.
+    pub fn hint_pair(&self, first: Action, second: Action, label: &str) -> Option<String> {
+        let keys: Vec<String> = [first, second].iter().filter_map(|x| self.first_key(*x)).map(display_key).collect();
+
+        if keys.is_empty() {
+            return None;
+        }
+
+        Some(format!("{} {}", keys.join(" "), label))
+    }
+
+    fn first_key(&self, action: Action) -> Option<KeyCode> {
+        self.keys.iter().find(|x| x.0 == action).and_then(|x| x.1.first().copied())
+    }
+
+    fn check_conflicts(&self) -> Result<()> {
+        let mut conflicts = vec![];
+
+        for (context, context_name) in [(Context::Normal, "normal mode"), (Context::Scroll, "paused mode"), (Context::Popup, "popups")] {
+            let bound: Vec<(KeyCode, Action)> = self.keys
+                .iter()
+                .filter(|x| works_in(x.0, context))
+                .flat_map(|(action, codes)| codes.iter().map(move |x| (*x, *action)))
+                .collect();
+
+            for (i, (code, action)) in bound.iter().enumerate() {
+                for (other_code, other_action) in bound[i + 1..].iter() {
+                    if code == other_code && action != other_action {
+                        conflicts.push(format!("{} is bound to both {} and {} in {}", display_key(*code), name(*action), name(*other_action), context_name));
+                    }
+                }
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(anyhow!("Conflicting key bindings: {}", conflicts.join("; ")))
+        }
+    }
+}
+
+fn name(action: Action) -> &'static str {
+    ACTIONS.iter().find(|x| x.0 == action).map(|x| x.1).unwrap_or_default()
+}
+
+fn works_in(action: Action, context: Context) -> bool {
+    ACTIONS.iter().find(|x| x.0 == action).map(|x| x.2.contains(&context)).unwrap_or(false)
+}
+
+/// A key written as a character such as `p`, `P` or `Shift+p`, or by name such as `Enter`,
+/// `Space`, `PageUp` or `F5`. Digits sort the grid and cannot be bound.
+fn parse_key(key: &str) -> Result<KeyCode> {
+    let invalid = || anyhow!("Unknown key {}, expected a character such as p or Shift+p, or a name such as Enter, Space, Tab, Up or F5", key);
+
+    if key.len() == 1 && key.chars().all(|x| x.is_ascii_digit()) {
+        return Err(anyhow!("{} sorts the grid by a column, digits cannot be bound", key));
+    }
+
+    if let Some(rest) = key.strip_prefix("Shift+").or_else(|| key.strip_prefix("shift+")) {
+        let mut chars = rest.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_alphabetic() => Ok(KeyCode::Char(c.to_ascii_uppercase())),
+            _ => Err(invalid())
+        };
+    }
+
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(KeyCode::Char(c));
+    }
+
+    let code = match key.to_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        other => match other.strip_prefix('f').and_then(|x| x.parse::<u8>().ok()) {
+            Some(res) if (1..=12).contains(&res) => KeyCode::F(res),
+            _ => return Err(invalid())
+        }
+    };
+
+    Ok(code)
+}
+
+fn display_key(code: KeyCode) -> String {
+    match code {
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::PageUp => "(Pg Up)".to_string(),
+        KeyCode::PageDown => "(Pg Dn)".to_string(),
+        KeyCode::Char(' ') => "(Space)".to_string(),
+        KeyCode::Char(c) if c.is_uppercase() => format!("(Shift+{})", c),
+        KeyCode::Char(c) => format!("({})", c),
+        KeyCode::F(res) => format!("(F{})", res),
+        other => format!("({:?})", other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load(keys: Value) -> Result<Keymap> {
+        Keymap::load(keys.as_object())
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(parse_key("p").unwrap(), KeyCode::Char('p'));
+        assert_eq!(parse_key("P").unwrap(), KeyCode::Char('P'));
+        assert_eq!(parse_key("Shift+p").unwrap(), KeyCode::Char('P'));
+        assert_eq!(parse_key("space").unwrap(), KeyCode::Char(' '));
+        assert_eq!(parse_key("PageDown").unwrap(), KeyCode::PageDown);
+        assert_eq!(parse_key("F12").unwrap(), KeyCode::F(12));
+        assert!(parse_key("F13").is_err());
+        assert!(parse_key("Shift+1").is_err());
+        assert!(parse_key("Ctrl+p").err().unwrap().to_string().starts_with("Unknown key Ctrl+p"));
+        assert_eq!(parse_key("3").err().unwrap().to_string(), "3 sorts the grid by a column, digits cannot be bound");
+    }
+
+    #[test]
+    fn defaults() {
+        let keymap = load(json!({})).unwrap();
+
+        assert_eq!(keymap.action(&Mode::Normal, KeyCode::Char('k')), Some(Action::Up));
+        assert_eq!(keymap.action(&Mode::Normal, KeyCode::Char('n')), Some(Action::Publish));
+        assert_eq!(keymap.action(&Mode::Scroll, KeyCode::Char('n')), Some(Action::NextMatch));
+        assert_eq!(keymap.action(&Mode::Normal, KeyCode::Char('1')), None);
+        assert_eq!(keymap.popup_action(KeyCode::Char('j'), false), Some(Action::Down));
+        assert_eq!(keymap.popup_action(KeyCode::Char('j'), true), None);
+        assert_eq!(keymap.popup_action(KeyCode::Esc, true), Some(Action::Close));
+        assert_eq!(keymap.popup_action(KeyCode::Char('q'), false), None);
+        assert_eq!(keymap.hint(Action::Edit, "Edit").as_deref(), Some("(E)dit"));
+        assert_eq!(keymap.hint(Action::Close, "Close Window").as_deref(), Some("(Esc) Close Window"));
+        assert_eq!(keymap.hint_pair(Action::PageUp, Action::PageDown, "Page").as_deref(), Some("(Pg Up) (Pg Dn) Page"));
+    }
+
+    #[test]
+    fn config_keys_replace_the_defaults() {
+        let keymap = load(json!({"pause": ["p", "F9"], "publish": "Shift+N", "close": ["Esc", "q"], "up": []})).unwrap();
+
+        assert_eq!(keymap.action(&Mode::Scroll, KeyCode::F(9)), Some(Action::Pause));
+        assert_eq!(keymap.action(&Mode::Normal, KeyCode::Char('N')), Some(Action::Publish));
+        assert_eq!(keymap.action(&Mode::Normal, KeyCode::Char('P')), None);
+        assert_eq!(keymap.popup_action(KeyCode::Char('q'), false), Some(Action::Close));
+        assert_eq!(keymap.action(&Mode::Normal, KeyCode::Up), None);
+        assert_eq!(keymap.key_name(Action::Up), "(unbound)");
+        assert_eq!(keymap.hint(Action::Up, "Select"), None);
+        assert_eq!(keymap.hint(Action::Publish, "Publish"), Some("(Shift+N) Publish".to_string()));
+    }
+
+    #[test]
+    fn invalid_keys() {
+        let error = |keys: Value| load(keys).err().unwrap().to_string();
+
+        assert!(error(json!({"jump": "x"})).starts_with("Unknown action jump, expected one of quit, up"));
+        assert_eq!(error(json!({"pause": 1})), "pause: must be a key or a list of keys, found 1");
+        assert_eq!(error(json!({"pause": ["p", 1]})), "pause: keys must be strings, found 1");
+        assert!(error(json!({"pause": "Ctrl+p"})).starts_with("pause: Unknown key Ctrl+p"));
+        assert_eq!(error(json!({"sort": "1"})).split(',').next().unwrap(), "Unknown action sort");
+        assert_eq!(error(json!({"pause": "1"})), "pause: 1 sorts the grid by a column, digits cannot be bound");
+    }
+
+    #[test]
+    fn conflicts() {
+        let error = |keys: Value| load(keys).err().unwrap().to_string();
+
+        assert_eq!(error(json!({"pause": "s"})), "Conflicting key bindings: (s) is bound to both pause and save in normal mode");
+        assert_eq!(error(json!({"copy": "v"})), "Conflicting key bindings: (v) is bound to both view_mode and copy in paused mode");
+        assert_eq!(error(json!({"refresh": "e"})), "Conflicting key bindings: (e) is bound to both edit and refresh in popups");
+        assert_eq!(error(json!({"logs": "Up"})), "Conflicting key bindings: ↑ is bound to both up and logs in normal mode; ↑ is bound to both up and logs in paused mode");
+        //actions that never work at the same time can share keys
+        assert!(load(json!({"search": "s", "close": "q", "next_tab": "Tab"})).is_ok());
+    }
+}
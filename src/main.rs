@@ -1,7 +1,7 @@
 extern crate core;
 
-use std::{env, io};
-use std::io::Stdout;
+use std::{env, fs, io, process};
+use std::io::{Stdout, Write};
 use std::path::{Path, PathBuf};
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -14,9 +14,15 @@ use tui::{
 };
 use crate::amqp::Ampq;
 use crate::app::App;
-use anyhow::Result;
+use anyhow::{Result, Context, anyhow};
 use directories::BaseDirs;
-use crate::config::Config;
+use crate::cli::CliCommand;
+use crate::operations::Operation;
+use crate::tap_queues::TapQueues;
+use std::sync::atomic::Ordering;
+use amiquip::QueueDeleteOptions;
+use crate::config::{Config, ConfigFormat, CONFIG_FILE_STEM};
+use crate::wizard::Wizard;
 
 mod app;
 mod models;
@@ -25,6 +31,33 @@ mod amqp;
 mod config;
 mod theme;
 mod file_logger;
+mod secrets;
+mod validate;
+mod cli;
+mod management;
+mod browser;
+mod wizard;
+mod routing;
+mod topology;
+mod routing_tester;
+mod operations;
+mod definitions;
+mod tap_queues;
+mod message_filter;
+mod message_search;
+mod message_list;
+mod payload;
+mod decoders;
+mod avro;
+mod compression;
+mod json_view;
+mod projection;
+mod column_view;
+mod message_diff;
+mod message_tiles;
+mod keymap;
+
+const CONFIG_SCHEMA: &str = include_str!("../schema/amqp-client-cli.schema.json");
 
 fn main() -> Result<()> {
     let name = env!("CARGO_PKG_NAME");
@@ -33,59 +66,322 @@ fn main() -> Result<()> {
     println!("{} v{}", name, version);
     println!();
 
-    let path_to_config = find_config_file();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match cli::parse_args(&args)? {
+        CliCommand::Run { config_path } => run(config_path),
+        CliCommand::ConfigValidate { config_path } => validate_config(config_path),
+        CliCommand::ConfigSchema => {
+            println!("{}", CONFIG_SCHEMA);
+            Ok(())
+        }
+        CliCommand::Operation { operation, config_path, assume_yes } => run_operation(operation, config_path, assume_yes),
+        CliCommand::DefinitionsExport { path, from_broker, config_path } => export_definitions(path, from_broker, config_path),
+        CliCommand::DefinitionsImport { path, config_path, assume_yes } => import_definitions(path, config_path, assume_yes),
+        CliCommand::Cleanup { config_path, assume_yes } => cleanup_queues(config_path, assume_yes),
+        CliCommand::Help => {
+            println!("{}", cli::usage());
+            Ok(())
+        }
+    }
+}
+
+fn run(config_path: Option<String>) -> Result<()> {
+    let path_to_config = find_config_file(config_path.clone());
 
-    if let Some(res) = path_to_config {
-        let config = Config::read_config(Path::new(&res))?;
+    let config = match path_to_config {
+        Some(res) => Some(Config::read_config(Path::new(&res))?),
+        None => None
+    };
+
+    //the setup wizard draws before the config's theme is loaded
+    if theme::no_color() {
+        theme::set(theme::NO_COLOR);
+    }
+
+    // setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let config = match config {
+        Some(res) => res,
+        None => {
+            let wizard_path = config_path
+                .map(PathBuf::from)
+                .or_else(default_config_path)
+                .unwrap_or_else(|| PathBuf::from(format!("{}.json", CONFIG_FILE_STEM)));
+
+            match Wizard::new(wizard_path.as_path()).run(&mut terminal) {
+                Ok(Some(res)) => res,
+                Ok(None) => {
+                    cleanup(&mut terminal)?;
+                    return Ok(());
+                }
+                Err(e) => {
+                    cleanup(&mut terminal)?;
+                    eprintln!("Setup Error: {}", e);
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    let mut app = match App::new(config) {
+        Ok(res) => res,
+        Err(e) => {
+            cleanup(&mut terminal)?;
+            eprintln!("App Error: {}", e);
+            return Ok(());
+        }
+    };
+
+    //SIGINT and SIGTERM go through the app's normal exit, so its queues are deleted
+    ctrlc::set_handler(|| app::TERMINATE.store(true, Ordering::SeqCst)).ok();
+
+    match app.run_app(&mut terminal) {
+        Ok(_) => {}
+        Err(e) => {
+            cleanup(&mut terminal)?;
+            eprintln!("App Run Error: {}", e);
+            return Ok(());
+        }
+    }
+
+    // restore terminal
+    cleanup(&mut terminal)?;
+
+    Ok(())
+}
+
+fn run_operation(operation: Operation, config_path: Option<String>, assume_yes: bool) -> Result<()> {
+    let config = read_config_for_command(config_path)?;
+
+    if !assume_yes && !confirm(format!("{} on {}:{}?", operation.describe(), config.host, config.port).as_str())? {
+        println!("Cancelled");
+        return Ok(());
+    }
 
-        // setup terminal
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+    let mut connection = Ampq::connect(&config)?;
+    let channel = connection.open_channel(None)?;
+    let result = operation.execute(&channel);
+    connection.close().ok();
 
-        let mut app = match App::new(config) {
-            Ok(res) => res,
+    match result {
+        Ok(res) => {
+            println!("{}", res);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn export_definitions(path: String, from_broker: bool, config_path: Option<String>) -> Result<()> {
+    let config = read_config_for_command(config_path)?;
+
+    let definitions = if from_broker { definitions::fetch(&config)? } else { definitions::from_config(&config) };
+    definitions::write(&definitions, Path::new(&path))?;
+
+    println!();
+    println!("{} exchanges, {} queues and {} bindings written to {}", definitions.exchanges.len(), definitions.queues.len(), definitions.bindings.len(), path);
+    if !from_broker {
+        //the items' queues are tap queues named per session, so there is nothing lasting to bind
+        println!("Only exchanges are exported from the config, its items' queues and bindings only exist while subscribed. Use --broker to export every exchange, queue and binding.");
+    }
+
+    Ok(())
+}
+
+fn import_definitions(path: String, config_path: Option<String>, assume_yes: bool) -> Result<()> {
+    let config = read_config_for_command(config_path)?;
+    let (operations, skipped) = definitions::operations(&definitions::read(Path::new(&path))?, &config);
+
+    println!();
+    for message in skipped.iter() {
+        println!("{}", message);
+    }
+    for operation in operations.iter() {
+        println!("  {}", operation.describe());
+    }
+
+    if operations.is_empty() {
+        println!("Nothing to declare in {}", path);
+        return Ok(());
+    }
+
+    if !assume_yes && !confirm(format!("Run these {} declarations on {}:{}?", operations.len(), config.host, config.port).as_str())? {
+        println!("Cancelled");
+        return Ok(());
+    }
+
+    let mut connection = Ampq::connect(&config)?;
+    let mut failed = 0;
+
+    //the broker closes a channel when it refuses something, so each one gets its own
+    for operation in operations.iter() {
+        let result = connection.open_channel(None)
+            .map_err(anyhow::Error::from)
+            .and_then(|channel| {
+                let result = operation.execute(&channel);
+                channel.close().ok();
+                result
+            });
+
+        match result {
+            Ok(res) => println!("{}", res),
             Err(e) => {
-                cleanup(&mut terminal)?;
-                eprintln!("App Error: {}", e.to_string());
-                return Ok(());
+                failed += 1;
+                eprintln!("{}: {}", operation.describe(), e);
+            }
+        }
+    }
+
+    connection.close().ok();
+
+    if failed > 0 {
+        eprintln!();
+        eprintln!("{} of {} declaration(s) failed", failed, operations.len());
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn cleanup_queues(config_path: Option<String>, assume_yes: bool) -> Result<()> {
+    let config = read_config_for_command(config_path)?;
+    let tap_queues = TapQueues::new(&config);
+    let (queues, messages) = tap_queues::find_orphans(&config, &tap_queues);
+
+    println!();
+    for message in messages.iter() {
+        println!("{}", message);
+    }
+
+    if queues.is_empty() {
+        println!("No queues to clean up");
+        return Ok(());
+    }
+
+    for queue in queues.iter() {
+        println!("  {}", queue);
+    }
+
+    if !assume_yes && !confirm(format!("Delete these {} queue(s) on {}:{} unless they have consumers?", queues.len(), config.host, config.port).as_str())? {
+        println!("Cancelled");
+        return Ok(());
+    }
+
+    let mut connection = Ampq::connect(&config)?;
+
+    for queue in queues.iter() {
+        //a failed passive declare or delete closes the channel, so each queue gets its own
+        let channel = connection.open_channel(None)?;
+
+        let consumers = match channel.queue_declare_passive(queue.as_str()) {
+            Ok(res) => res.declared_consumer_count().unwrap_or(0),
+            Err(_) => {
+                println!("Already gone: {}", queue);
+                tap_queues.remove(queue.as_str()).unwrap_or_else(|e| eprintln!("Error tracking queue: {:#}", e));
+                continue;
             }
         };
 
-        match app.run_app(&mut terminal) {
-            Ok(_) => {}
-            Err(e) => {
-                cleanup(&mut terminal)?;
-                eprintln!("App Run Error: {}", e.to_string());
-                return Ok(());
+        if consumers > 0 {
+            println!("Still in use by {} consumer(s): {}", consumers, queue);
+            continue;
+        }
+
+        match channel.queue_delete(queue.as_str(), QueueDeleteOptions { if_unused: true, if_empty: false }) {
+            Ok(count) => {
+                println!("Queue Deleted: {} ({} messages)", queue, count);
+                tap_queues.remove(queue.as_str()).unwrap_or_else(|e| eprintln!("Error tracking queue: {:#}", e));
             }
+            Err(e) => eprintln!("Error deleting queue: {}, error: {}", queue, e)
         }
 
-        // restore terminal
-        cleanup(&mut terminal)?;
+        channel.close().ok();
     }
 
+    connection.close().ok();
+
     Ok(())
 }
 
-fn find_config_file() -> Option<String> {
+fn read_config_for_command(config_path: Option<String>) -> Result<Config> {
+    let path = command_config_file(config_path, "connect with")?;
+
+    Config::read_config(Path::new(&path))
+}
+
+/// The config a command runs with. A path that was given must exist, falling back to the
+/// default files could run the command against another broker.
+fn command_config_file(config_path: Option<String>, purpose: &str) -> Result<String> {
+    match config_path {
+        Some(path) if Path::new(&path).is_file() => Ok(path),
+        Some(path) => Err(anyhow!("Configuration file not found: {}", path)),
+        None => find_config_file(None).ok_or_else(|| anyhow!("No configuration file to {}", purpose))
+    }
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    println!();
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Where the setup wizard writes a new config file.
+fn default_config_path() -> Option<PathBuf> {
+    BaseDirs::new().map(|base_dirs| base_dirs.config_dir().join(format!("{}.json", CONFIG_FILE_STEM)))
+}
+
+fn validate_config(config_path: Option<String>) -> Result<()> {
+    let path = command_config_file(config_path, "validate")?;
+
+    let source = fs::read_to_string(&path).with_context(|| format!("Reading {}", path))?;
+    let errors = validate::validate(source.as_str(), ConfigFormat::from_path(Path::new(&path)));
+
+    println!();
+    if errors.is_empty() {
+        println!("{} is valid", path);
+        return Ok(());
+    }
+
+    for error in errors.iter() {
+        eprintln!("{}:{}", path, error);
+    }
+    eprintln!();
+    eprintln!("{} error(s) found", errors.len());
+
+    process::exit(1);
+}
+
+fn find_config_file(config_path: Option<String>) -> Option<String> {
     println!("Looking for configuration file.");
-    let args: Vec<String> = env::args().collect();
 
-    //paths to look for amqp-client-cli.json
+    //paths to look for amqp-client-cli.json, .toml, .yaml or .yml
     let mut paths_to_look: Vec<String> = vec![];
 
     //add argument path
-    if args.len() > 1 {
-        paths_to_look.push(args[1].to_string());
+    if let Some(res) = config_path {
+        paths_to_look.push(res);
     }
 
-    let file_name = "amqp-client-cli.json";
+    let file_names: Vec<String> = ConfigFormat::extensions()
+        .map(|extension| format!("{}.{}", CONFIG_FILE_STEM, extension))
+        .collect();
 
     //local execution path
-    paths_to_look.push(file_name.to_string());
+    paths_to_look.extend(file_names.iter().cloned());
 
     //add OS paths
     if let Some(base_dirs) = BaseDirs::new() {
@@ -93,21 +389,25 @@ fn find_config_file() -> Option<String> {
         // Windows: C:\Users\Alice\AppData\Roaming
         // macOS:   /Users/Alice/Library/Application Support
         let dir = base_dirs.config_dir().to_string_lossy().to_string();
-        let full_path: PathBuf = [dir, file_name.to_string()].iter().collect();
-        paths_to_look.push(full_path.to_string_lossy().to_string());
+        for file_name in file_names.iter() {
+            let full_path: PathBuf = [dir.clone(), file_name.to_string()].iter().collect();
+            paths_to_look.push(full_path.to_string_lossy().to_string());
+        }
 
 
         // Linux:   /home/alice
         // Windows: C:\Users\Alice
         // macOS:   /Users/Alice
         let dir = base_dirs.home_dir().to_string_lossy().to_string();
-        let full_path: PathBuf = [dir, file_name.to_string()].iter().collect();
-        paths_to_look.push(full_path.to_string_lossy().to_string());
+        for file_name in file_names.iter() {
+            let full_path: PathBuf = [dir.clone(), file_name.to_string()].iter().collect();
+            paths_to_look.push(full_path.to_string_lossy().to_string());
+        }
     }
 
-    for path in paths_to_look.iter().map(|x| Path::new(x)) {
+    for path in paths_to_look.iter().map(Path::new) {
         if path.exists() && path.is_file() {
-            println!("  Configuration file found at: {}", path.to_string_lossy().to_string());
+            println!("  Configuration file found at: {}", path.to_string_lossy());
             return Some(path.to_string_lossy().to_string());
         }
         else {
@@ -126,4 +426,21 @@ fn cleanup(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> std::io::Result
             DisableMouseCapture
         )?;
     terminal.show_cursor()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_given_config_file_must_exist() {
+        let missing = env::temp_dir().join(format!("amqp-client-cli-test-{}-missing.json", process::id())).to_string_lossy().to_string();
+        assert_eq!(command_config_file(Some(missing.clone()), "connect with").err().unwrap().to_string(), format!("Configuration file not found: {}", missing));
+        assert!(read_config_for_command(Some(missing)).is_err());
+
+        let existing = env::temp_dir().join(format!("amqp-client-cli-test-{}-existing.json", process::id()));
+        fs::write(&existing, "{}").unwrap();
+        let found = command_config_file(Some(existing.to_string_lossy().to_string()), "connect with");
+        fs::remove_file(&existing).unwrap();
+        assert_eq!(found.unwrap(), existing.to_string_lossy());
+    }
+}
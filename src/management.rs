@@ -0,0 +1,281 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use anyhow::{Result, Context, anyhow};
+use crossbeam::channel::{Receiver, unbounded};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use native_tls::TlsConnector;
+use serde::de::DeserializeOwned;
+use ureq::Agent;
+use crate::Config;
+use crate::models::definitions::Definitions;
+use crate::models::management::{BindingInfo, ExchangeInfo, ManagementSnapshot, QueueInfo, VhostInfo};
+
+/// Client for the RabbitMQ management HTTP API, e.g. `http://127.0.0.1:15672`.
+pub struct ManagementClient {
+    agent: Agent,
+    base_url: String,
+    username: String,
+    password: String,
+    vhost: String
+}
+
+impl ManagementClient {
+    pub fn new(base_url: &str, username: &str, password: &str, vhost: Option<&str>) -> Result<Self> {
+        let agent = ureq::AgentBuilder::new()
+            .tls_connector(Arc::new(TlsConnector::new()?))
+            .timeout(Duration::from_secs(10))
+            .build();
+
+        //an empty vhost is the broker's default vhost "/"
+        let vhost = match vhost {
+            Some(res) if !res.is_empty() => res.to_string(),
+            _ => "/".to_string()
+        };
+
+        Ok(
+            ManagementClient {
+                agent,
+                base_url: base_url.trim_end_matches('/').to_string(),
+                username: username.to_string(),
+                password: password.to_string(),
+                vhost
+            }
+        )
+    }
+
+    pub fn vhosts(&self) -> Result<Vec<VhostInfo>> {
+        self.get("/api/vhosts")
+    }
+
+    pub fn exchanges(&self) -> Result<Vec<ExchangeInfo>> {
+        self.get(format!("/api/exchanges/{}", encode_segment(self.vhost.as_str())).as_str())
+    }
+
+    pub fn queues(&self) -> Result<Vec<QueueInfo>> {
+        self.get(format!("/api/queues/{}", encode_segment(self.vhost.as_str())).as_str())
+    }
+
+    pub fn bindings(&self) -> Result<Vec<BindingInfo>> {
+        self.get(format!("/api/bindings/{}", encode_segment(self.vhost.as_str())).as_str())
+    }
+
+    pub fn definitions(&self) -> Result<Definitions> {
+        self.get(format!("/api/definitions/{}", encode_segment(self.vhost.as_str())).as_str())
+    }
+
+    pub fn snapshot(&self) -> Result<ManagementSnapshot> {
+        let mut exchanges = self.exchanges()?;
+        exchanges.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut queues = self.queues()?;
+        queues.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(
+            ManagementSnapshot {
+                vhost: self.vhost.clone(),
+                vhosts: self.vhosts()?,
+                exchanges,
+                queues,
+                bindings: self.bindings()?
+            }
+        )
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let url = format!("{}{}", self.base_url, path);
+
+        let response = self.agent
+            .get(url.as_str())
+            .set("Authorization", basic_auth(self.username.as_str(), self.password.as_str()).as_str())
+            .call()
+            .with_context(|| format!("Requesting {}", url))?;
+
+        let value = response.into_json::<T>()
+            .with_context(|| format!("Reading response from {}", url))?;
+
+        Ok(value)
+    }
+}
+
+/// A snapshot being fetched on a background thread so the UI keeps drawing while it waits.
+pub struct SnapshotRequest {
+    receiver: Receiver<Result<ManagementSnapshot, String>>
+}
+
+impl SnapshotRequest {
+    /// Fetches `vhost`, or the vhost from the config when `None`.
+    pub fn spawn(config: &Config, vhost: Option<String>) -> Result<Self> {
+        let management_url = match &config.management_url {
+            Some(res) if !res.is_empty() => res.clone(),
+            _ => return Err(anyhow!("management_url is not set in the config file"))
+        };
+
+        let username = config.username.clone();
+        let password = config.password.clone();
+        let vhost = vhost.or_else(|| config.vhost.clone());
+
+        let (sender, receiver) = unbounded();
+
+        thread::spawn(move || {
+            let snapshot = ManagementClient::new(management_url.as_str(), username.as_str(), password.as_str(), vhost.as_deref())
+                .and_then(|client| client.snapshot())
+                .map_err(|e| format!("{:#}", e));
+
+            sender.send(snapshot).ok();
+        });
+
+        Ok(SnapshotRequest { receiver })
+    }
+
+    /// `None` while the request is still running.
+    pub fn try_take(&self) -> Option<Result<ManagementSnapshot, String>> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Percent-encodes a vhost or resource name for use as one path segment, so `/` becomes `%2F`,
+/// or a user name or password for the user info of a URL.
+pub fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::new();
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(format!("%{:02X}", byte).as_str())
+        }
+    }
+    encoded
+}
+
+fn basic_auth(username: &str, password: &str) -> String {
+    format!("Basic {}", STANDARD.encode(format!("{}:{}", username, password)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use crate::models::enums::ExchangeTypeSer;
+
+    /// Answers each request with the body for its path, or 404, after checking the credentials,
+    /// and records the request lines it was sent.
+    fn mock_server(routes: Vec<(&'static str, &'static str)>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let thread_requests = requests.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut authorized = false;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    authorized |= header.trim() == format!("Authorization: {}", basic_auth("guest", "secret"));
+                }
+                thread_requests.lock().unwrap().push(request_line.trim().to_string());
+
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match routes.iter().find(|x| x.0 == path) {
+                    _ if !authorized => ("401 Unauthorized", r#"{"error":"not_authorised"}"#),
+                    Some(res) => ("200 OK", res.1),
+                    None => ("404 Not Found", r#"{"error":"Object Not Found"}"#)
+                };
+                write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    const ROUTES: [(&str, &str); 4] = [
+        ("/api/vhosts", r#"[{"name":"/"},{"name":"staging"}]"#),
+        ("/api/exchanges/%2F", r#"[{"name":"orders","type":"topic","durable":true},{"name":"amq.direct","type":"direct"},{"name":"","type":"direct"}]"#),
+        ("/api/queues/%2F", r#"[{"name":"b","messages":3,"consumers":1,"message_stats":{"publish_details":{"rate":2.5}}},{"name":"a"}]"#),
+        ("/api/bindings/%2F", r#"[{"source":"orders","destination":"b","destination_type":"queue","routing_key":"order.*","arguments":{}}]"#),
+    ];
+
+    #[test]
+    fn lists_vhosts_exchanges_queues_and_bindings() {
+        let (url, requests) = mock_server(ROUTES.to_vec());
+        let client = ManagementClient::new(format!("{}/", url).as_str(), "guest", "secret", None).unwrap();
+
+        let vhosts: Vec<String> = client.vhosts().unwrap().into_iter().map(|x| x.name).collect();
+        assert_eq!(vhosts, vec!["/", "staging"]);
+
+        let exchanges = client.exchanges().unwrap();
+        assert_eq!(exchanges.len(), 3);
+        assert!(matches!(exchanges[0].exchange_type_ser(), Some(ExchangeTypeSer::Topic)));
+        assert!(!exchanges[0].is_builtin());
+        assert!(exchanges[1].is_builtin() && exchanges[2].is_builtin());
+
+        let queues = client.queues().unwrap();
+        assert_eq!(queues[0].messages, Some(3));
+        assert_eq!(queues[0].publish_rate(), 2.5);
+        assert_eq!(queues[1].consumers, None);
+        assert_eq!(queues[1].deliver_rate(), 0.0);
+
+        let bindings = client.bindings().unwrap();
+        assert_eq!((bindings[0].source.as_str(), bindings[0].destination.as_str(), bindings[0].routing_key.as_str()), ("orders", "b", "order.*"));
+
+        //the default vhost is sent encoded as one path segment
+        assert_eq!(requests.lock().unwrap()[1], "GET /api/exchanges/%2F HTTP/1.1");
+    }
+
+    #[test]
+    fn snapshot_sorts_by_name() {
+        let (url, _) = mock_server(ROUTES.to_vec());
+        let snapshot = ManagementClient::new(url.as_str(), "guest", "secret", Some("")).unwrap().snapshot().unwrap();
+
+        assert_eq!(snapshot.vhost, "/");
+        let queues: Vec<&str> = snapshot.queues.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(queues, vec!["a", "b"]);
+        let exchanges: Vec<&str> = snapshot.exchanges.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(exchanges, vec!["", "amq.direct", "orders"]);
+    }
+
+    #[test]
+    fn other_vhosts_are_encoded() {
+        let (url, requests) = mock_server(vec![("/api/queues/my%20vhost%2Fa", "[]")]);
+        let client = ManagementClient::new(url.as_str(), "guest", "secret", Some("my vhost/a")).unwrap();
+
+        assert!(client.queues().unwrap().is_empty());
+        assert_eq!(requests.lock().unwrap()[0], "GET /api/queues/my%20vhost%2Fa HTTP/1.1");
+    }
+
+    #[test]
+    fn url_user_info_is_encoded() {
+        assert_eq!(encode_segment("p@ss/w:rd%é"), "p%40ss%2Fw%3Ard%25%C3%A9");
+        assert_eq!(encode_segment("guest-1.a_b~"), "guest-1.a_b~");
+    }
+
+    #[test]
+    fn wrong_credentials_fail() {
+        let (url, _) = mock_server(ROUTES.to_vec());
+        let client = ManagementClient::new(url.as_str(), "guest", "wrong", None).unwrap();
+
+        let error = format!("{:#}", client.vhosts().unwrap_err());
+        assert!(error.starts_with(format!("Requesting {}/api/vhosts", url).as_str()), "{}", error);
+        assert!(error.contains("401"), "{}", error);
+    }
+
+    #[test]
+    fn unexpected_responses_fail() {
+        let (url, _) = mock_server(vec![("/api/vhosts", r#"{"not":"a list"}"#)]);
+        let client = ManagementClient::new(url.as_str(), "guest", "secret", None).unwrap();
+
+        assert!(format!("{:#}", client.vhosts().unwrap_err()).starts_with("Reading response from"));
+        assert!(client.exchanges().unwrap_err().to_string().starts_with("Requesting"));
+    }
+}
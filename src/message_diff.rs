@@ -0,0 +1,322 @@
+use serde_json::Value;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph};
+use crate::message_list::MessageRecord;
+use crate::payload;
+use crate::theme;
+
+/// The most cells of the table used to line up the lines of two bodies, about 32 MB.
+const MAX_LINE_UP: usize = 4_000_000;
+
+/// Two messages side by side: the paths that were added, removed or changed when both bodies are
+/// JSON, otherwise their lines with the ones that differ marked.
+pub struct MessageDiff {
+    older_title: String,
+    newer_title: String,
+    rows: Vec<DiffRow>,
+    summary: String,
+    scroll: u16
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Change {
+    Same,
+    Added,
+    Removed,
+    Changed
+}
+
+/// One line of each side, empty on the side that does not have it.
+struct DiffRow {
+    change: Change,
+    older: String,
+    newer: String
+}
+
+impl MessageDiff {
+    pub fn new() -> Self {
+        MessageDiff {
+            older_title: "".to_string(),
+            newer_title: "".to_string(),
+            rows: vec![],
+            summary: "".to_string(),
+            scroll: 0
+        }
+    }
+
+    pub fn open(&mut self, older: &MessageRecord, newer: &MessageRecord) {
+        self.older_title = title(older);
+        self.newer_title = title(newer);
+        self.scroll = 0;
+
+        let older_json = payload::json_value(&older.body, older.decoded.as_ref());
+        let newer_json = payload::json_value(&newer.body, newer.decoded.as_ref());
+
+        match older_json.zip(newer_json) {
+            Some((older_json, newer_json)) => {
+                let mut rows = vec![];
+                let unchanged = diff_json("$", &older_json, &newer_json, &mut rows);
+                self.summary = format!("JSON: {}, {} unchanged", count_changes(&rows), unchanged);
+                self.rows = rows;
+            }
+            None => {
+                let (rows, lined_up) = diff_lines(older.body_lines(), newer.body_lines());
+                let unchanged = rows.iter().filter(|x| x.change == Change::Same).count();
+                self.summary = format!("Lines: {}, {} unchanged", count_changes(&rows), unchanged);
+                if !lined_up {
+                    self.summary.push_str(", too large to line up, compared line by line");
+                }
+                self.rows = rows;
+            }
+        }
+    }
+
+    pub fn scroll_up(&mut self, lines: u16) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    pub fn scroll_down(&mut self, lines: u16) {
+        self.scroll = (self.scroll + lines).min(self.rows.len().saturating_sub(1) as u16);
+    }
+
+    pub fn draw<B: Backend>(&self, frame: &mut Frame<B>, area: Rect) {
+        let grid = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+            .split(area);
+
+        let sides = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(grid[1]);
+
+        let summary = Paragraph::new(self.summary.as_str())
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled("Diff", Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))));
+
+        let older: Vec<Spans> = self.rows.iter().map(|x| styled(x.older.as_str(), x.change, Change::Removed)).collect();
+        let newer: Vec<Spans> = self.rows.iter().map(|x| styled(x.newer.as_str(), x.change, Change::Added)).collect();
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(summary, grid[0]);
+        frame.render_widget(side(older, self.older_title.as_str(), self.scroll), sides[0]);
+        frame.render_widget(side(newer, self.newer_title.as_str(), self.scroll), sides[1]);
+    }
+}
+
+fn title(record: &MessageRecord) -> String {
+    format!("{} {} {}", record.timestamp.format("%H:%M:%S%.3f"), record.name, record.routing_key)
+}
+
+/// `2 changed, 1 added, 0 removed`
+fn count_changes(rows: &[DiffRow]) -> String {
+    let count = |change: Change| rows.iter().filter(|x| x.change == change).count();
+    format!("{} changed, {} added, {} removed", count(Change::Changed), count(Change::Added), count(Change::Removed))
+}
+
+/// A line of one side, coloured by what happened to it. `only` is the change that has this side
+/// alone, removed on the older side and added on the newer one.
+fn styled(line: &str, change: Change, only: Change) -> Spans<'static> {
+    let color = match change {
+        Change::Same => theme::current().foreground,
+        Change::Changed => theme::current().diff_changed,
+        _ if change == only => if only == Change::Added { theme::current().diff_added } else { theme::current().diff_removed },
+        _ => theme::current().pending
+    };
+
+    Spans::from(Span::styled(line.to_string(), Style::default().fg(color)))
+}
+
+fn side(lines: Vec<Spans<'static>>, title: &str, scroll: u16) -> Paragraph<'static> {
+    Paragraph::new(lines)
+        .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+        .block(Block::default().borders(Borders::ALL).title(Span::styled(title.to_string(), Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
+        .scroll((scroll, 0))
+}
+
+/// Adds a row for every path under `path` that differs, objects by key and arrays by index.
+/// Returns the number of values that are the same.
+fn diff_json(path: &str, older: &Value, newer: &Value, rows: &mut Vec<DiffRow>) -> usize {
+    match (older, newer) {
+        (Value::Object(older), Value::Object(newer)) => {
+            let mut unchanged = 0;
+            for (key, value) in older {
+                let child = format!("{}{}", path, key_segment(key));
+                match newer.get(key) {
+                    Some(res) => unchanged += diff_json(child.as_str(), value, res, rows),
+                    None => rows.push(DiffRow { change: Change::Removed, older: format!("{}: {}", child, value), newer: "".to_string() })
+                }
+            }
+            for (key, value) in newer.iter().filter(|x| !older.contains_key(x.0)) {
+                rows.push(DiffRow { change: Change::Added, older: "".to_string(), newer: format!("{}{}: {}", path, key_segment(key), value) });
+            }
+            unchanged
+        }
+        (Value::Array(older), Value::Array(newer)) => {
+            let mut unchanged = 0;
+            for i in 0..older.len().max(newer.len()) {
+                let child = format!("{}[{}]", path, i);
+                match (older.get(i), newer.get(i)) {
+                    (Some(a), Some(b)) => unchanged += diff_json(child.as_str(), a, b, rows),
+                    (Some(a), None) => rows.push(DiffRow { change: Change::Removed, older: format!("{}: {}", child, a), newer: "".to_string() }),
+                    (None, Some(b)) => rows.push(DiffRow { change: Change::Added, older: "".to_string(), newer: format!("{}: {}", child, b) }),
+                    (None, None) => {}
+                }
+            }
+            unchanged
+        }
+        _ if older == newer => 1,
+        _ => {
+            rows.push(DiffRow { change: Change::Changed, older: format!("{}: {}", path, older), newer: format!("{}: {}", path, newer) });
+            0
+        }
+    }
+}
+
+/// `.key`, or `['key']` for keys a dotted path cannot hold.
+fn key_segment(key: &str) -> String {
+    if key.is_empty() || key.contains(['.', '[', ']', '\'', ' ']) {
+        format!("['{}']", key)
+    }
+    else {
+        format!(".{}", key)
+    }
+}
+
+/// Lines of both bodies lined up by their longest common subsequence. Lines removed right before
+/// lines were added are shown next to them as changed. The lines both bodies start and end with
+/// are taken off first; when what is left would need a table of more than `MAX_LINE_UP` cells,
+/// it is paired line by line instead and the second value is false.
+fn diff_lines(older: &[String], newer: &[String]) -> (Vec<DiffRow>, bool) {
+    let prefix = older.iter().zip(newer).take_while(|(a, b)| a == b).count();
+    let suffix = older[prefix..].iter().rev().zip(newer[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+
+    let same = |lines: &[String]| lines.iter().map(|x| DiffRow { change: Change::Same, older: x.clone(), newer: x.clone() }).collect::<Vec<DiffRow>>();
+    let older_middle = &older[prefix..older.len() - suffix];
+    let newer_middle = &newer[prefix..newer.len() - suffix];
+
+    let mut rows = same(&older[..prefix]);
+    let lined_up = (older_middle.len() + 1).saturating_mul(newer_middle.len() + 1) <= MAX_LINE_UP;
+    if lined_up {
+        line_up(older_middle, newer_middle, &mut rows);
+    }
+    else {
+        pair(&mut older_middle.to_vec(), &mut newer_middle.to_vec(), &mut rows);
+    }
+    rows.extend(same(&older[older.len() - suffix..]));
+
+    (rows, lined_up)
+}
+
+fn line_up(older: &[String], newer: &[String], rows: &mut Vec<DiffRow>) {
+    //common[i][j] is the length of the longest common subsequence of older[i..] and newer[j..]
+    let mut common = vec![vec![0usize; newer.len() + 1]; older.len() + 1];
+    for i in (0..older.len()).rev() {
+        for j in (0..newer.len()).rev() {
+            common[i][j] = if older[i] == newer[j] { common[i + 1][j + 1] + 1 } else { common[i + 1][j].max(common[i][j + 1]) };
+        }
+    }
+
+    let mut removed: Vec<String> = vec![];
+    let mut added: Vec<String> = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < older.len() || j < newer.len() {
+        if i < older.len() && j < newer.len() && older[i] == newer[j] {
+            pair(&mut removed, &mut added, rows);
+            rows.push(DiffRow { change: Change::Same, older: older[i].clone(), newer: newer[j].clone() });
+            i += 1;
+            j += 1;
+        }
+        else if j == newer.len() || (i < older.len() && common[i + 1][j] >= common[i][j + 1]) {
+            removed.push(older[i].clone());
+            i += 1;
+        }
+        else {
+            added.push(newer[j].clone());
+            j += 1;
+        }
+    }
+    pair(&mut removed, &mut added, rows);
+}
+
+/// Turns a run of removed and added lines into rows, side by side as far as both go.
+fn pair(removed: &mut Vec<String>, added: &mut Vec<String>, rows: &mut Vec<DiffRow>) {
+    let mut removed = std::mem::take(removed).into_iter();
+    let mut added = std::mem::take(added).into_iter();
+
+    loop {
+        let row = match (removed.next(), added.next()) {
+            (Some(older), Some(newer)) => DiffRow { change: Change::Changed, older, newer },
+            (Some(older), None) => DiffRow { change: Change::Removed, older, newer: "".to_string() },
+            (None, Some(newer)) => DiffRow { change: Change::Added, older: "".to_string(), newer },
+            (None, None) => break
+        };
+        rows.push(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split(' ').map(|x| x.to_string()).collect()
+    }
+
+    fn changes(rows: &[DiffRow]) -> Vec<(Change, &str, &str)> {
+        rows.iter().map(|x| (x.change, x.older.as_str(), x.newer.as_str())).collect()
+    }
+
+    #[test]
+    fn lines_are_lined_up() {
+        let (rows, lined_up) = diff_lines(&lines("a b c d e"), &lines("a x c d y e f"));
+
+        assert!(lined_up);
+        assert!(changes(&rows) == vec![
+            (Change::Same, "a", "a"),
+            (Change::Changed, "b", "x"),
+            (Change::Same, "c", "c"),
+            (Change::Same, "d", "d"),
+            (Change::Added, "", "y"),
+            (Change::Same, "e", "e"),
+            (Change::Added, "", "f")
+        ]);
+    }
+
+    #[test]
+    fn large_bodies_are_paired_between_the_common_start_and_end() {
+        let older: Vec<String> = ["head".to_string()].into_iter().chain((0..3000).map(|x| x.to_string())).chain(["tail".to_string()]).collect();
+        let newer: Vec<String> = ["head".to_string()].into_iter().chain((0..2500).map(|x| format!("n{}", x))).chain(["tail".to_string()]).collect();
+        let (rows, lined_up) = diff_lines(&older, &newer);
+
+        assert!(!lined_up);
+        assert_eq!(rows.len(), 3002);
+        assert!(rows[0].change == Change::Same && rows[3001].change == Change::Same);
+        assert!(rows[1].change == Change::Changed && rows[2501].change == Change::Removed);
+
+        //the same lines with only a few in the middle differing are still lined up
+        let mut edited = older.clone();
+        edited[1500] = "edited".to_string();
+        assert!(diff_lines(&older, &edited).1);
+    }
+
+    #[test]
+    fn json_paths() {
+        let mut rows = vec![];
+        let unchanged = diff_json("$", &json!({"a": 1, "b": [1, 2], "c": {"d": true}, "e f": 0}), &json!({"a": 2, "b": [1], "c": {"d": true}, "g": null, "e f": 0}), &mut rows);
+
+        assert_eq!(unchanged, 3);
+        assert!(changes(&rows) == vec![
+            (Change::Changed, "$.a: 1", "$.a: 2"),
+            (Change::Removed, "$.b[1]: 2", ""),
+            (Change::Added, "", "$.g: null")
+        ]);
+        assert_eq!(count_changes(&rows), "1 changed, 1 added, 1 removed");
+    }
+}
@@ -0,0 +1,301 @@
+use std::cmp::Ordering;
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use serde_json::Value;
+
+/// Decides which deliveries of a subscription are shown. Written as one of:
+/// - `text`: the body contains `text`
+/// - `/pattern/`: the body matches the regular expression
+/// - `$.path OP value`: a JSONPath predicate on a JSON body, e.g. `$.order.status == "FAILED"`,
+///   with `==`, `!=`, `<`, `<=`, `>`, `>=` or `=~` (regex), or just `$.path` for "exists"
+pub enum MessageFilter {
+    Substring(String),
+    Regex(Regex),
+    JsonPath { path: Vec<PathSegment>, predicate: Option<Predicate> }
+}
+
+pub struct Predicate {
+    comparison: Comparison,
+    value: Value,
+    /// Compiled once for `=~`.
+    regex: Option<Regex>
+}
+
+pub enum PathSegment {
+    Field(String),
+    Index(usize),
+    Wildcard
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Matches
+}
+
+impl MessageFilter {
+    /// `None` for an empty expression, which lets everything through.
+    pub fn parse(expression: &str) -> Result<Option<Self>> {
+        let expression = expression.trim();
+
+        if expression.is_empty() {
+            return Ok(None);
+        }
+
+        if expression.len() >= 2 && expression.starts_with('/') && expression.ends_with('/') {
+            let pattern = &expression[1..expression.len() - 1];
+            let regex = Regex::new(pattern).map_err(|e| anyhow!("Invalid filter regex: {}", e))?;
+            return Ok(Some(MessageFilter::Regex(regex)));
+        }
+
+        if expression.starts_with('$') {
+            return parse_json_path(expression).map(Some);
+        }
+
+        Ok(Some(MessageFilter::Substring(expression.to_string())))
+    }
+
+    pub fn matches(&self, body: &str) -> bool {
+        match self {
+            MessageFilter::Substring(res) => body.contains(res.as_str()),
+            MessageFilter::Regex(res) => res.is_match(body),
+            MessageFilter::JsonPath { path, predicate } => {
+                let document: Value = match serde_json::from_str(body) {
+                    Ok(res) => res,
+                    Err(_) => return false
+                };
+
+                let mut selected = vec![];
+                select(&document, path, &mut selected);
+
+                match predicate {
+                    None => !selected.is_empty(),
+                    Some(predicate) => selected.iter().any(|x| predicate.test(x))
+                }
+            }
+        }
+    }
+}
+
+fn parse_json_path(expression: &str) -> Result<MessageFilter> {
+    let operators = [("==", Comparison::Equal), ("!=", Comparison::NotEqual), ("=~", Comparison::Matches), ("<=", Comparison::LessOrEqual), (">=", Comparison::GreaterOrEqual), ("<", Comparison::Less), (">", Comparison::Greater)];
+
+    //the first operator outside of a quoted field name splits path and value
+    let mut split = None;
+    let mut quote: Option<char> = None;
+    for (i, c) in expression.char_indices() {
+        match quote {
+            Some(res) if c == res => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None => {
+                if let Some(operator) = operators.iter().find(|x| expression[i..].starts_with(x.0)) {
+                    split = Some((i, *operator));
+                    break;
+                }
+            }
+        }
+    }
+
+    let (path, predicate) = match split {
+        None => (expression, None),
+        Some((i, (operator, comparison))) => {
+            let literal = expression[i + operator.len()..].trim();
+            let value: Value = serde_json::from_str(literal).map_err(|_| anyhow!("Invalid filter value {}, expected a JSON value such as \"text\", 42 or true", literal))?;
+
+            let regex = if comparison == Comparison::Matches {
+                let pattern = value.as_str().ok_or_else(|| anyhow!("=~ needs a quoted regular expression"))?;
+                Some(Regex::new(pattern).map_err(|e| anyhow!("Invalid filter regex: {}", e))?)
+            }
+            else {
+                None
+            };
+
+            (&expression[..i], Some(Predicate { comparison, value, regex }))
+        }
+    };
+
+    Ok(MessageFilter::JsonPath { path: parse_path(path.trim())?, predicate })
+}
+
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>> {
+    let invalid = || anyhow!("Invalid JSONPath {}, expected e.g. $.order.items[0].status", path);
+
+    let mut segments = vec![];
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let name = &after[..end];
+            segments.push(match name {
+                "" => return Err(invalid()),
+                "*" => PathSegment::Wildcard,
+                _ => PathSegment::Field(name.to_string())
+            });
+            rest = &after[end..];
+        }
+        else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inside = after[..end].trim();
+            segments.push(match inside {
+                "*" => PathSegment::Wildcard,
+                _ if inside.len() >= 2 && (inside.starts_with('\'') || inside.starts_with('"')) => PathSegment::Field(inside[1..inside.len() - 1].to_string()),
+                _ => PathSegment::Index(inside.parse().map_err(|_| invalid())?)
+            });
+            rest = &after[end + 1..];
+        }
+        else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(segments)
+}
+
+pub fn select<'a>(value: &'a Value, path: &[PathSegment], selected: &mut Vec<&'a Value>) {
+    let (segment, rest) = match path.split_first() {
+        Some(res) => res,
+        None => {
+            selected.push(value);
+            return;
+        }
+    };
+
+    match (segment, value) {
+        (PathSegment::Field(name), Value::Object(map)) => {
+            if let Some(res) = map.get(name) {
+                select(res, rest, selected);
+            }
+        }
+        (PathSegment::Index(index), Value::Array(list)) => {
+            if let Some(res) = list.get(*index) {
+                select(res, rest, selected);
+            }
+        }
+        (PathSegment::Wildcard, Value::Object(map)) => map.values().for_each(|x| select(x, rest, selected)),
+        (PathSegment::Wildcard, Value::Array(list)) => list.iter().for_each(|x| select(x, rest, selected)),
+        _ => {}
+    }
+}
+
+impl Predicate {
+    fn test(&self, actual: &Value) -> bool {
+        let ordering = match (actual, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64().and_then(|a| b.as_f64().and_then(|b| a.partial_cmp(&b))),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None
+        };
+
+        match self.comparison {
+            Comparison::Equal => ordering == Some(Ordering::Equal),
+            Comparison::NotEqual => ordering != Some(Ordering::Equal),
+            Comparison::Less => ordering == Some(Ordering::Less),
+            Comparison::LessOrEqual => matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal)),
+            Comparison::Greater => ordering == Some(Ordering::Greater),
+            Comparison::GreaterOrEqual => matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal)),
+            Comparison::Matches => match (actual.as_str(), &self.regex) {
+                (Some(text), Some(regex)) => regex.is_match(text),
+                _ => false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(expression: &str, body: &str) -> bool {
+        MessageFilter::parse(expression).unwrap().unwrap().matches(body)
+    }
+
+    const ORDER: &str = r#"{"order": {"id": 42, "status": "FAILED", "items": [{"sku": "a-1", "qty": 2}, {"sku": "b-2", "qty": 5}], "note key": "x", "paid": false, "coupon": null}}"#;
+
+    #[test]
+    fn empty_expression_lets_everything_through() {
+        assert!(MessageFilter::parse("  ").unwrap().is_none());
+    }
+
+    #[test]
+    fn substring_and_regex() {
+        assert!(matches("FAILED", ORDER));
+        assert!(!matches("failed", ORDER));
+        assert!(matches("/\"status\":\\s*\"FAIL/", ORDER));
+        assert!(matches("/(?i)failed/", ORDER));
+        assert!(!matches("/^FAILED$/", ORDER));
+        //a single slash is text
+        assert!(matches("/", "a/b"));
+        assert!(MessageFilter::parse("/(/").is_err());
+    }
+
+    #[test]
+    fn path_exists() {
+        assert!(matches("$.order.status", ORDER));
+        assert!(matches("$.order.coupon", ORDER));
+        assert!(!matches("$.order.missing", ORDER));
+        assert!(matches("$.order.items[1].sku", ORDER));
+        assert!(!matches("$.order.items[2]", ORDER));
+        assert!(matches("$['order']['note key']", ORDER));
+        assert!(!matches("$.order", "not json"));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(matches("$.order.status == \"FAILED\"", ORDER));
+        assert!(!matches("$.order.status != \"FAILED\"", ORDER));
+        assert!(matches("$.order.id == 42", ORDER));
+        assert!(matches("$.order.id == 42.0", ORDER));
+        assert!(matches("$.order.id >= 42", ORDER));
+        assert!(!matches("$.order.id > 42", ORDER));
+        assert!(matches("$.order.id < 43", ORDER));
+        assert!(matches("$.order.id <= 42", ORDER));
+        assert!(matches("$.order.paid == false", ORDER));
+        assert!(matches("$.order.coupon == null", ORDER));
+        assert!(matches("$.order.status > \"ABC\"", ORDER));
+        //values of different types never compare
+        assert!(!matches("$.order.id == \"42\"", ORDER));
+        assert!(!matches("$.order.id < \"50\"", ORDER));
+        assert!(matches("$.order.id != \"42\"", ORDER));
+    }
+
+    #[test]
+    fn wildcards_match_any_element() {
+        assert!(matches("$.order.items[*].qty > 4", ORDER));
+        assert!(!matches("$.order.items[*].qty > 5", ORDER));
+        assert!(matches("$.order.items.*.sku == \"a-1\"", ORDER));
+        assert!(matches("$.*.id == 42", ORDER));
+    }
+
+    #[test]
+    fn regex_comparison() {
+        assert!(matches("$.order.status =~ \"^FAIL\"", ORDER));
+        assert!(matches("$.order.items[*].sku =~ \"^b-\\\\d$\"", ORDER));
+        assert!(!matches("$.order.id =~ \"42\"", ORDER));
+        assert_eq!(MessageFilter::parse("$.order.status =~ 5").err().unwrap().to_string(), "=~ needs a quoted regular expression");
+        assert!(MessageFilter::parse("$.order.status =~ \"(\"").err().unwrap().to_string().starts_with("Invalid filter regex"));
+    }
+
+    #[test]
+    fn operators_inside_quoted_names_do_not_split() {
+        let body = r#"{"a==b": 1, "c": "x==y"}"#;
+        assert!(matches("$['a==b'] == 1", body));
+        assert!(matches("$.c == \"x==y\"", body));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!(MessageFilter::parse("$.order.status == FAILED").err().unwrap().to_string().starts_with("Invalid filter value FAILED"));
+        assert!(MessageFilter::parse("$.order..status").is_err());
+        assert!(MessageFilter::parse("$.items[x]").is_err());
+        assert!(MessageFilter::parse("$.items[0").is_err());
+        assert!(MessageFilter::parse("$order").is_err());
+        assert!(parse_path("order").is_err());
+    }
+}
@@ -0,0 +1,513 @@
+use std::collections::VecDeque;
+use std::io::{stdout, Write};
+use anyhow::Result;
+use bevy_reflect::Uuid;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Local};
+use itertools::Itertools;
+use serde_json::Value;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap};
+use crate::theme;
+use crate::json_view::JsonView;
+use crate::projection;
+use crate::projection::Projection;
+use crate::message_search::MessageSearch;
+use crate::models::read_value::ReadValue;
+use crate::payload;
+use crate::payload::ViewMode;
+
+/// One received message, kept whole so it can be selected and inspected.
+pub struct MessageRecord {
+    pub item_id: Uuid,
+    /// The item's alias or exchange name.
+    pub name: String,
+    pub exchange_name: String,
+    pub routing_key: String,
+    pub body: Vec<u8>,
+    pub decoded: Option<Result<Value, String>>,
+    view_mode: ViewMode,
+    /// The body rendered for `view_mode`.
+    rendered: Vec<String>,
+    /// The body laid out for the JSON mode, when it is JSON.
+    json: Option<JsonView>,
+    /// The item's fields with their values, when it has fields and the body is JSON.
+    pub fields: Option<Vec<(String, Option<String>)>>,
+    pub properties: Vec<(String, String)>,
+    pub timestamp: DateTime<Local>
+}
+
+impl MessageRecord {
+    pub fn new(read_value: &ReadValue, name: &str, pretty: bool, projection: Option<&Projection>) -> Self {
+        let json = payload::json_value(&read_value.body, read_value.decoded.as_ref());
+        let view_mode = ViewMode::detect(&read_value.body, read_value.decoded.as_ref(), read_value.property("content_type"), pretty);
+
+        MessageRecord {
+            view_mode,
+            rendered: payload::render(&read_value.body, read_value.decoded.as_ref(), view_mode),
+            name: name.to_string(),
+            exchange_name: read_value.exchange_name.clone(),
+            routing_key: read_value.routing_key.clone(),
+            body: read_value.body.clone(),
+            decoded: read_value.decoded.clone(),
+            item_id: read_value.id,
+            fields: projection.zip(json.as_ref()).map(|(projection, json)| projection.values(json)),
+            json: json.as_ref().map(JsonView::new),
+            properties: read_value.properties.clone(),
+            timestamp: read_value.timestamp
+        }
+    }
+
+    /// The item's fields or the body on one line, shortened for the list.
+    pub fn preview(&self) -> String {
+        let preview = match (&self.fields, self.view_mode) {
+            (Some(fields), _) => projection::summary(fields),
+            (None, ViewMode::Hex) => self.body.iter().take(64).map(|x| format!("{:02x}", x)).join(" "),
+            (None, _) => self.body_lines().join(" ").split_whitespace().join(" ")
+        };
+
+        preview.chars().take(200).collect()
+    }
+
+    /// The body the way `view_mode` shows it.
+    pub fn body_lines(&self) -> &[String] {
+        &self.rendered
+    }
+
+    fn set_view_mode(&mut self, view_mode: ViewMode) {
+        self.view_mode = view_mode;
+        self.rendered = payload::render(&self.body, self.decoded.as_ref(), view_mode);
+    }
+
+    /// Where the message came from and its properties, shown above the body.
+    fn header_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("exchange: {} ({})", self.exchange_name, self.name),
+            format!("routing_key: {}", self.routing_key),
+            format!("received: {}", self.timestamp.format("%Y/%m/%d %I:%M:%S%.6f %p")),
+            format!("size: {} bytes", self.body.len())
+        ];
+        lines.extend(self.properties.iter().map(|(name, value)| format!("{}: {}", name, value)));
+        lines.push("".to_string());
+
+        lines
+    }
+
+    /// The JSON view while it is shown.
+    fn shown_json(&self) -> Option<&JsonView> {
+        self.json.as_ref().filter(|_| self.view_mode == ViewMode::Json)
+    }
+
+    /// Everything the detail pane shows, with search matches marked and JSON coloured and folded.
+    fn detail_spans(&self, search: &MessageSearch) -> Vec<Spans<'static>> {
+        let mut lines: Vec<Spans> = self.header_lines().iter().map(|x| search.highlight(x, false)).collect();
+
+        match self.shown_json() {
+            Some(json) => lines.extend(json.visible().into_iter().map(|x| search.highlight_spans(json.spans(x), false))),
+            None => lines.extend(self.body_lines().iter().map(|x| search.highlight(x, false)))
+        }
+
+        lines
+    }
+
+    fn detail_len(&self) -> usize {
+        let body_len = match self.shown_json() {
+            Some(json) => json.visible().len(),
+            None => self.body_lines().len()
+        };
+
+        self.header_lines().len() + body_len
+    }
+
+    /// Folds or unfolds the JSON object or array at detail line `cursor`. Returns where the
+    /// cursor goes, the line that opens it.
+    fn toggle_fold(&mut self, cursor: usize) -> usize {
+        let header_len = self.header_lines().len();
+        let json = match self.json.as_mut().filter(|_| self.view_mode == ViewMode::Json) {
+            Some(res) if cursor >= header_len => res,
+            _ => return cursor
+        };
+
+        let line = match json.visible().get(cursor - header_len) {
+            Some(res) => *res,
+            None => return cursor
+        };
+        let opening = json.toggle(line);
+
+        header_len + json.visible().iter().position(|x| *x == opening).unwrap_or_default()
+    }
+
+    /// Whether the body is shown as coloured JSON.
+    pub fn is_json(&self) -> bool {
+        self.shown_json().is_some()
+    }
+
+    pub fn matches(&self, search: &MessageSearch) -> bool {
+        search.is_match(self.name.as_str())
+            || search.is_match(self.routing_key.as_str())
+            || self.body_lines().iter().any(|x| search.is_match(x))
+    }
+}
+
+/// The received messages as records, shown as a list with the selected one in full below it.
+pub struct MessageList {
+    records: VecDeque<MessageRecord>,
+    capacity: usize,
+    /// Index into `records`; the newest message while `None`.
+    selected: Option<usize>,
+    /// Indices into `records` of the messages marked to be compared, oldest first.
+    marked: Vec<usize>,
+    /// Up and down move a cursor through the detail pane instead of selecting messages.
+    pub detail_focus: bool,
+    detail_cursor: usize,
+    /// First line of the detail pane shown.
+    detail_scroll: usize
+}
+
+impl MessageList {
+    pub fn new(capacity: usize) -> Self {
+        MessageList {
+            records: VecDeque::new(),
+            capacity,
+            selected: None,
+            marked: vec![],
+            detail_focus: false,
+            detail_cursor: 0,
+            detail_scroll: 0
+        }
+    }
+
+    pub fn push(&mut self, record: MessageRecord) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+            self.selected = self.selected.map(|x| x.saturating_sub(1));
+            self.marked = self.marked.iter().filter(|x| **x > 0).map(|x| x - 1).collect();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Stops following new messages and selects the newest one.
+    pub fn hold(&mut self) {
+        self.selected = self.records.len().checked_sub(1);
+        self.reset_detail();
+    }
+
+    pub fn follow(&mut self) {
+        self.selected = None;
+        self.detail_focus = false;
+        self.reset_detail();
+    }
+
+    pub fn records(&self) -> &VecDeque<MessageRecord> {
+        &self.records
+    }
+
+    pub fn selected(&self) -> Option<&MessageRecord> {
+        match self.selected {
+            Some(res) => self.records.get(res),
+            None => self.records.back()
+        }
+    }
+
+    /// Marks or unmarks the selected message. Marking a third message unmarks the one marked
+    /// first. Returns whether it is marked now.
+    pub fn toggle_mark(&mut self) -> Option<bool> {
+        let index = self.selected_index()?;
+
+        if let Some(position) = self.marked.iter().position(|x| *x == index) {
+            self.marked.remove(position);
+            return Some(false);
+        }
+
+        if self.marked.len() == 2 {
+            self.marked.remove(0);
+        }
+        self.marked.push(index);
+
+        Some(true)
+    }
+
+    /// The two marked messages, or the marked one and the selected one, older first.
+    pub fn marked_pair(&self) -> Option<(&MessageRecord, &MessageRecord)> {
+        let mut pair = self.marked.clone();
+        if pair.len() == 1 {
+            pair.push(self.selected_index()?);
+        }
+        pair.sort_unstable();
+        pair.dedup();
+
+        match pair.as_slice() {
+            [older, newer] => Some((&self.records[*older], &self.records[*newer])),
+            _ => None
+        }
+    }
+
+    /// Shows the selected message as text, JSON, a hex dump or base64, in turn.
+    pub fn next_view_mode(&mut self) -> Option<ViewMode> {
+        let index = self.selected_index()?;
+        let record = &mut self.records[index];
+        record.set_view_mode(record.view_mode.next());
+        self.detail_cursor = 0;
+
+        Some(record.view_mode)
+    }
+
+    pub fn toggle_detail_focus(&mut self) {
+        self.detail_focus = !self.detail_focus;
+    }
+
+    pub fn cursor_up(&mut self) {
+        self.detail_cursor = self.detail_cursor.saturating_sub(1);
+    }
+
+    pub fn cursor_down(&mut self) {
+        let length = self.selected().map(|x| x.detail_len()).unwrap_or_default();
+        self.detail_cursor = (self.detail_cursor + 1).min(length.saturating_sub(1));
+    }
+
+    /// Folds or unfolds the JSON object or array under the cursor.
+    pub fn toggle_fold(&mut self) {
+        if let Some(index) = self.selected_index() {
+            self.detail_cursor = self.records[index].toggle_fold(self.detail_cursor);
+        }
+    }
+
+    /// Folds the selected message's JSON down to its top level, or unfolds all of it.
+    pub fn fold_all(&mut self, folded: bool) {
+        if let Some(json) = self.selected_index().and_then(|x| self.records[x].json.as_mut()) {
+            json.fold_all(folded);
+            self.reset_detail();
+        }
+    }
+
+    pub fn select_up(&mut self, search: &MessageSearch) {
+        self.move_selection(search, -1);
+    }
+
+    pub fn select_down(&mut self, search: &MessageSearch) {
+        self.move_selection(search, 1);
+    }
+
+    pub fn detail_scroll_up(&mut self, lines: usize) {
+        self.detail_scroll = self.detail_scroll.saturating_sub(lines);
+        self.detail_cursor = self.detail_cursor.saturating_sub(lines);
+    }
+
+    pub fn detail_scroll_down(&mut self, lines: usize) {
+        let length = self.selected().map(|x| x.detail_len()).unwrap_or_default();
+        self.detail_scroll = (self.detail_scroll + lines).min(length.saturating_sub(1));
+        self.detail_cursor = (self.detail_cursor + lines).min(length.saturating_sub(1));
+    }
+
+    /// Selects the next older or newer message with a search match, wrapping around at the ends.
+    /// False when nothing matches.
+    pub fn search_jump(&mut self, search: &MessageSearch, older: bool) -> bool {
+        let matches: Vec<usize> = (0..self.records.len()).filter(|x| self.records[*x].matches(search)).collect();
+        let current = self.selected.unwrap_or(self.records.len());
+
+        let next = if older {
+            matches.iter().rev().find(|x| **x < current).or(matches.last())
+        }
+        else {
+            matches.iter().find(|x| **x > current).or(matches.first())
+        };
+
+        match next {
+            Some(res) => {
+                self.selected = Some(*res);
+                self.reset_detail();
+                true
+            }
+            None => false
+        }
+    }
+
+    pub fn draw<B: Backend>(&mut self, frame: &mut Frame<B>, area: Rect, search: &MessageSearch) {
+        let grid = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(area);
+
+        let visible = self.visible(search);
+
+        let rows: Vec<Row> = visible
+            .iter()
+            .map(|i| {
+                let record = &self.records[*i];
+                let style = if self.marked.contains(i) { Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD) } else { Style::default() };
+                Row::new(vec![
+                    Cell::from(record.timestamp.format("%H:%M:%S%.3f").to_string()),
+                    Cell::from(record.name.clone()),
+                    Cell::from(record.routing_key.clone()),
+                    Cell::from(format_size(record.body.len())),
+                    Cell::from(search.highlight(record.preview().as_str(), false))
+                ]).style(style)
+            })
+            .collect();
+
+        let selected = match self.selected {
+            Some(res) => visible.iter().position(|x| *x == res),
+            None => visible.len().checked_sub(1)
+        };
+        let mut state = TableState::default();
+        state.select(selected);
+
+        let mut title = if search.filter_view && search.is_active() { format!("Messages matching {}", search.query) } else { "Messages".to_string() };
+        if !self.marked.is_empty() {
+            title = format!("{} ({} marked)", title, self.marked.len());
+        }
+        let header_style = Style::default().fg(theme::current().accent);
+        let widths = [Constraint::Length(12), Constraint::Length(20), Constraint::Length(20), Constraint::Length(9), Constraint::Percentage(100)];
+
+        let table = Table::new(rows)
+            .header(Row::new(vec!["Time", "Exchange", "Routing Key", "Size", "Preview"]).style(header_style))
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled(title, Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
+            .highlight_style(Style::default().fg(theme::current().input))
+            .highlight_symbol(">")
+            .widths(&widths)
+            .column_spacing(1);
+
+        frame.render_stateful_widget(table, grid[0], &mut state);
+
+        let mut detail: Vec<Spans> = match selected.map(|x| &self.records[visible[x]]) {
+            Some(record) => record.detail_spans(search),
+            None => vec![]
+        };
+
+        if self.detail_focus && !detail.is_empty() {
+            self.detail_cursor = self.detail_cursor.min(detail.len() - 1);
+            self.detail_scroll = scroll_to(&detail, self.detail_scroll, self.detail_cursor, grid[1]);
+            for span in detail[self.detail_cursor].0.iter_mut() {
+                span.style = span.style.bg(theme::current().pending);
+            }
+        }
+        let detail: Vec<Spans> = detail.into_iter().skip(self.detail_scroll).collect();
+
+        let detail_title = match selected {
+            Some(res) if self.detail_focus => format!("Message ({:?}, Space folds)", self.records[visible[res]].view_mode),
+            Some(res) => format!("Message ({:?})", self.records[visible[res]].view_mode),
+            None => "Message".to_string()
+        };
+
+        let paragraph = Paragraph::new(detail)
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled(detail_title, Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
+            .wrap(Wrap { trim: false });
+
+        frame.render_widget(paragraph, grid[1]);
+    }
+
+    /// Indices of the records shown: all of them, or the ones with a search match in the filter view.
+    fn visible(&self, search: &MessageSearch) -> Vec<usize> {
+        if search.filter_view && search.is_active() {
+            (0..self.records.len()).filter(|x| self.records[*x].matches(search)).collect()
+        }
+        else {
+            (0..self.records.len()).collect()
+        }
+    }
+
+    fn move_selection(&mut self, search: &MessageSearch, step: i64) {
+        let visible = self.visible(search);
+        if visible.is_empty() {
+            return;
+        }
+
+        let position = match self.selected.and_then(|x| visible.iter().position(|y| *y == x)) {
+            Some(res) => (res as i64 + step).clamp(0, visible.len() as i64 - 1) as usize,
+            None => visible.len() - 1
+        };
+        self.selected = Some(visible[position]);
+        self.reset_detail();
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        self.selected.or_else(|| self.records.len().checked_sub(1))
+    }
+
+    fn reset_detail(&mut self) {
+        self.detail_scroll = 0;
+        self.detail_cursor = 0;
+    }
+}
+
+/// The first line to show so `cursor` is in view in `area`, moving as little as possible from
+/// `scroll`. Wrapped lines take more than one row.
+fn scroll_to(lines: &[Spans], scroll: usize, cursor: usize, area: Rect) -> usize {
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    let height = area.height.saturating_sub(2).max(1) as usize;
+    let rows = |x: &Spans| x.width().max(1).div_ceil(width);
+
+    let mut scroll = scroll.min(cursor);
+    while scroll < cursor && lines[scroll..=cursor].iter().map(rows).sum::<usize>() > height {
+        scroll += 1;
+    }
+
+    scroll
+}
+
+/// Puts `text` on the clipboard through the terminal (OSC 52), which works over SSH too. Terminals
+/// that do not support it ignore it.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut stdout = stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_logger::LogFormat;
+
+    fn record(body: &str) -> MessageRecord {
+        let read_value = ReadValue {
+            id: Uuid::new_v4(),
+            exchange_name: "orders".to_string(),
+            routing_key: "order.new".to_string(),
+            body: body.as_bytes().to_vec(),
+            decoded: None,
+            properties: vec![],
+            projection: None,
+            log_format: LogFormat::Text,
+            timestamp: Local::now()
+        };
+
+        MessageRecord::new(&read_value, "Orders", true, None)
+    }
+
+    #[test]
+    fn body_lines_follow_the_view_mode() {
+        let mut list = MessageList::new(2);
+        list.push(record("{\"id\": 1}"));
+
+        assert_eq!(list.selected().unwrap().body_lines(), ["{", "  \"id\": 1", "}"]);
+        assert!(list.next_view_mode() == Some(ViewMode::Hex));
+        assert!(list.selected().unwrap().body_lines()[0].starts_with("00000000"));
+    }
+
+    #[test]
+    fn the_oldest_records_are_dropped_at_capacity() {
+        let mut list = MessageList::new(2);
+        for body in ["a", "b", "c"] {
+            list.push(record(body));
+        }
+
+        assert_eq!(list.records.iter().map(|x| x.body_lines()[0].as_str()).collect::<Vec<&str>>(), ["b", "c"]);
+    }
+}
@@ -0,0 +1,128 @@
+use regex::{Regex, RegexBuilder};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use crate::theme;
+
+/// Incremental search over the message buffer. The query is a regular expression, matched
+/// without regard to case unless it has an upper case letter. While it does not compile, e.g.
+/// half way through typing `(a|b)`, it is matched as plain text.
+pub struct MessageSearch {
+    pub query: String,
+    /// Only show the messages with a match.
+    pub filter_view: bool,
+
+    regex: Option<Regex>,
+    valid_regex: bool
+}
+
+impl MessageSearch {
+    pub fn new() -> Self {
+        MessageSearch {
+            query: "".to_string(),
+            filter_view: false,
+            regex: None,
+            valid_regex: true
+        }
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.compile();
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.compile();
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.filter_view = false;
+        self.compile();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.regex.is_some()
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match &self.regex {
+            Some(res) => res.is_match(line),
+            None => false
+        }
+    }
+
+    /// `line` with every match marked, more strongly on the line the last jump went to.
+    pub fn highlight(&self, line: &str, current: bool) -> Spans<'static> {
+        self.highlight_spans(vec![Span::raw(line.to_string())], current)
+    }
+
+    /// Like `highlight`, for a line that already has styles, e.g. coloured JSON. Matches keep
+    /// the styles they do not set.
+    pub fn highlight_spans(&self, spans: Vec<Span<'static>>, current: bool) -> Spans<'static> {
+        let regex = match &self.regex {
+            Some(res) => res,
+            None => return Spans::from(spans)
+        };
+
+        let match_style = if current {
+            Style::default().fg(theme::current().background).bg(theme::current().selected).add_modifier(Modifier::BOLD)
+        }
+        else {
+            Style::default().fg(theme::current().background).bg(theme::current().input)
+        };
+
+        let line: String = spans.iter().map(|x| x.content.as_ref()).collect();
+        let found: Vec<(usize, usize)> = regex.find_iter(line.as_str()).filter(|x| !x.as_str().is_empty()).map(|x| (x.start(), x.end())).collect();
+
+        //split the spans where matches start and end
+        let mut highlighted = vec![];
+        let mut offset = 0;
+        for span in spans {
+            let end = offset + span.content.len();
+
+            while offset < end {
+                let (next, style) = match found.iter().find(|x| x.0 <= offset && offset < x.1) {
+                    Some(res) => (res.1.min(end), span.style.patch(match_style)),
+                    None => (found.iter().map(|x| x.0).filter(|x| *x > offset).min().unwrap_or(end).min(end), span.style)
+                };
+                highlighted.push(Span::styled(line[offset..next].to_string(), style));
+                offset = next;
+            }
+        }
+
+        Spans::from(highlighted)
+    }
+
+    /// Shown as the title of the search input.
+    pub fn title(&self) -> &'static str {
+        if self.valid_regex {
+            "Search (regex)"
+        }
+        else {
+            "Search (not a valid regex, matching as text)"
+        }
+    }
+
+    fn compile(&mut self) {
+        if self.query.is_empty() {
+            self.regex = None;
+            self.valid_regex = true;
+            return;
+        }
+
+        let case_insensitive = !self.query.chars().any(|c| c.is_uppercase());
+        let build = |pattern: &str| RegexBuilder::new(pattern).case_insensitive(case_insensitive).build();
+
+        match build(self.query.as_str()) {
+            Ok(res) => {
+                self.regex = Some(res);
+                self.valid_regex = true;
+            }
+            Err(_) => {
+                self.regex = build(regex::escape(self.query.as_str()).as_str()).ok();
+                self.valid_regex = false;
+            }
+        }
+    }
+}
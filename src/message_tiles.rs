@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use bevy_reflect::Uuid;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Paragraph};
+use crate::message_list::MessageRecord;
+use crate::message_search::MessageSearch;
+use crate::projection;
+use crate::theme;
+use crate::ui::LineKind;
+
+/// A pane of its own for one subscribed item's messages. Tiles show what the Message List keeps,
+/// the last 1000 messages of all items together, so a busy item can push a quiet one's out.
+pub struct MessageTile {
+    pub item_id: Uuid,
+    pub name: String,
+    pub color: Option<Color>
+}
+
+/// Lays `tiles` out in a grid that fills `area`, each with its newest messages at the bottom.
+pub fn draw<B: Backend>(frame: &mut Frame<B>, area: Rect, tiles: &[MessageTile], records: &VecDeque<MessageRecord>, search: &MessageSearch) {
+    if tiles.is_empty() {
+        let paragraph = Paragraph::new("Subscribe to items to see each one's messages in a pane of its own.")
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled("Messages", Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))));
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let columns = (tiles.len() as f64).sqrt().ceil() as usize;
+    let rows = tiles.len().div_ceil(columns);
+
+    let row_areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Ratio(1, rows as u32); rows])
+        .split(area);
+
+    for (row, row_tiles) in tiles.chunks(columns).enumerate() {
+        //the last row can have fewer tiles, which share its width
+        let tile_areas = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Ratio(1, row_tiles.len() as u32); row_tiles.len()])
+            .split(row_areas[row]);
+
+        for (tile, tile_area) in row_tiles.iter().zip(tile_areas) {
+            draw_tile(frame, tile_area, tile, records, search);
+        }
+    }
+}
+
+fn draw_tile<B: Backend>(frame: &mut Frame<B>, area: Rect, tile: &MessageTile, records: &VecDeque<MessageRecord>, search: &MessageSearch) {
+    let height = area.height.saturating_sub(2) as usize;
+    let filtered = search.filter_view && search.is_active();
+
+    let count = records.iter().filter(|x| x.item_id == tile.item_id).count();
+
+    //only the newest messages that fill the tile are laid out
+    let mut lines: Vec<(String, LineKind)> = vec![];
+    for record in records.iter().rev().filter(|x| x.item_id == tile.item_id && (!filtered || x.matches(search))) {
+        if lines.len() >= height {
+            break;
+        }
+        let mut message_lines = record_lines(record, tile.color);
+        message_lines.append(&mut lines);
+        lines = message_lines;
+    }
+
+    let spans: Vec<Spans> = lines[lines.len().saturating_sub(height)..]
+        .iter()
+        .map(|(line, kind)| kind.spans(line.as_str(), search, false))
+        .collect();
+
+    let color = tile.color.unwrap_or(theme::current().accent);
+    let title = format!("{} ({} messages)", tile.name, count);
+
+    let paragraph = Paragraph::new(spans)
+        .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(tile.color.unwrap_or(theme::current().foreground)))
+            .title(Span::styled(title, Style::default().fg(color).add_modifier(Modifier::BOLD))));
+
+    frame.render_widget(paragraph, area);
+}
+
+/// The time and routing key, then the item's fields or the body, like the message stream.
+fn record_lines(record: &MessageRecord, color: Option<Color>) -> Vec<(String, LineKind)> {
+    let header = format!("{} | {}", record.timestamp.format("%H:%M:%S%.3f"), record.routing_key);
+    let mut lines = vec![(header, LineKind::Header(color))];
+
+    match &record.fields {
+        Some(res) => lines.push((projection::summary(res), LineKind::Text)),
+        None => {
+            let kind = if record.is_json() { LineKind::Json } else { LineKind::Text };
+            lines.extend(record.body_lines().iter().map(|x| (x.clone(), kind)));
+        }
+    }
+
+    lines
+}
@@ -0,0 +1,67 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The topology part of a RabbitMQ definitions file, as exported by the management UI or
+/// `GET /api/definitions/{vhost}`. Users, permissions and policies are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Definitions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rabbit_version: Option<String>,
+    #[serde(default)]
+    pub vhosts: Vec<VhostDefinition>,
+    #[serde(default)]
+    pub exchanges: Vec<ExchangeDefinition>,
+    #[serde(default)]
+    pub queues: Vec<QueueDefinition>,
+    #[serde(default)]
+    pub bindings: Vec<BindingDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VhostDefinition {
+    pub name: String,
+}
+
+/// `vhost` is missing from files exported for a single vhost.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExchangeDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vhost: Option<String>,
+    #[serde(rename = "type")]
+    pub exchange_type: String,
+    #[serde(default)]
+    pub durable: bool,
+    #[serde(default)]
+    pub auto_delete: bool,
+    #[serde(default)]
+    pub internal: bool,
+    #[serde(default)]
+    pub arguments: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vhost: Option<String>,
+    #[serde(default)]
+    pub durable: bool,
+    #[serde(default)]
+    pub auto_delete: bool,
+    #[serde(default)]
+    pub arguments: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BindingDefinition {
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vhost: Option<String>,
+    pub destination: String,
+    pub destination_type: String,
+    #[serde(default)]
+    pub routing_key: String,
+    #[serde(default)]
+    pub arguments: Map<String, Value>,
+}
@@ -1,7 +1,8 @@
 use serde_derive::{Deserialize, Serialize};
 use bevy_reflect::{Reflect};
+use amiquip::ExchangeType;
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy, Reflect)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Reflect, PartialEq)]
 pub enum ExchangeTypeSer {
     /// Direct exchange; delivers messages to queues based on the routing key.
     Direct,
@@ -35,6 +36,17 @@ impl From<&str> for ExchangeTypeSer {
     }
 }
 
+impl From<ExchangeTypeSer> for ExchangeType {
+    fn from(value: ExchangeTypeSer) -> Self {
+        match value {
+            ExchangeTypeSer::Direct => ExchangeType::Direct,
+            ExchangeTypeSer::Fanout => ExchangeType::Fanout,
+            ExchangeTypeSer::Topic => ExchangeType::Topic,
+            ExchangeTypeSer::Headers => ExchangeType::Headers,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect, PartialEq)]
 pub enum SelectedState {
     Unselected,
@@ -1,6 +1,9 @@
 use serde_derive::{Deserialize, Serialize};
 use bevy_reflect::{Reflect, Uuid};
+use serde_json::{Map, Value};
+use tui::style::Color;
 use crate::models::enums::{ExchangeTypeSer, SelectedState};
+use crate::theme;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ExchangeOptionsSer {
@@ -11,6 +14,14 @@ pub struct ExchangeOptionsSer {
     pub pretty: Option<bool>,
     pub log_file: Option<String>,
     pub publish_file: Option<String>,
+    pub filter: Option<String>,
+    pub decoder: Option<String>,
+    pub schema_file: Option<String>,
+    pub content_encoding: Option<String>,
+    pub publish_encoding: Option<String>,
+    pub fields: Option<Vec<String>>,
+    pub log_format: Option<String>,
+    pub color: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Reflect)]
@@ -24,7 +35,28 @@ pub struct ExchangeOptions {
     pub pretty: bool,
     pub log_file: String,
     pub publish_file: String,
-    pub selected_state: SelectedState
+    /// Only deliveries matching this are shown, see `MessageFilter` for the syntax.
+    pub filter: String,
+    /// Decodes binary bodies to JSON, see `Decoder::from_options`.
+    pub decoder: String,
+    /// The `.proto` or `.avsc` file the protobuf and avro decoders read.
+    pub schema_file: String,
+    /// Decompresses received bodies with gzip, zstd or deflate whatever their `content_encoding`
+    /// property says, or never with `identity`. Empty follows the property.
+    pub content_encoding: String,
+    /// Compresses the publish file with gzip, zstd or deflate and sets `content_encoding`.
+    pub publish_encoding: String,
+    /// JSON fields shown on one line instead of the whole body, separated by commas, see
+    /// `Projection`.
+    pub fields: String,
+    /// `text`, `csv` or `tsv`, see `LogFormat`.
+    pub log_format: String,
+    /// Colour of the item's name in the selector and above its messages, see `theme::parse_color`.
+    pub color: String,
+    pub selected_state: SelectedState,
+    #[reflect(ignore)]
+    #[serde(skip)]
+    pub document: Map<String, Value>
 }
 
 impl Default for ExchangeOptions {
@@ -38,7 +70,51 @@ impl Default for ExchangeOptions {
             pretty: false,
             log_file: "".to_string(),
             publish_file: "".to_string(),
-            selected_state: SelectedState::Unselected
+            filter: "".to_string(),
+            decoder: "".to_string(),
+            schema_file: "".to_string(),
+            content_encoding: "".to_string(),
+            publish_encoding: "".to_string(),
+            fields: "".to_string(),
+            log_format: "".to_string(),
+            color: "".to_string(),
+            selected_state: SelectedState::Unselected,
+            document: Map::new()
         }
     }
-}
\ No newline at end of file
+}
+
+impl ExchangeOptions {
+    /// The alias when one is set, otherwise the exchange name.
+    pub fn display_name(&self) -> &str {
+        if self.alias.is_empty() {
+            self.exchange_name.as_str()
+        }
+        else {
+            self.alias.as_str()
+        }
+    }
+
+    /// The item's colour, `None` when it has none, it cannot be parsed or colours are off.
+    pub fn display_color(&self) -> Option<Color> {
+        if theme::colorless() {
+            return None;
+        }
+
+        theme::parse_color(self.color.as_str()).ok()
+    }
+
+    /// Whether a subscription made with `other` would bind, filter and decode the same way as one
+    /// made with these options, so a running subscription can be kept.
+    pub fn subscribes_like(&self, other: &ExchangeOptions) -> bool {
+        self.exchange_name == other.exchange_name
+            && self.exchange_type == other.exchange_type
+            && self.queue_routing_key == other.queue_routing_key
+            && self.filter == other.filter
+            && self.decoder == other.decoder
+            && self.schema_file == other.schema_file
+            && self.content_encoding == other.content_encoding
+            && self.fields == other.fields
+            && self.log_format == other.log_format
+    }
+}
@@ -0,0 +1,96 @@
+use serde_derive::Deserialize;
+use serde_json::Value;
+use crate::models::enums::ExchangeTypeSer;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VhostInfo {
+    pub name: String,
+}
+
+/// An exchange as listed by the RabbitMQ management HTTP API.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExchangeInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub exchange_type: String,
+}
+
+impl ExchangeInfo {
+    /// `None` for exchange types the selector cannot subscribe to, e.g. plugin provided ones.
+    pub fn exchange_type_ser(&self) -> Option<ExchangeTypeSer> {
+        match self.exchange_type.as_str() {
+            "direct" => Some(ExchangeTypeSer::Direct),
+            "fanout" => Some(ExchangeTypeSer::Fanout),
+            "topic" => Some(ExchangeTypeSer::Topic),
+            "headers" => Some(ExchangeTypeSer::Headers),
+            _ => None
+        }
+    }
+
+    /// The default exchange and the broker's own `amq.*` exchanges.
+    pub fn is_builtin(&self) -> bool {
+        self.name.is_empty() || self.name.starts_with("amq.")
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct QueueInfo {
+    pub name: String,
+    #[serde(default)]
+    pub messages: Option<u64>,
+    #[serde(default)]
+    pub consumers: Option<u64>,
+    #[serde(default)]
+    pub message_stats: Option<MessageStats>,
+}
+
+impl QueueInfo {
+    pub fn publish_rate(&self) -> f64 {
+        self.message_stats
+            .as_ref()
+            .and_then(|x| x.publish_details.as_ref())
+            .map(|x| x.rate)
+            .unwrap_or_default()
+    }
+
+    pub fn deliver_rate(&self) -> f64 {
+        self.message_stats
+            .as_ref()
+            .and_then(|x| x.deliver_get_details.as_ref())
+            .map(|x| x.rate)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MessageStats {
+    #[serde(default)]
+    pub publish_details: Option<RateDetails>,
+    #[serde(default)]
+    pub deliver_get_details: Option<RateDetails>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RateDetails {
+    pub rate: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BindingInfo {
+    pub source: String,
+    pub destination: String,
+    pub destination_type: String,
+    pub routing_key: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+/// Everything the browser shows for one vhost, fetched together.
+#[derive(Debug, Clone)]
+pub struct ManagementSnapshot {
+    pub vhost: String,
+    pub vhosts: Vec<VhostInfo>,
+    pub exchanges: Vec<ExchangeInfo>,
+    pub queues: Vec<QueueInfo>,
+    pub bindings: Vec<BindingInfo>,
+}
@@ -1,3 +1,5 @@
 pub mod read_value;
 pub mod exchange_options;
-pub mod enums;
\ No newline at end of file
+pub mod enums;
+pub mod management;
+pub mod definitions;
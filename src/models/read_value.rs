@@ -1,9 +1,28 @@
+use std::sync::Arc;
 use bevy_reflect::Uuid;
 use chrono::{DateTime, Local};
+use serde_json::Value;
+use crate::file_logger::LogFormat;
+use crate::projection::Projection;
 
 pub struct ReadValue {
     pub id: Uuid,
     pub exchange_name: String,
-    pub value: String,
+    pub routing_key: String,
+    pub body: Vec<u8>,
+    /// The body as JSON when a decoder is set for the item or its content type, or why it could
+    /// not be decoded.
+    pub decoded: Option<Result<Value, String>>,
+    /// The message properties that were set, as name and value.
+    pub properties: Vec<(String, String)>,
+    /// The item's fields and log format as they were when it subscribed.
+    pub projection: Option<Arc<Projection>>,
+    pub log_format: LogFormat,
     pub timestamp: DateTime<Local>
 }
+
+impl ReadValue {
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.iter().find(|x| x.0 == name).map(|x| x.1.as_str())
+    }
+}
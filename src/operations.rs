@@ -0,0 +1,318 @@
+use amiquip::{AmqpValue, Channel, ExchangeDeclareOptions, FieldTable, QueueDeclareOptions, QueueDeleteOptions};
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+use crate::models::enums::ExchangeTypeSer;
+
+pub enum Destination {
+    Queue(String),
+    Exchange(String)
+}
+
+/// A change to the broker's exchanges, queues or bindings, run only after it has been confirmed.
+pub enum Operation {
+    DeclareExchange { name: String, exchange_type: ExchangeTypeSer, durable: bool, auto_delete: bool, internal: bool, arguments: Map<String, Value> },
+    DeleteExchange { name: String, if_unused: bool },
+    DeclareQueue { name: String, durable: bool, exclusive: bool, auto_delete: bool, arguments: Map<String, Value> },
+    PurgeQueue { name: String },
+    DeleteQueue { name: String, if_unused: bool, if_empty: bool },
+    Bind { destination: Destination, source: String, routing_key: String, arguments: Map<String, Value> },
+    Unbind { destination: Destination, source: String, routing_key: String, arguments: Map<String, Value> }
+}
+
+impl Operation {
+    /// Parses the same words on the command line and in the TUI, e.g. `queue purge orders`.
+    pub fn parse(args: &[&str]) -> Result<Self> {
+        let mut flags: Vec<&str> = vec![];
+        let mut arguments = Map::new();
+        let mut words: Vec<&str> = vec![];
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match *arg {
+                "--arg" => {
+                    let pair = iter.next().ok_or_else(|| anyhow!("--arg needs a KEY=VALUE"))?;
+                    let (key, value) = parse_argument(pair)?;
+                    arguments.insert(key, value);
+                }
+                flag if flag.starts_with("--") => flags.push(flag),
+                word => words.push(word)
+            }
+        }
+
+        let allow_flags = |allowed: &[&str]| -> Result<()> {
+            match flags.iter().find(|x| !allowed.contains(x)) {
+                Some(res) => Err(anyhow!("Unknown option {} for {}", res, words.iter().take(2).copied().collect::<Vec<&str>>().join(" "))),
+                None => Ok(())
+            }
+        };
+        let has_flag = |flag: &str| flags.contains(&flag);
+
+        let operation = match words.as_slice() {
+            ["exchange", "declare", name, exchange_type] => {
+                allow_flags(&["--durable", "--auto-delete", "--internal"])?;
+                Operation::DeclareExchange {
+                    name: name.to_string(),
+                    exchange_type: parse_exchange_type(exchange_type)?,
+                    durable: has_flag("--durable"),
+                    auto_delete: has_flag("--auto-delete"),
+                    internal: has_flag("--internal"),
+                    arguments
+                }
+            }
+            ["exchange", "delete", name] => {
+                allow_flags(&["--if-unused"])?;
+                Operation::DeleteExchange { name: name.to_string(), if_unused: has_flag("--if-unused") }
+            }
+            ["queue", "declare", name] => {
+                allow_flags(&["--durable", "--exclusive", "--auto-delete"])?;
+                Operation::DeclareQueue {
+                    name: name.to_string(),
+                    durable: has_flag("--durable"),
+                    exclusive: has_flag("--exclusive"),
+                    auto_delete: has_flag("--auto-delete"),
+                    arguments
+                }
+            }
+            ["queue", "purge", name] => {
+                allow_flags(&[])?;
+                Operation::PurgeQueue { name: name.to_string() }
+            }
+            ["queue", "delete", name] => {
+                allow_flags(&["--if-unused", "--if-empty"])?;
+                Operation::DeleteQueue { name: name.to_string(), if_unused: has_flag("--if-unused"), if_empty: has_flag("--if-empty") }
+            }
+            [kind @ ("queue" | "exchange"), action @ ("bind" | "unbind"), destination, source, rest @ ..] if rest.len() <= 1 => {
+                allow_flags(&[])?;
+                let destination = if *kind == "queue" { Destination::Queue(destination.to_string()) } else { Destination::Exchange(destination.to_string()) };
+                let source = source.to_string();
+                let routing_key = rest.first().map(|x| x.to_string()).unwrap_or_default();
+
+                if *action == "bind" {
+                    Operation::Bind { destination, source, routing_key, arguments }
+                }
+                else {
+                    Operation::Unbind { destination, source, routing_key, arguments }
+                }
+            }
+            _ => return Err(anyhow!("Unknown operation: {}\n\n{}", args.join(" "), usage()))
+        };
+
+        Ok(operation)
+    }
+
+    /// What is about to happen, for the confirmation prompt.
+    pub fn describe(&self) -> String {
+        match self {
+            Operation::DeclareExchange { name, exchange_type, .. } => format!("Declare {:?} exchange {}", exchange_type, name),
+            Operation::DeleteExchange { name, if_unused } => format!("Delete exchange {}{}", name, if *if_unused { " if unused" } else { "" }),
+            Operation::DeclareQueue { name, .. } => format!("Declare queue {}", name),
+            Operation::PurgeQueue { name } => format!("Purge every message from queue {}", name),
+            Operation::DeleteQueue { name, if_unused, if_empty } => {
+                let conditions: Vec<&str> = [(*if_unused, "unused"), (*if_empty, "empty")].iter().filter(|x| x.0).map(|x| x.1).collect();
+                if conditions.is_empty() {
+                    format!("Delete queue {}", name)
+                }
+                else {
+                    format!("Delete queue {} if {}", name, conditions.join(" and "))
+                }
+            }
+            Operation::Bind { destination, source, routing_key, .. } => format!("Bind {} to exchange {} with routing key \"{}\"", destination.describe(), source, routing_key),
+            Operation::Unbind { destination, source, routing_key, .. } => format!("Unbind {} from exchange {} with routing key \"{}\"", destination.describe(), source, routing_key)
+        }
+    }
+
+    /// Runs the operation on `channel`; the broker closes the channel if it refuses.
+    pub fn execute(&self, channel: &Channel) -> Result<String> {
+        match self {
+            Operation::DeclareExchange { name, exchange_type, durable, auto_delete, internal, arguments } => {
+                let options = ExchangeDeclareOptions {
+                    durable: *durable,
+                    auto_delete: *auto_delete,
+                    internal: *internal,
+                    arguments: field_table(arguments)
+                };
+                channel.exchange_declare((*exchange_type).into(), name.as_str(), options)?;
+                Ok(format!("Exchange declared: {}", name))
+            }
+            Operation::DeleteExchange { name, if_unused } => {
+                channel.exchange_delete(name.as_str(), *if_unused)?;
+                Ok(format!("Exchange deleted: {}", name))
+            }
+            Operation::DeclareQueue { name, durable, exclusive, auto_delete, arguments } => {
+                let options = QueueDeclareOptions {
+                    durable: *durable,
+                    exclusive: *exclusive,
+                    auto_delete: *auto_delete,
+                    arguments: field_table(arguments)
+                };
+                let queue = channel.queue_declare(name.as_str(), options)?;
+                Ok(format!("Queue declared: {} ({} messages)", queue.name(), queue.declared_message_count().unwrap_or(0)))
+            }
+            Operation::PurgeQueue { name } => {
+                let count = channel.queue_purge(name.as_str())?;
+                Ok(format!("Queue purged: {} ({} messages)", name, count))
+            }
+            Operation::DeleteQueue { name, if_unused, if_empty } => {
+                let count = channel.queue_delete(name.as_str(), QueueDeleteOptions { if_unused: *if_unused, if_empty: *if_empty })?;
+                Ok(format!("Queue Deleted: {} ({} messages)", name, count))
+            }
+            Operation::Bind { destination, source, routing_key, arguments } => {
+                match destination {
+                    Destination::Queue(queue) => channel.queue_bind(queue.as_str(), source.as_str(), routing_key.as_str(), field_table(arguments))?,
+                    Destination::Exchange(exchange) => channel.exchange_bind(exchange.as_str(), source.as_str(), routing_key.as_str(), field_table(arguments))?
+                }
+                Ok(format!("Bound {} to {}", destination.describe(), source))
+            }
+            Operation::Unbind { destination, source, routing_key, arguments } => {
+                match destination {
+                    Destination::Queue(queue) => channel.queue_unbind(queue.as_str(), source.as_str(), routing_key.as_str(), field_table(arguments))?,
+                    Destination::Exchange(exchange) => channel.exchange_unbind(exchange.as_str(), source.as_str(), routing_key.as_str(), field_table(arguments))?
+                }
+                Ok(format!("Unbound {} from {}", destination.describe(), source))
+            }
+        }
+    }
+}
+
+impl Destination {
+    fn describe(&self) -> String {
+        match self {
+            Destination::Queue(name) => format!("queue {}", name),
+            Destination::Exchange(name) => format!("exchange {}", name)
+        }
+    }
+}
+
+pub fn usage() -> String {
+    [
+        "exchange declare NAME TYPE [--durable] [--auto-delete] [--internal] [--arg KEY=VALUE]...",
+        "exchange delete NAME [--if-unused]",
+        "exchange bind|unbind DESTINATION SOURCE [ROUTING_KEY] [--arg KEY=VALUE]...",
+        "queue declare NAME [--durable] [--exclusive] [--auto-delete] [--arg KEY=VALUE]...",
+        "queue purge NAME",
+        "queue delete NAME [--if-unused] [--if-empty]",
+        "queue bind|unbind QUEUE EXCHANGE [ROUTING_KEY] [--arg KEY=VALUE]...",
+    ].join("\n")
+}
+
+/// Converts JSON arguments, such as `x-message-ttl` or binding headers, into AMQP field values.
+pub fn field_table(arguments: &Map<String, Value>) -> FieldTable {
+    arguments
+        .iter()
+        .map(|(key, value)| (key.clone(), amqp_value(value)))
+        .collect()
+}
+
+fn amqp_value(value: &Value) -> AmqpValue {
+    match value {
+        Value::Null => AmqpValue::Void,
+        Value::Bool(res) => AmqpValue::Boolean(*res),
+        Value::Number(res) => match res.as_i64() {
+            Some(number) => AmqpValue::LongLongInt(number),
+            None => AmqpValue::Double(res.as_f64().unwrap_or_default())
+        },
+        Value::String(res) => AmqpValue::LongString(res.clone()),
+        Value::Array(res) => AmqpValue::FieldArray(res.iter().map(amqp_value).collect()),
+        Value::Object(res) => AmqpValue::FieldTable(field_table(res))
+    }
+}
+
+/// The JSON form of an argument or header value, for showing it.
+pub fn json_value(value: &AmqpValue) -> Value {
+    match value {
+        AmqpValue::Boolean(res) => Value::Bool(*res),
+        AmqpValue::ShortShortInt(res) => Value::from(*res),
+        AmqpValue::ShortShortUInt(res) => Value::from(*res),
+        AmqpValue::ShortInt(res) => Value::from(*res),
+        AmqpValue::ShortUInt(res) => Value::from(*res),
+        AmqpValue::LongInt(res) => Value::from(*res),
+        AmqpValue::LongUInt(res) => Value::from(*res),
+        AmqpValue::LongLongInt(res) => Value::from(*res),
+        AmqpValue::Float(res) => Value::from(*res),
+        AmqpValue::Double(res) => Value::from(*res),
+        AmqpValue::DecimalValue(res) => Value::from(res.value as f64 / 10f64.powi(res.scale as i32)),
+        AmqpValue::LongString(res) => Value::String(res.clone()),
+        AmqpValue::FieldArray(res) => Value::Array(res.iter().map(json_value).collect()),
+        AmqpValue::Timestamp(res) => Value::from(*res),
+        AmqpValue::FieldTable(res) => Value::Object(res.iter().map(|(k, v)| (k.clone(), json_value(v))).collect()),
+        AmqpValue::ByteArray(res) => Value::String(String::from_utf8_lossy(res).to_string()),
+        AmqpValue::Void => Value::Null
+    }
+}
+
+/// `KEY=VALUE`, where a value that reads as JSON (numbers, booleans) keeps its type and anything
+/// else is a string.
+fn parse_argument(pair: &str) -> Result<(String, Value)> {
+    let (key, value) = pair.split_once('=').ok_or_else(|| anyhow!("Expected KEY=VALUE, found {}", pair))?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+
+    Ok((key.to_string(), value))
+}
+
+fn parse_exchange_type(name: &str) -> Result<ExchangeTypeSer> {
+    ExchangeTypeSer::iterator()
+        .find(|x| format!("{:?}", x).eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!("Unknown exchange type {}, expected direct, fanout, topic or headers", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Operation> {
+        Operation::parse(&line.split_whitespace().collect::<Vec<&str>>())
+    }
+
+    #[test]
+    fn exchange_operations() {
+        let operation = parse("exchange declare orders TOPIC --durable --arg alternate-exchange=unrouted").unwrap();
+        assert_eq!(operation.describe(), "Declare Topic exchange orders");
+        match operation {
+            Operation::DeclareExchange { durable, auto_delete, internal, arguments, .. } => {
+                assert!(durable && !auto_delete && !internal);
+                assert_eq!(arguments["alternate-exchange"], "unrouted");
+            }
+            _ => panic!("expected an exchange declare")
+        }
+
+        assert_eq!(parse("exchange delete orders --if-unused").unwrap().describe(), "Delete exchange orders if unused");
+        assert!(parse("exchange declare orders nope").is_err());
+    }
+
+    #[test]
+    fn queue_operations() {
+        let operation = parse("queue declare jobs --arg x-message-ttl=60000 --arg x-queue-mode=lazy").unwrap();
+        match operation {
+            Operation::DeclareQueue { durable, arguments, .. } => {
+                assert!(!durable);
+                assert_eq!(arguments["x-message-ttl"], 60000);
+                assert_eq!(arguments["x-queue-mode"], "lazy");
+            }
+            _ => panic!("expected a queue declare")
+        }
+
+        assert_eq!(parse("queue purge jobs").unwrap().describe(), "Purge every message from queue jobs");
+        assert_eq!(parse("queue delete jobs").unwrap().describe(), "Delete queue jobs");
+        assert_eq!(parse("queue delete jobs --if-empty --if-unused").unwrap().describe(), "Delete queue jobs if unused and empty");
+    }
+
+    #[test]
+    fn bindings() {
+        assert_eq!(parse("queue bind jobs orders order.*").unwrap().describe(), "Bind queue jobs to exchange orders with routing key \"order.*\"");
+        assert_eq!(parse("exchange unbind audit orders").unwrap().describe(), "Unbind exchange audit from exchange orders with routing key \"\"");
+        assert!(parse("queue bind jobs orders a b").is_err());
+    }
+
+    #[test]
+    fn invalid_operations() {
+        assert!(parse("queue purge").is_err());
+        assert!(parse("queue rename jobs other").is_err());
+
+        let error = parse("queue purge jobs --durable").err().unwrap().to_string();
+        assert_eq!(error, "Unknown option --durable for queue purge");
+
+        let error = parse("queue declare jobs --arg").err().unwrap().to_string();
+        assert_eq!(error, "--arg needs a KEY=VALUE");
+        assert!(parse("queue declare jobs --arg ttl").is_err());
+    }
+}
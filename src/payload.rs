@@ -0,0 +1,247 @@
+use std::fmt::Write;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+
+/// How a message body is shown.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ViewMode {
+    Text,
+    Json,
+    Hex,
+    Base64
+}
+
+impl ViewMode {
+    pub fn next(self) -> Self {
+        match self {
+            ViewMode::Text => ViewMode::Json,
+            ViewMode::Json => ViewMode::Hex,
+            ViewMode::Hex => ViewMode::Base64,
+            ViewMode::Base64 => ViewMode::Text
+        }
+    }
+
+    /// Pretty JSON for bodies a decoder ran on and JSON bodies on `pretty` items, a hex dump for binary
+    /// bodies and the text otherwise. The content type wins over looking at the body when it is set.
+    pub fn detect(body: &[u8], decoded: Option<&Result<Value, String>>, content_type: Option<&str>, pretty: bool) -> Self {
+        //the JSON mode also shows why a body could not be decoded
+        if decoded.is_some() {
+            return ViewMode::Json;
+        }
+
+        let content_type = content_type.unwrap_or_default().to_lowercase();
+
+        let binary = if content_type.is_empty() {
+            !is_text(body)
+        }
+        else {
+            !(content_type.starts_with("text/") || content_type.contains("json") || content_type.contains("xml")) || !is_text(body)
+        };
+
+        if binary {
+            ViewMode::Hex
+        }
+        else if pretty && serde_json::from_slice::<Value>(body).is_ok() {
+            ViewMode::Json
+        }
+        else {
+            ViewMode::Text
+        }
+    }
+}
+
+/// `body` as lines in `mode`. The JSON mode shows what a decoder made of the body, if one ran. A
+/// body that is not JSON is shown as text in the JSON mode and one that could not be decoded as a
+/// hex dump, with a line saying why.
+pub fn render(body: &[u8], decoded: Option<&Result<Value, String>>, mode: ViewMode) -> Vec<String> {
+    match mode {
+        ViewMode::Text => text(body).lines().map(|x| x.to_string()).collect(),
+        ViewMode::Json => match decoded {
+            Some(Ok(res)) => serde_json::to_string_pretty(res).unwrap_or_default().lines().map(|x| x.to_string()).collect(),
+            Some(Err(e)) => {
+                let mut lines = vec![format!("(cannot decode: {})", e)];
+                lines.extend(hex_dump(body));
+                lines
+            }
+            None => json(body)
+        },
+        ViewMode::Hex => hex_dump(body),
+        ViewMode::Base64 => STANDARD
+            .encode(body)
+            .as_bytes()
+            .chunks(76)
+            .map(|x| String::from_utf8_lossy(x).to_string())
+            .collect()
+    }
+}
+
+fn json(body: &[u8]) -> Vec<String> {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(res) => serde_json::to_string_pretty(&res).unwrap_or_default().lines().map(|x| x.to_string()).collect(),
+        Err(e) => {
+            let mut lines = vec![format!("(not JSON: {})", e)];
+            lines.extend(text(body).lines().map(|x| x.to_string()));
+            lines
+        }
+    }
+}
+
+/// The body as JSON, what a decoder made of it when one ran.
+pub fn json_value(body: &[u8], decoded: Option<&Result<Value, String>>) -> Option<Value> {
+    match decoded {
+        Some(res) => res.as_ref().ok().cloned(),
+        None => serde_json::from_slice(body).ok()
+    }
+}
+
+/// The body as text, with bytes that are not UTF-8 and control characters other than line
+/// breaks and tabs escaped so they cannot upset the terminal.
+pub fn text(body: &[u8]) -> String {
+    let mut text = String::with_capacity(body.len());
+
+    for chunk in body.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_control() && c != '\n' && c != '\t' {
+                if c != '\r' {
+                    text.push_str(c.escape_default().to_string().as_str());
+                }
+            }
+            else {
+                text.push(c);
+            }
+        }
+
+        for byte in chunk.invalid() {
+            write!(text, "\\x{:02x}", byte).ok();
+        }
+    }
+
+    text
+}
+
+/// UTF-8 without control characters other than whitespace.
+fn is_text(body: &[u8]) -> bool {
+    match std::str::from_utf8(body) {
+        Ok(res) => !res.chars().any(|c| c.is_control() && !c.is_whitespace()),
+        Err(_) => false
+    }
+}
+
+/// Offset, 16 bytes in hex and the printable ones as characters, like `hexdump -C`.
+fn hex_dump(body: &[u8]) -> Vec<String> {
+    body.chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut hex = String::new();
+            for (j, byte) in chunk.iter().enumerate() {
+                if j == 8 {
+                    hex.push(' ');
+                }
+                write!(hex, "{:02x} ", byte).ok();
+            }
+
+            let printable: String = chunk.iter().map(|x| if x.is_ascii_graphic() || *x == b' ' { *x as char } else { '.' }).collect();
+
+            format!("{:08x}  {:<49} |{}|", i * 16, hex, printable)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_bodies_are_pretty_only_on_pretty_items() {
+        let body = br#"{"id":1}"#;
+
+        assert_eq!(ViewMode::detect(body, None, None, true), ViewMode::Json);
+        assert_eq!(ViewMode::detect(body, None, None, false), ViewMode::Text);
+        assert_eq!(render(body, None, ViewMode::Json), vec!["{", "  \"id\": 1", "}"]);
+    }
+
+    #[test]
+    fn other_text_on_a_pretty_item_stays_text() {
+        let body = b"order 1 shipped";
+
+        assert_eq!(ViewMode::detect(body, None, None, true), ViewMode::Text);
+
+        //the JSON mode says why and shows the text anyway
+        let lines = render(body, None, ViewMode::Json);
+        assert!(lines[0].starts_with("(not JSON: expected value"), "{}", lines[0]);
+        assert_eq!(lines[1], "order 1 shipped");
+    }
+
+    #[test]
+    fn decoded_bodies_are_json() {
+        let decoded = Ok(json!({"id": 1}));
+        assert_eq!(ViewMode::detect(&[0x81, 0xa2], Some(&decoded), None, false), ViewMode::Json);
+        assert_eq!(render(&[0x81, 0xa2], Some(&decoded), ViewMode::Json), vec!["{", "  \"id\": 1", "}"]);
+
+        let failed = Err("unexpected end of input".to_string());
+        assert_eq!(render(&[0x81, 0xa2], Some(&failed), ViewMode::Json), vec![
+            "(cannot decode: unexpected end of input)",
+            "00000000  81 a2                                             |..|"
+        ]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_binary_and_escaped() {
+        let body = b"caf\xc3\xa9 \xff\xfe";
+
+        assert_eq!(ViewMode::detect(body, None, None, false), ViewMode::Hex);
+        assert_eq!(text(body), "café \\xff\\xfe");
+    }
+
+    #[test]
+    fn control_characters_are_escaped() {
+        let body = "\u{1b}[31mred\u{7}\r\n\tnext".as_bytes();
+
+        assert_eq!(ViewMode::detect(body, None, None, false), ViewMode::Hex);
+        assert_eq!(text(body), "\\u{1b}[31mred\\u{7}\n\tnext");
+        assert_eq!(render(body, None, ViewMode::Text), vec!["\\u{1b}[31mred\\u{7}", "\tnext"]);
+
+        //line breaks and tabs alone are still text
+        assert_eq!(ViewMode::detect(b"a\r\n\tb", None, None, false), ViewMode::Text);
+    }
+
+    #[test]
+    fn hex_dump_has_offsets_two_halves_and_printable_bytes() {
+        let body: Vec<u8> = (0x3e..0x50).chain([0x00, b' ']).collect();
+
+        assert_eq!(render(&body, None, ViewMode::Hex), vec![
+            "00000000  3e 3f 40 41 42 43 44 45  46 47 48 49 4a 4b 4c 4d  |>?@ABCDEFGHIJKLM|",
+            "00000010  4e 4f 00 20                                       |NO. |"
+        ]);
+        assert!(render(b"", None, ViewMode::Hex).is_empty());
+    }
+
+    #[test]
+    fn base64_wraps_at_76_characters() {
+        let lines = render(&[0u8; 60], None, ViewMode::Base64);
+
+        assert_eq!(lines.iter().map(|x| x.len()).collect::<Vec<usize>>(), vec![76, 4]);
+        assert_eq!(lines[1], "AAAA");
+    }
+
+    #[test]
+    fn the_content_type_wins_over_the_body() {
+        let body = br#"{"id":1}"#;
+
+        assert_eq!(ViewMode::detect(body, None, Some("application/octet-stream"), true), ViewMode::Hex);
+        assert_eq!(ViewMode::detect(body, None, Some("Application/JSON; charset=utf-8"), true), ViewMode::Json);
+        assert_eq!(ViewMode::detect(body, None, Some("text/plain"), false), ViewMode::Text);
+        assert_eq!(ViewMode::detect(b"<a/>", None, Some("application/xml"), true), ViewMode::Text);
+
+        //a text content type does not make binary bodies text
+        assert_eq!(ViewMode::detect(b"\xff\x00", None, Some("text/plain"), false), ViewMode::Hex);
+    }
+
+    #[test]
+    fn view_modes_cycle() {
+        assert_eq!(ViewMode::Text.next().next().next().next(), ViewMode::Text);
+        assert_eq!(ViewMode::Json.next(), ViewMode::Hex);
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::{Result, anyhow};
+use itertools::Itertools;
+use serde_json::Value;
+use crate::message_filter::{parse_path, select, PathSegment};
+
+/// The fields of JSON bodies an item shows instead of the whole body. Each is written as
+/// - `customer.id` or `items[0].sku`, a path from the top of the body
+/// - `$.customer.id`, a JSONPath, where `*` picks every key or item
+/// - `/customer/id`, a JSON pointer
+pub struct Projection {
+    fields: Vec<(String, FieldPath)>
+}
+
+enum FieldPath {
+    JsonPath(Vec<PathSegment>),
+    Pointer(String)
+}
+
+impl Projection {
+    /// `None` when no fields are given, which shows the whole body.
+    pub fn parse(fields: &[String]) -> Result<Option<Self>> {
+        let mut parsed = vec![];
+
+        for field in fields.iter().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            let path = if field.starts_with('/') {
+                FieldPath::Pointer(field.to_string())
+            }
+            else if field.starts_with('$') {
+                FieldPath::JsonPath(parse_path(field)?)
+            }
+            else if field.starts_with('[') {
+                FieldPath::JsonPath(parse_path(format!("${}", field).as_str())?)
+            }
+            else {
+                FieldPath::JsonPath(parse_path(format!("$.{}", field).as_str()).map_err(|_| anyhow!("Invalid field {}, expected e.g. customer.id, $.items[*].sku or /customer/id", field))?)
+            };
+
+            parsed.push((field.to_string(), path));
+        }
+
+        if parsed.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Projection { fields: parsed }))
+    }
+
+    /// The item's `fields` option, a list written on one line with commas between the fields.
+    pub fn parse_option(fields: &str) -> Result<Option<Self>> {
+        Self::parse(&split_fields(fields))
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.fields.iter().map(|x| x.0.clone()).collect()
+    }
+
+    /// Each field with its value, `None` when the body does not have it. Strings are shown
+    /// without quotes, other values as JSON, and the values of a path that picks more than one
+    /// are separated by commas.
+    pub fn values(&self, json: &Value) -> Vec<(String, Option<String>)> {
+        self.fields
+            .iter()
+            .map(|(name, path)| {
+                let selected: Vec<&Value> = match path {
+                    FieldPath::JsonPath(res) => {
+                        let mut selected = vec![];
+                        select(json, res, &mut selected);
+                        selected
+                    }
+                    FieldPath::Pointer(res) => json.pointer(res).into_iter().collect()
+                };
+
+                let value = if selected.is_empty() { None } else { Some(selected.iter().map(|x| field_text(x)).join(",")) };
+
+                (name.clone(), value)
+            })
+            .collect()
+    }
+}
+
+/// Fields on one line, e.g. `orderId: 7 | status: NEW | customer.id: -`.
+pub fn summary(values: &[(String, Option<String>)]) -> String {
+    values
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value.as_deref().unwrap_or("-")))
+        .join(" | ")
+}
+
+/// Splits `a, $['b,c'], d` at the commas outside of brackets and quotes.
+pub fn split_fields(fields: &str) -> Vec<String> {
+    let mut split = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+
+    for c in fields.chars() {
+        match (quote, c) {
+            (Some(res), _) if c == res => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                split.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    split.push(current.trim().to_string());
+
+    split.into_iter().filter(|x| !x.is_empty()).collect()
+}
+
+fn field_text(value: &Value) -> String {
+    match value {
+        Value::String(res) => res.clone(),
+        other => other.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn split_fields_keeps_brackets_and_quotes_whole() {
+        assert_eq!(split_fields(" a, $['b,c'] ,items[0].sku,, \"d,e\" "), vec!["a", "$['b,c']", "items[0].sku", "\"d,e\""]);
+        assert_eq!(split_fields("$['x]']['y,z'], w"), vec!["$['x]']['y,z']", "w"]);
+        assert!(split_fields(" , ").is_empty());
+    }
+
+    #[test]
+    fn values_of_every_kind_of_path() {
+        let projection = Projection::parse_option("orderId, $.items[*].sku, /customer/id, [\"note\"], missing").unwrap().unwrap();
+        let body = json!({"orderId": 7, "items": [{"sku": "a"}, {"sku": "b"}], "customer": {"id": "c-1"}, "note": null});
+
+        assert_eq!(projection.names(), vec!["orderId", "$.items[*].sku", "/customer/id", "[\"note\"]", "missing"]);
+        assert_eq!(summary(&projection.values(&body)), "orderId: 7 | $.items[*].sku: a,b | /customer/id: c-1 | [\"note\"]: null | missing: -");
+    }
+
+    #[test]
+    fn no_fields_and_invalid_fields() {
+        assert!(Projection::parse_option(" ").unwrap().is_none());
+        assert!(Projection::parse_option("a..b").err().unwrap().to_string().starts_with("Invalid field a..b"));
+        assert!(Projection::parse_option("$.items[x]").is_err());
+    }
+}
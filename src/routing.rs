@@ -0,0 +1,85 @@
+use crate::models::enums::ExchangeTypeSer;
+
+/// Whether a binding would route a message, as far as can be told from the routing key alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteMatch {
+    Yes,
+    No,
+    /// Headers exchanges route on message headers, which a routing key does not say anything about.
+    Unknown
+}
+
+/// AMQP topic matching: words are separated by `.`, `*` matches exactly one word and `#` matches
+/// zero or more words.
+pub fn topic_matches(pattern: &str, routing_key: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let routing_key: Vec<&str> = routing_key.split('.').collect();
+
+    words_match(&pattern, &routing_key)
+}
+
+fn words_match(pattern: &[&str], routing_key: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => routing_key.is_empty(),
+        Some((&"#", rest)) => (0..=routing_key.len()).any(|skip| words_match(rest, &routing_key[skip..])),
+        Some((word, rest)) => match routing_key.split_first() {
+            None => false,
+            Some((key_word, key_rest)) => (*word == "*" || word == key_word) && words_match(rest, key_rest)
+        }
+    }
+}
+
+pub fn binding_matches(exchange_type: ExchangeTypeSer, binding_key: &str, routing_key: &str) -> RouteMatch {
+    let matches = match exchange_type {
+        ExchangeTypeSer::Direct => binding_key == routing_key,
+        ExchangeTypeSer::Fanout => true,
+        ExchangeTypeSer::Topic => topic_matches(binding_key, routing_key),
+        ExchangeTypeSer::Headers => return RouteMatch::Unknown
+    };
+
+    if matches { RouteMatch::Yes } else { RouteMatch::No }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_exactly_one_word() {
+        assert!(topic_matches("order.*", "order.created"));
+        assert!(!topic_matches("order.*", "order"));
+        assert!(!topic_matches("order.*", "order.created.eu"));
+        assert!(topic_matches("*.created", "order.created"));
+    }
+
+    #[test]
+    fn hash_matches_zero_or_more_words() {
+        assert!(topic_matches("#", "order.created.eu"));
+        assert!(topic_matches("#", ""));
+        assert!(topic_matches("order.#", "order"));
+        assert!(topic_matches("order.#", "order.created.eu"));
+        assert!(topic_matches("#.eu", "order.created.eu"));
+        assert!(topic_matches("order.#.eu", "order.eu"));
+        assert!(topic_matches("order.#.eu", "order.a.b.eu"));
+        assert!(!topic_matches("order.#.eu", "order.a.b.us"));
+        assert!(topic_matches("#.*", "order"));
+    }
+
+    #[test]
+    fn words_match_exactly() {
+        assert!(topic_matches("order.created", "order.created"));
+        assert!(!topic_matches("order.created", "order.create"));
+        assert!(!topic_matches("order", "orders"));
+        assert!(!topic_matches("order.", "order"));
+        assert!(topic_matches("order.", "order."));
+    }
+
+    #[test]
+    fn bindings_by_exchange_type() {
+        assert_eq!(binding_matches(ExchangeTypeSer::Direct, "a.b", "a.b"), RouteMatch::Yes);
+        assert_eq!(binding_matches(ExchangeTypeSer::Direct, "a.*", "a.b"), RouteMatch::No);
+        assert_eq!(binding_matches(ExchangeTypeSer::Topic, "a.*", "a.b"), RouteMatch::Yes);
+        assert_eq!(binding_matches(ExchangeTypeSer::Fanout, "x", "a.b"), RouteMatch::Yes);
+        assert_eq!(binding_matches(ExchangeTypeSer::Headers, "", "a.b"), RouteMatch::Unknown);
+    }
+}
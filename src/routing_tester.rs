@@ -0,0 +1,175 @@
+use bevy_reflect::Uuid;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use crate::theme;
+use crate::models::enums::{ExchangeTypeSer, SelectedState};
+use crate::models::exchange_options::ExchangeOptions;
+use crate::routing::{binding_matches, topic_matches, RouteMatch};
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum TesterField {
+    Pattern,
+    RoutingKey
+}
+
+/// Checks routing keys against a topic binding pattern without going to the broker, and works
+/// out which subscribed items would get a message published with the key being typed.
+pub struct RoutingTester {
+    pub field: TesterField,
+    pub pattern: String,
+    pub routing_key: String,
+    pub routing_keys: Vec<String>,
+
+    exchange_name: String,
+    exchange_type: ExchangeTypeSer
+}
+
+impl RoutingTester {
+    pub fn new() -> Self {
+        RoutingTester {
+            field: TesterField::Pattern,
+            pattern: "".to_string(),
+            routing_key: "".to_string(),
+            routing_keys: vec![],
+            exchange_name: "".to_string(),
+            exchange_type: ExchangeTypeSer::Topic
+        }
+    }
+
+    /// Starts from the selected item's binding pattern, keeping the routing keys already entered.
+    pub fn open(&mut self, exchange_options: &ExchangeOptions) {
+        self.exchange_name = exchange_options.exchange_name.clone();
+        self.exchange_type = exchange_options.exchange_type;
+        self.pattern = exchange_options.queue_routing_key.clone();
+        self.field = TesterField::RoutingKey;
+    }
+
+    pub fn next_field(&mut self) {
+        self.field = match self.field {
+            TesterField::Pattern => TesterField::RoutingKey,
+            TesterField::RoutingKey => TesterField::Pattern
+        };
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        match self.field {
+            TesterField::Pattern => self.pattern.push(c),
+            TesterField::RoutingKey => self.routing_key.push(c)
+        }
+    }
+
+    /// Removes the last character, or the last routing key in the list once the input is empty.
+    pub fn pop_char(&mut self) {
+        match self.field {
+            TesterField::Pattern => {
+                self.pattern.pop();
+            }
+            TesterField::RoutingKey if self.routing_key.is_empty() => {
+                self.routing_keys.pop();
+            }
+            TesterField::RoutingKey => {
+                self.routing_key.pop();
+            }
+        }
+    }
+
+    pub fn add_routing_key(&mut self) {
+        if self.field == TesterField::RoutingKey && !self.routing_key.is_empty() {
+            self.routing_keys.push(self.routing_key.clone());
+            self.routing_key.clear();
+        }
+    }
+
+    /// The key a message would be published with: the one being typed, otherwise the last one added.
+    pub fn publish_key(&self) -> Option<&str> {
+        if !self.routing_key.is_empty() {
+            return Some(self.routing_key.as_str());
+        }
+
+        self.routing_keys.last().map(|x| x.as_str())
+    }
+
+    /// Subscribed items on the same exchange whose binding would route a message published with `publish_key`.
+    /// Items still waiting for their first message are bound already, so they count too.
+    pub fn receivers(&self, items: &[ExchangeOptions]) -> Vec<Uuid> {
+        let publish_key = match self.publish_key() {
+            Some(res) => res,
+            None => return vec![]
+        };
+
+        items
+            .iter()
+            .filter(|x| x.selected_state != SelectedState::Unselected && x.exchange_name == self.exchange_name)
+            .filter(|x| binding_matches(x.exchange_type, x.queue_routing_key.as_str(), publish_key) != RouteMatch::No)
+            .map(|x| x.id)
+            .collect()
+    }
+
+    pub fn draw<B: Backend>(&self, frame: &mut Frame<B>, area: Rect, items: &[ExchangeOptions]) {
+        let grid = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Length(3), Constraint::Min(0), Constraint::Length(4)].as_ref())
+            .split(area);
+
+        let input_style = |field: TesterField| {
+            if self.field == field { Style::default().fg(theme::current().input) } else { Style::default().fg(theme::current().foreground) }
+        };
+
+        let pattern = Paragraph::new(self.pattern.as_str())
+            .style(input_style(TesterField::Pattern).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled("Topic Pattern", Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))));
+
+        let routing_key = Paragraph::new(self.routing_key.as_str())
+            .style(input_style(TesterField::RoutingKey).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title("Routing Key"));
+
+        let mut lines: Vec<Spans> = self.routing_keys
+            .iter()
+            .map(|x| self.result_line(x.as_str()))
+            .collect();
+        if !self.routing_key.is_empty() {
+            lines.push(self.result_line(self.routing_key.as_str()));
+        }
+
+        let results = Paragraph::new(lines)
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title("Matches"));
+
+        let receivers = self.receivers(items);
+        let summary = match self.publish_key() {
+            None => "Add routing keys to test them against the pattern.".to_string(),
+            Some(_) if receivers.is_empty() => "No subscribed item would receive it.".to_string(),
+            Some(_) => items
+                .iter()
+                .filter(|x| receivers.contains(&x.id))
+                .map(|x| x.display_name())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        };
+
+        let summary_title = format!("Subscribed items receiving a publish to {} with {}", self.exchange_name, self.publish_key().unwrap_or(""));
+        let summary_paragraph = Paragraph::new(summary)
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(summary_title))
+            .wrap(Wrap { trim: true });
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(pattern, grid[0]);
+        frame.render_widget(routing_key, grid[1]);
+        frame.render_widget(results, grid[2]);
+        frame.render_widget(summary_paragraph, grid[3]);
+    }
+
+    fn result_line(&self, routing_key: &str) -> Spans<'static> {
+        if topic_matches(self.pattern.as_str(), routing_key) {
+            Spans::from(Span::styled(format!("✓ {}", routing_key), Style::default().fg(theme::current().selected)))
+        }
+        else {
+            Spans::from(Span::styled(format!("✗ {}", routing_key), Style::default().fg(theme::current().pending)))
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use anyhow::{Result, Context, anyhow};
+use directories::BaseDirs;
+
+/// Where the broker password comes from. Only the indirection is ever written back to the
+/// config file, never a password that was resolved from it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordSource {
+    /// Plaintext `password` in the config file.
+    Plain(String),
+
+    /// Name of an environment variable holding the password (`password_env`).
+    Env(String),
+
+    /// Path to a file whose first line is the password (`password_file`). A leading `~` is the
+    /// home directory.
+    File(String),
+
+    /// Shell command printing the password on stdout (`password_command`).
+    Command(String),
+
+    /// Nothing configured; the user is asked for the password at startup.
+    Prompt,
+}
+
+impl PasswordSource {
+    pub fn from_fields(password: Option<String>, password_env: Option<String>, password_file: Option<String>, password_command: Option<String>) -> Result<Self> {
+        let sources = [
+            password.map(PasswordSource::Plain),
+            password_env.map(PasswordSource::Env),
+            password_file.map(PasswordSource::File),
+            password_command.map(PasswordSource::Command),
+        ];
+
+        let mut configured = sources.into_iter().flatten();
+
+        match (configured.next(), configured.next()) {
+            (None, _) => Ok(PasswordSource::Prompt),
+            (Some(source), None) => Ok(source),
+            (Some(_), Some(_)) => Err(anyhow!("Only one of password, password_env, password_file or password_command may be set")),
+        }
+    }
+
+    pub fn resolve(&self, username: &str, host: &str) -> Result<String> {
+        match self {
+            PasswordSource::Plain(password) => Ok(password.clone()),
+            PasswordSource::Env(name) => env::var(name)
+                .with_context(|| format!("Reading password from environment variable: {}", name)),
+            PasswordSource::File(path) => {
+                let contents = fs::read_to_string(expand_home(path))
+                    .with_context(|| format!("Reading password file: {}", path))?;
+
+                Ok(contents.lines().next().unwrap_or_default().to_string())
+            }
+            PasswordSource::Command(command) => run_password_command(command),
+            PasswordSource::Prompt => rpassword::prompt_password(format!("Password for {}@{}: ", username, host))
+                .with_context(|| "Reading password from terminal"),
+        }
+    }
+}
+
+/// `path` with a leading `~` replaced by the home directory, as a shell would.
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some(res) if res.is_empty() || res.starts_with('/') || res.starts_with('\\') => res.trim_start_matches(['/', '\\']),
+        _ => return PathBuf::from(path)
+    };
+
+    match BaseDirs::new() {
+        Some(base_dirs) => base_dirs.home_dir().join(rest),
+        None => PathBuf::from(path)
+    }
+}
+
+fn run_password_command(command: &str) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    }
+    else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    let output = shell
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Running password_command: {}", command))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("password_command exited with {}", output.status));
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| "password_command output is not valid UTF-8")?;
+
+    Ok(stdout.lines().next().unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn one_source_at_most() {
+        assert_eq!(PasswordSource::from_fields(None, None, None, None).unwrap(), PasswordSource::Prompt);
+        assert_eq!(PasswordSource::from_fields(some("guest"), None, None, None).unwrap(), PasswordSource::Plain("guest".to_string()));
+        assert_eq!(PasswordSource::from_fields(None, some("AMQP_PASSWORD"), None, None).unwrap(), PasswordSource::Env("AMQP_PASSWORD".to_string()));
+        assert_eq!(PasswordSource::from_fields(None, None, some("~/amqp"), None).unwrap(), PasswordSource::File("~/amqp".to_string()));
+        assert_eq!(PasswordSource::from_fields(None, None, None, some("pass amqp")).unwrap(), PasswordSource::Command("pass amqp".to_string()));
+        assert!(PasswordSource::from_fields(some("guest"), None, None, some("pass amqp")).err().unwrap().to_string().starts_with("Only one of"));
+    }
+
+    #[test]
+    fn from_the_environment() {
+        let name = format!("AMQP_CLIENT_CLI_TEST_PASSWORD_{}", std::process::id());
+        env::set_var(name.as_str(), "p@ss/word");
+
+        assert_eq!(PasswordSource::Env(name.clone()).resolve("guest", "localhost").unwrap(), "p@ss/word");
+        env::remove_var(name.as_str());
+        assert!(PasswordSource::Env(name).resolve("guest", "localhost").is_err());
+    }
+
+    #[test]
+    fn first_line_of_a_file() {
+        let path = env::temp_dir().join(format!("amqp-client-cli-test-{}-password", std::process::id()));
+        fs::write(&path, "secret\nignored\n").unwrap();
+        let password = PasswordSource::File(path.to_string_lossy().to_string()).resolve("guest", "localhost");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(password.unwrap(), "secret");
+        assert!(PasswordSource::File("/nonexistent/password".to_string()).resolve("guest", "localhost").err().unwrap().to_string().starts_with("Reading password file"));
+    }
+
+    #[test]
+    fn home_is_expanded() {
+        let home = BaseDirs::new().unwrap().home_dir().to_path_buf();
+
+        assert_eq!(expand_home("~/.secrets/amqp"), home.join(".secrets/amqp"));
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~other/amqp"), PathBuf::from("~other/amqp"));
+        assert_eq!(expand_home("/etc/amqp"), PathBuf::from("/etc/amqp"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_of_a_command() {
+        assert_eq!(PasswordSource::Command("printf 'from command\\nsecond'".to_string()).resolve("guest", "localhost").unwrap(), "from command");
+        assert_eq!(PasswordSource::Command("exit 3".to_string()).resolve("guest", "localhost").err().unwrap().to_string(), "password_command exited with exit status: 3");
+    }
+}
@@ -0,0 +1,229 @@
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use directories::BaseDirs;
+use serde_derive::{Deserialize, Serialize};
+use crate::Config;
+use crate::amqp::DEFAULT_QUEUE_NAME_TEMPLATE;
+use crate::config::{self, CONFIG_FILE_STEM};
+use crate::management::ManagementClient;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct TrackedQueue {
+    broker: String,
+    queue: String
+}
+
+/// Queues this program created and has not deleted yet, kept in a state file so they can be
+/// cleaned up after an instance was killed. Shared by every instance on the machine.
+#[derive(Clone)]
+pub struct TapQueues {
+    path: Option<PathBuf>,
+    broker: String
+}
+
+impl TapQueues {
+    pub fn new(config: &Config) -> Self {
+        let path = BaseDirs::new().map(|base_dirs| base_dirs.data_local_dir().join(format!("{}-queues.json", CONFIG_FILE_STEM)));
+        let broker = format!("{}:{}/{}", config.host, config.port, config.vhost.clone().unwrap_or_default());
+
+        TapQueues { path, broker }
+    }
+
+    pub fn add(&self, queue: &str) -> Result<()> {
+        let entry = TrackedQueue { broker: self.broker.clone(), queue: queue.to_string() };
+
+        self.update(|tracked| {
+            if tracked.contains(&entry) {
+                return false;
+            }
+            tracked.push(entry);
+            true
+        })
+    }
+
+    pub fn remove(&self, queue: &str) -> Result<()> {
+        self.update(|tracked| {
+            let count = tracked.len();
+            tracked.retain(|x| x.broker != self.broker || x.queue != queue);
+            tracked.len() != count
+        })
+    }
+
+    /// Tracked queues on the config's broker and vhost.
+    pub fn list(&self) -> Result<Vec<String>> {
+        let tracked = match &self.path {
+            Some(path) => read(path)?,
+            None => vec![]
+        };
+
+        Ok(tracked.into_iter().filter(|x| x.broker == self.broker).map(|x| x.queue).collect())
+    }
+
+    /// Changes the tracked queues and writes them back when `change` returns true. The state file
+    /// is locked meanwhile, so instances running at the same time keep each other's entries, and
+    /// replaced in one step, so reading it never sees half of it.
+    fn update(&self, change: impl FnOnce(&mut Vec<TrackedQueue>) -> bool) -> Result<()> {
+        let path = match &self.path {
+            Some(res) => res,
+            None => return Ok(())
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let lock_path = path.with_extension("lock");
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Opening {}", lock_path.display()))?;
+        lock.lock().with_context(|| format!("Locking {}", lock_path.display()))?;
+
+        let mut tracked = read(path)?;
+        if change(&mut tracked) {
+            config::write_atomic(path, serde_json::to_string_pretty(&tracked)?.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// No file yet is no tracked queues, but a file that cannot be parsed is an error, so the next
+/// write does not replace the queues it still lists.
+fn read(path: &Path) -> Result<Vec<TrackedQueue>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let contents = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let tracked = serde_json::from_str(contents.as_str())
+        .with_context(|| format!("Parsing {}, fix or delete it to track queues again", path.display()))?;
+
+    Ok(tracked)
+}
+
+/// Queues that look like they were left behind by an instance that did not exit normally: the
+/// tracked ones, and through the management API every queue with the naming prefix and no
+/// consumers. Whether a queue is still unused is checked again before deleting it.
+pub fn find_orphans(config: &Config, tap_queues: &TapQueues) -> (Vec<String>, Vec<String>) {
+    let mut messages = vec![];
+    let mut queues = tap_queues.list().unwrap_or_else(|e| {
+        messages.push(format!("Cannot read the queues tracked on this machine: {:#}", e));
+        vec![]
+    });
+
+    let management_url = match &config.management_url {
+        Some(res) if !res.is_empty() => res.as_str(),
+        _ => {
+            messages.push("management_url is not set, only queues tracked on this machine are checked".to_string());
+            return (queues, messages);
+        }
+    };
+
+    let prefix = queue_name_prefix(config.queue_name_template.as_deref().unwrap_or(DEFAULT_QUEUE_NAME_TEMPLATE));
+    if prefix.is_empty() {
+        messages.push("queue_name_template does not start with a fixed prefix, only queues tracked on this machine are checked".to_string());
+        return (queues, messages);
+    }
+
+    let listed = ManagementClient::new(management_url, config.username.as_str(), config.password.as_str(), config.vhost.as_deref())
+        .and_then(|client| client.queues());
+
+    match listed {
+        Ok(res) => {
+            for queue in res.into_iter().filter(|x| x.name.starts_with(prefix.as_str()) && x.consumers == Some(0)) {
+                if !queues.contains(&queue.name) {
+                    queues.push(queue.name);
+                }
+            }
+        }
+        Err(e) => messages.push(format!("Cannot list queues through the management API: {:#}", e))
+    }
+
+    (queues, messages)
+}
+
+/// The fixed start of every queue name made from `template`, e.g. `amqp-client-cli.`.
+pub fn queue_name_prefix(template: &str) -> String {
+    let template = template.replace("{app}", env!("CARGO_PKG_NAME"));
+
+    template.split('{').next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn tap_queues(name: &str, broker: &str) -> TapQueues {
+        let path = std::env::temp_dir().join(format!("amqp-client-cli-test-{}-{}.json", std::process::id(), name));
+        fs::remove_file(&path).ok();
+        TapQueues { path: Some(path), broker: broker.to_string() }
+    }
+
+    #[test]
+    fn tracks_queues_per_broker() {
+        let local = tap_queues("per-broker", "localhost:5672/");
+        let other = TapQueues { broker: "other:5672/".to_string(), ..local.clone() };
+
+        local.add("a").unwrap();
+        local.add("a").unwrap();
+        other.add("a").unwrap();
+        local.add("b").unwrap();
+        assert_eq!(local.list().unwrap(), vec!["a", "b"]);
+        assert_eq!(other.list().unwrap(), vec!["a"]);
+
+        local.remove("a").unwrap();
+        assert_eq!(local.list().unwrap(), vec!["b"]);
+        assert_eq!(other.list().unwrap(), vec!["a"]);
+
+        fs::remove_file(local.path.unwrap()).ok();
+    }
+
+    #[test]
+    fn instances_at_the_same_time_keep_each_others_queues() {
+        let shared = tap_queues("concurrent", "localhost:5672/");
+
+        let threads: Vec<_> = (0..8)
+            .map(|instance| {
+                let tap_queues = shared.clone();
+                thread::spawn(move || {
+                    for i in 0..20 {
+                        tap_queues.add(format!("q.{}.{}", instance, i).as_str()).unwrap();
+                    }
+                    for i in 0..10 {
+                        tap_queues.remove(format!("q.{}.{}", instance, i).as_str()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|x| x.join().unwrap());
+
+        assert_eq!(shared.list().unwrap().len(), 80);
+        fs::remove_file(shared.path.unwrap()).ok();
+    }
+
+    #[test]
+    fn corrupt_state_file_is_reported_and_kept() {
+        let tap_queues = tap_queues("corrupt", "localhost:5672/");
+        let path = tap_queues.path.clone().unwrap();
+        fs::write(&path, "[{\"broker\": \"localhost:5672/\", \"queue\": \"a\"}, {\"bro").unwrap();
+
+        assert!(tap_queues.list().is_err());
+        let error = format!("{:#}", tap_queues.add("b").unwrap_err());
+        assert!(error.starts_with("Parsing"), "{}", error);
+        assert!(fs::read_to_string(&path).unwrap().ends_with("{\"bro"));
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn prefix_is_the_fixed_start_of_the_template() {
+        assert_eq!(queue_name_prefix(DEFAULT_QUEUE_NAME_TEMPLATE), "amqp-client-cli.");
+        assert_eq!(queue_name_prefix("tap-{exchange}-{item}"), "tap-");
+        assert_eq!(queue_name_prefix("{item}"), "");
+    }
+}
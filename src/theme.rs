@@ -1,8 +1,331 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
 use tui::style::Color;
+use crate::config::ConfigFormat;
+use crate::validate;
 
-pub const BACKGROUND: Color = Color::Black;
-pub const FOREGROUND: Color = Color::White;
-pub const SELECTED: Color = Color::Green;
-pub const INPUT: Color = Color::Yellow;
-pub const PENDING: Color = Color::DarkGray;
-pub const ACCENT: Color = Color::Cyan;
\ No newline at end of file
+/// The colours everything is drawn with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub foreground: Color,
+    pub selected: Color,
+    pub input: Color,
+    pub pending: Color,
+    pub accent: Color,
+    pub json_key: Color,
+    pub json_string: Color,
+    pub json_number: Color,
+    pub json_literal: Color,
+    pub diff_added: Color,
+    pub diff_removed: Color,
+    pub diff_changed: Color
+}
+
+pub const DARK: Theme = Theme {
+    background: Color::Black,
+    foreground: Color::White,
+    selected: Color::Green,
+    input: Color::Yellow,
+    pending: Color::DarkGray,
+    accent: Color::Cyan,
+    json_key: Color::Cyan,
+    json_string: Color::Green,
+    json_number: Color::Magenta,
+    json_literal: Color::Yellow,
+    diff_added: Color::Green,
+    diff_removed: Color::Red,
+    diff_changed: Color::Yellow
+};
+
+pub const LIGHT: Theme = Theme {
+    background: Color::White,
+    foreground: Color::Black,
+    selected: Color::Green,
+    input: Color::Blue,
+    pending: Color::Gray,
+    accent: Color::Blue,
+    json_key: Color::Blue,
+    json_string: Color::Green,
+    json_number: Color::Magenta,
+    json_literal: Color::Red,
+    diff_added: Color::Green,
+    diff_removed: Color::Red,
+    diff_changed: Color::Blue
+};
+
+/// The terminal's own colours, for `NO_COLOR`.
+pub const NO_COLOR: Theme = Theme {
+    background: Color::Reset,
+    foreground: Color::Reset,
+    selected: Color::Reset,
+    input: Color::Reset,
+    pending: Color::Reset,
+    accent: Color::Reset,
+    json_key: Color::Reset,
+    json_string: Color::Reset,
+    json_number: Color::Reset,
+    json_literal: Color::Reset,
+    diff_added: Color::Reset,
+    diff_removed: Color::Reset,
+    diff_changed: Color::Reset
+};
+
+const THEME_COLORS: [&str; 13] = ["background", "foreground", "selected", "input", "pending", "accent", "json_key", "json_string", "json_number", "json_literal", "diff_added", "diff_removed", "diff_changed"];
+
+static CURRENT: RwLock<Theme> = RwLock::new(DARK);
+
+/// The theme being drawn with.
+pub fn current() -> Theme {
+    *CURRENT.read().unwrap()
+}
+
+pub fn set(theme: Theme) {
+    *CURRENT.write().unwrap() = theme;
+}
+
+/// Whether the `NO_COLOR` environment variable asks for no colours.
+pub fn no_color() -> bool {
+    env::var("NO_COLOR").map(|x| !x.is_empty()).unwrap_or(false)
+}
+
+/// Whether colours of items are left out too, because of `NO_COLOR` or the `no-color` theme.
+pub fn colorless() -> bool {
+    no_color() || current() == NO_COLOR
+}
+
+impl Theme {
+    fn with_color(mut self, name: &str, color: Color) -> Result<Self> {
+        let field = match name {
+            "background" => &mut self.background,
+            "foreground" => &mut self.foreground,
+            "selected" => &mut self.selected,
+            "input" => &mut self.input,
+            "pending" => &mut self.pending,
+            "accent" => &mut self.accent,
+            "json_key" => &mut self.json_key,
+            "json_string" => &mut self.json_string,
+            "json_number" => &mut self.json_number,
+            "json_literal" => &mut self.json_literal,
+            "diff_added" => &mut self.diff_added,
+            "diff_removed" => &mut self.diff_removed,
+            "diff_changed" => &mut self.diff_changed,
+            other => return Err(anyhow!("Unknown theme colour {}, expected base or one of {}", other, THEME_COLORS.join(", ")))
+        };
+        *field = color;
+
+        Ok(self)
+    }
+}
+
+/// The themes to choose from: `dark`, `light` and `no-color`, then the ones in the theme file and
+/// the config, which replace themes of the same name.
+pub struct Themes {
+    themes: Vec<(String, Theme)>,
+    current: usize
+}
+
+impl Themes {
+    pub fn load(theme_file: Option<&str>, themes: Option<&Map<String, Value>>) -> Result<Self> {
+        let mut loaded = Themes {
+            themes: vec![("dark".to_string(), DARK), ("light".to_string(), LIGHT), ("no-color".to_string(), NO_COLOR)],
+            current: 0
+        };
+
+        if let Some(path) = theme_file {
+            let source = fs::read_to_string(path).map_err(|e| anyhow!("Reading theme file {}: {}", path, e))?;
+            let document = validate::parse_document(source.as_str(), ConfigFormat::from_path(Path::new(path)))
+                .map_err(|e| anyhow!("Parsing theme file {}: {}", path, e))?;
+            loaded.add_all(&document).map_err(|e| anyhow!("Theme file {}: {}", path, e))?;
+        }
+
+        if let Some(res) = themes {
+            loaded.add_all(res)?;
+        }
+
+        Ok(loaded)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.themes.iter().map(|x| x.0.as_str()).collect()
+    }
+
+    /// Draws with the theme called `name`, or without colours when `NO_COLOR` is set.
+    pub fn start(&mut self, name: &str) -> Result<()> {
+        self.select(name)?;
+        if no_color() {
+            self.select("no-color")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn select(&mut self, name: &str) -> Result<()> {
+        self.current = self.position(name).ok_or_else(|| anyhow!("Unknown theme {}, expected one of {}", name, self.names().join(", ")))?;
+        set(self.themes[self.current].1);
+
+        Ok(())
+    }
+
+    /// Draws with the next theme and returns its name.
+    pub fn next(&mut self) -> &str {
+        self.current = (self.current + 1) % self.themes.len();
+        set(self.themes[self.current].1);
+
+        self.themes[self.current].0.as_str()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.themes.iter().position(|x| x.0 == name)
+    }
+
+    /// Adds each theme in `themes`, an object with the colours it changes and the theme it
+    /// starts from as `base`, `dark` when it has none.
+    fn add_all(&mut self, themes: &Map<String, Value>) -> Result<()> {
+        for (name, definition) in themes {
+            let definition = definition.as_object().ok_or_else(|| anyhow!("Theme {} must be an object of colours", name))?;
+
+            let base_name = match definition.get("base") {
+                None | Some(Value::Null) => "dark",
+                Some(Value::String(res)) => res.as_str(),
+                Some(other) => return Err(anyhow!("Theme {}: base must be the name of a theme, found {}", name, other))
+            };
+            let mut theme = self.position(base_name)
+                .map(|x| self.themes[x].1)
+                .ok_or_else(|| anyhow!("Theme {}: unknown base {}, expected one of {}", name, base_name, self.names().join(", ")))?;
+
+            for (key, value) in definition.iter().filter(|x| x.0 != "base") {
+                //256 colour palette numbers can be written without quotes
+                let color = match value {
+                    Value::String(res) => res.clone(),
+                    Value::Number(res) => res.to_string(),
+                    _ => return Err(anyhow!("Theme {}: {} must be a colour, found {}", name, key, value))
+                };
+                theme = theme.with_color(key, parse_color(color.as_str()).map_err(|e| anyhow!("Theme {}: {}: {}", name, key, e))?)
+                    .map_err(|e| anyhow!("Theme {}: {}", name, e))?;
+            }
+
+            match self.position(name) {
+                Some(res) => self.themes[res].1 = theme,
+                None => self.themes.push((name.clone(), theme))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A colour written as a name like `red` or `light_blue`, as `#rrggbb`, or as a number from 0 to
+/// 255 for the 256 colour palette.
+pub fn parse_color(value: &str) -> Result<Color> {
+    let value = value.trim();
+    let invalid = || anyhow!("Unknown colour {}, expected a name such as red or light_blue, #rrggbb or 0-255", value);
+
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|x| x.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let rgb = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+
+    if let Ok(index) = value.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+
+    let name: String = value.to_lowercase().chars().filter(|x| !"_- ".contains(*x)).collect();
+    let color = match name.as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        "default" | "reset" => Color::Reset,
+        _ => return Err(invalid())
+    };
+
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn themes(value: Value) -> Result<Themes> {
+        Themes::load(None, value.as_object())
+    }
+
+    #[test]
+    fn colours() {
+        assert_eq!(parse_color("Light_Blue").unwrap(), Color::LightBlue);
+        assert_eq!(parse_color(" dark-grey ").unwrap(), Color::DarkGray);
+        assert_eq!(parse_color("#ff8000").unwrap(), Color::Rgb(255, 128, 0));
+        assert_eq!(parse_color("208").unwrap(), Color::Indexed(208));
+        assert_eq!(parse_color("default").unwrap(), Color::Reset);
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("256").is_err());
+        assert!(parse_color("mauve").err().unwrap().to_string().starts_with("Unknown colour mauve"));
+    }
+
+    #[test]
+    fn themes_start_from_their_base() {
+        let mut loaded = themes(json!({
+            "ocean": {"accent": "#0080ff", "json_number": 208},
+            "paper": {"base": "light", "diff_changed": "magenta"},
+            "light": {"accent": "red"}
+        })).unwrap();
+
+        assert_eq!(loaded.names(), vec!["dark", "light", "no-color", "ocean", "paper"]);
+        let find = |name: &str| loaded.themes[loaded.position(name).unwrap()].1;
+        assert_eq!(find("ocean"), Theme { accent: Color::Rgb(0, 128, 255), json_number: Color::Indexed(208), ..DARK });
+        //paper is read before light is replaced, later themes see earlier ones
+        assert_eq!(find("paper"), Theme { diff_changed: Color::Magenta, ..LIGHT });
+        //a theme without a base starts from dark, even when it replaces light
+        assert_eq!(find("light"), Theme { accent: Color::Red, ..DARK });
+
+        assert!(loaded.select("sepia").err().unwrap().to_string().starts_with("Unknown theme sepia, expected one of dark, light"));
+    }
+
+    #[test]
+    fn invalid_themes() {
+        let error = |value: Value| themes(value).err().unwrap().to_string();
+
+        assert_eq!(error(json!({"a": "red"})), "Theme a must be an object of colours");
+        assert_eq!(error(json!({"a": {"base": 1}})), "Theme a: base must be the name of a theme, found 1");
+        assert!(error(json!({"a": {"base": "sepia"}})).starts_with("Theme a: unknown base sepia"));
+        assert!(error(json!({"a": {"border": "red"}})).starts_with("Theme a: Unknown theme colour border"));
+        assert!(error(json!({"a": {"accent": "mauve"}})).starts_with("Theme a: accent: Unknown colour mauve"));
+        assert_eq!(error(json!({"a": {"accent": true}})), "Theme a: accent must be a colour, found true");
+    }
+
+    #[test]
+    fn theme_files() {
+        let path = std::env::temp_dir().join(format!("amqp-client-cli-test-{}-themes.toml", std::process::id()));
+        fs::write(&path, "[ocean]\naccent = \"blue\"\n").unwrap();
+        let loaded = Themes::load(path.to_str(), Some(json!({"ocean": {"base": "ocean", "input": "red"}}).as_object().unwrap()));
+        fs::write(&path, "[ocean]\naccent = \"mauve\"\n").unwrap();
+        let invalid = Themes::load(path.to_str(), None);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.themes[3].1, Theme { accent: Color::Blue, input: Color::Red, ..DARK });
+        assert!(invalid.err().unwrap().to_string().starts_with(format!("Theme file {}: Theme ocean: accent", path.display()).as_str()));
+        assert!(Themes::load(Some("/nonexistent/themes.json"), None).err().unwrap().to_string().starts_with("Reading theme file"));
+    }
+}
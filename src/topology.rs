@@ -0,0 +1,221 @@
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use serde_json::Value;
+use crate::{Config, theme};
+use crate::management::SnapshotRequest;
+use crate::models::enums::ExchangeTypeSer;
+use crate::models::management::{BindingInfo, ManagementSnapshot};
+use crate::routing::{binding_matches, RouteMatch};
+
+/// Tree of everything bound to one exchange, following exchange to exchange bindings, with the
+/// bindings a test routing key would take highlighted.
+pub struct Topology {
+    pub routing_key: String,
+
+    exchange_name: String,
+    snapshot: Option<ManagementSnapshot>,
+    request: Option<SnapshotRequest>,
+    status: String,
+    scroll: u16
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Topology {
+            routing_key: "".to_string(),
+            exchange_name: "".to_string(),
+            snapshot: None,
+            request: None,
+            status: "".to_string(),
+            scroll: 0
+        }
+    }
+
+    pub fn open(&mut self, exchange_name: &str, config: &Config) {
+        self.exchange_name = exchange_name.to_string();
+        self.scroll = 0;
+        self.refresh(config);
+    }
+
+    pub fn refresh(&mut self, config: &Config) {
+        match SnapshotRequest::spawn(config, None) {
+            Ok(request) => {
+                self.request = Some(request);
+                self.status = "Requesting...".to_string();
+            }
+            Err(e) => self.status = e.to_string()
+        }
+    }
+
+    pub fn poll(&mut self) {
+        let result = match self.request.as_ref().and_then(|x| x.try_take()) {
+            Some(res) => res,
+            None => return
+        };
+
+        self.request = None;
+
+        match result {
+            Ok(snapshot) => {
+                self.status = "".to_string();
+                self.snapshot = Some(snapshot);
+            }
+            Err(e) => self.status = format!("Error: {}", e)
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll += 1;
+    }
+
+    pub fn draw<B: Backend>(&self, frame: &mut Frame<B>, area: Rect) {
+        let grid = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0), Constraint::Length(4)].as_ref())
+            .split(area);
+
+        let mut tree: Vec<Spans> = vec![];
+        let mut receivers: Vec<(String, RouteMatch)> = vec![];
+        let mut exchange_type = "".to_string();
+
+        if let Some(snapshot) = &self.snapshot {
+            match snapshot.exchanges.iter().find(|x| x.name == self.exchange_name) {
+                None => tree.push(Spans::from(format!("Exchange {} does not exist on vhost {}", self.exchange_name, snapshot.vhost))),
+                Some(exchange) => {
+                    exchange_type = exchange.exchange_type.clone();
+                    tree.push(Spans::from(Span::styled(format!("{} ({})", exchange.name, exchange.exchange_type), Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))));
+
+                    let reach = if self.routing_key.is_empty() { None } else { Some(RouteMatch::Yes) };
+                    let mut visited = vec![self.exchange_name.clone()];
+                    self.add_bindings(snapshot, self.exchange_name.as_str(), reach, "", &mut visited, &mut tree, &mut receivers);
+
+                    if tree.len() == 1 {
+                        tree.push(Spans::from("└── (nothing is bound to this exchange)"));
+                    }
+                }
+            }
+        }
+
+        let title = format!("Topology {} {}", self.exchange_name, self.status);
+
+        let input = Paragraph::new(self.routing_key.as_str())
+            .style(Style::default().fg(theme::current().input).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title("Test Routing Key"));
+
+        let tree_paragraph = Paragraph::new(tree)
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled(title, Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
+            .scroll((self.scroll, 0));
+
+        let summary = if self.routing_key.is_empty() {
+            "Type a routing key to see which queues would receive a message published with it.".to_string()
+        }
+        else if receivers.is_empty() {
+            format!("No queue would receive a message published to {} with routing key {}", self.exchange_name, self.routing_key)
+        }
+        else {
+            receivers
+                .iter()
+                .map(|(queue, reach)| if *reach == RouteMatch::Unknown { format!("{} (depends on headers)", queue) } else { queue.clone() })
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        let summary_title = if exchange_type == "headers" { "Receives (headers exchanges route on message headers)" } else { "Receives" };
+        let summary_paragraph = Paragraph::new(summary)
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(summary_title))
+            .wrap(Wrap { trim: true });
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(input, grid[0]);
+        frame.render_widget(tree_paragraph, grid[1]);
+        frame.render_widget(summary_paragraph, grid[2]);
+    }
+
+    /// `reach` is `None` when no routing key is being tested, otherwise whether a message can get
+    /// as far as `exchange_name`.
+    #[allow(clippy::too_many_arguments)]
+    fn add_bindings(&self, snapshot: &ManagementSnapshot, exchange_name: &str, reach: Option<RouteMatch>, prefix: &str, visited: &mut Vec<String>, tree: &mut Vec<Spans>, receivers: &mut Vec<(String, RouteMatch)>) {
+        let exchange_type = snapshot.exchanges
+            .iter()
+            .find(|x| x.name == exchange_name)
+            .and_then(|x| x.exchange_type_ser());
+
+        let bindings: Vec<&BindingInfo> = snapshot.bindings
+            .iter()
+            .filter(|x| x.source == exchange_name)
+            .collect();
+
+        for (i, binding) in bindings.iter().enumerate() {
+            let is_last = i == bindings.len() - 1;
+            let branch = if is_last { "└── " } else { "├── " };
+
+            let binding_match = match (reach, exchange_type) {
+                (None, _) => None,
+                (Some(RouteMatch::No), _) => Some(RouteMatch::No),
+                (Some(_), None) => Some(RouteMatch::Unknown),
+                (Some(parent), Some(exchange_type)) => {
+                    match binding_matches(exchange_type, binding.routing_key.as_str(), self.routing_key.as_str()) {
+                        RouteMatch::Yes => Some(parent),
+                        other => Some(other)
+                    }
+                }
+            };
+
+            let (marker, style) = match binding_match {
+                None => ("", Style::default().fg(theme::current().foreground)),
+                Some(RouteMatch::Yes) => (" ✓", Style::default().fg(theme::current().selected)),
+                Some(RouteMatch::No) => (" ✗", Style::default().fg(theme::current().pending)),
+                Some(RouteMatch::Unknown) => (" ?", Style::default().fg(theme::current().input)),
+            };
+
+            let key = match exchange_type {
+                Some(ExchangeTypeSer::Headers) => format!("headers {}", format_arguments(&binding.arguments)),
+                Some(ExchangeTypeSer::Fanout) => "(all messages)".to_string(),
+                _ => format!("[{}]", binding.routing_key)
+            };
+
+            let is_cycle = binding.destination_type == "exchange" && visited.contains(&binding.destination);
+
+            tree.push(Spans::from(vec![
+                Span::raw(format!("{}{}", prefix, branch)),
+                Span::styled(format!("{} {} {}{}{}", binding.destination_type, binding.destination, key, marker, if is_cycle { " (cycle)" } else { "" }), style),
+            ]));
+
+            if binding.destination_type == "queue" {
+                if let Some(res) = binding_match {
+                    if res != RouteMatch::No && !receivers.iter().any(|x| x.0 == binding.destination) {
+                        receivers.push((binding.destination.clone(), res));
+                    }
+                }
+            }
+            else if !is_cycle {
+                //messages routed to an exchange keep their routing key
+                visited.push(binding.destination.clone());
+                let child_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+                self.add_bindings(snapshot, binding.destination.as_str(), binding_match, child_prefix.as_str(), visited, tree, receivers);
+                visited.pop();
+            }
+        }
+    }
+}
+
+fn format_arguments(arguments: &Value) -> String {
+    match arguments {
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v.as_str().map(|x| x.to_string()).unwrap_or_else(|| v.to_string())))
+            .collect::<Vec<String>>()
+            .join(" "),
+        _ => "".to_string()
+    }
+}
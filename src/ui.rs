@@ -1,17 +1,29 @@
+use std::collections::{HashSet, VecDeque};
 use bevy_reflect::{GetField, Reflect, Uuid};
-use serde_json::Value;
 use tui::backend::Backend;
 use tui::Frame;
 use tui::layout::{Alignment, Constraint, Corner, Direction, Layout, Rect};
-use tui::style::{Modifier, Style};
+use tui::style::{Color, Modifier, Style};
 use tui::text::{Span, Spans};
-use tui::widgets::{Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table};
+use tui::widgets::{Block, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Table, TableState};
 use crate::{App, theme};
 use crate::app::{Mode, Windows};
+use crate::keymap::Action;
 use crate::models::exchange_options::{ExchangeOptions};
 use bevy_reflect::Struct;
 use itertools::Itertools;
 use crate::file_logger::FileLogger;
+use crate::message_list::{MessageList, MessageRecord};
+use crate::message_search::MessageSearch;
+use crate::payload;
+use crate::payload::ViewMode;
+use crate::json_view;
+use crate::projection;
+use crate::file_logger::LogFormat;
+use crate::column_view::ColumnView;
+use crate::message_tiles;
+use crate::message_tiles::MessageTile;
+use anyhow::Result;
 use crate::models::enums::{ExchangeTypeSer, SelectedState};
 
 pub enum EditType {
@@ -20,10 +32,46 @@ pub enum EditType {
     MultiSelect(ExchangeTypeSer)
 }
 
+/// What a line of the message stream is, which decides its colours.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineKind {
+    Text,
+    /// Pretty printed JSON.
+    Json,
+    /// The name and time above a message, in the item's colour.
+    Header(Option<Color>)
+}
+
+impl LineKind {
+    /// `line` coloured for its kind, with search matches marked.
+    pub fn spans(&self, line: &str, search: &MessageSearch, current: bool) -> Spans<'static> {
+        match self {
+            LineKind::Text | LineKind::Header(None) => search.highlight(line, current),
+            LineKind::Json => search.highlight_spans(json_view::highlight_line(line), current),
+            LineKind::Header(Some(color)) => search.highlight_spans(vec![Span::styled(line.to_string(), Style::default().fg(*color))], current)
+        }
+    }
+}
+
+/// A line of the message stream.
+struct StreamLine {
+    text: String,
+    kind: LineKind,
+    /// The message it belongs to, for the search filter view.
+    message_id: usize
+}
+
 pub struct Ui {
     pub options_exchange: ExchangeOptions,
     pub show_logs: bool,
     pub string_input: String,
+    pub search: MessageSearch,
+    pub messages: MessageList,
+    pub show_message_list: bool,
+    /// One item's messages as a table, shown instead of the other message views.
+    pub column_view: Option<ColumnView>,
+    /// Each subscribed item's messages in a pane of its own instead of one stream.
+    pub show_tiles: bool,
 
     selector_index: usize,
     options_window_index: i32,
@@ -36,15 +84,22 @@ pub struct Ui {
     multi_select_list_items: Vec<String>,
     options_exchange_type: ExchangeTypeSer,
     options_count: usize,
-    line_buffer: Vec<String>,
-    line_buffer_size: usize,
-    window_lines: Vec<String>,
+    /// The newest lines of the message stream, the bottom of which is shown while not paused.
+    stream: VecDeque<StreamLine>,
+    stream_size: usize,
+    message_count: usize,
+    /// The line in `stream` the last search jump went to.
+    search_line: Option<usize>,
+    search_origin: i32,
     messages_window_height: i32,
     scroll_position: i32,
     messages_upper_scroll: usize,
     messages_lower_scroll: usize,
     selector_length: usize,
-    selector_ids: Vec<Uuid>
+    selector_ids: Vec<Uuid>,
+    pending_selection: Option<Uuid>,
+    show_confirm: bool,
+    confirm_message: String
 }
 
 impl Ui {
@@ -61,9 +116,16 @@ impl Ui {
             show_logs: true,
             options_exchange,
             options_count,
-            line_buffer: vec![],
-            line_buffer_size: 1000,
-            window_lines: vec![],
+            stream: VecDeque::new(),
+            stream_size: 1000,
+            message_count: 0,
+            search: MessageSearch::new(),
+            messages: MessageList::new(1000),
+            show_message_list: false,
+            column_view: None,
+            show_tiles: false,
+            search_line: None,
+            search_origin: 0,
             messages_window_height: 0,
             show_string_input: false,
             string_input: "".to_string(),
@@ -76,6 +138,9 @@ impl Ui {
             options_exchange_type: ExchangeTypeSer::Direct,
             selector_length: 0,
             selector_ids: vec![],
+            pending_selection: None,
+            show_confirm: false,
+            confirm_message: "".to_string(),
         }
     }
 
@@ -119,88 +184,200 @@ impl Ui {
         if self.show_multi_select_input {
             self.draw_multi_select_input(frame);
         }
+
+        if app.active_window == Windows::Browser {
+            app.browser.draw(frame, Self::centered_rect(90, 80, frame.size()));
+        }
+
+        if app.active_window == Windows::Topology {
+            app.topology.draw(frame, Self::centered_rect(90, 80, frame.size()));
+        }
+
+        if app.active_window == Windows::OperationInput || app.active_window == Windows::ConfirmOperation {
+            self.draw_operation_input(frame, app);
+        }
+
+        if app.active_window == Windows::MessageSearch {
+            self.draw_search_input(frame);
+        }
+
+        if self.show_confirm {
+            self.draw_confirm(frame);
+        }
+
+        if app.active_window == Windows::MessageDiff {
+            app.message_diff.draw(frame, Self::centered_rect(90, 80, frame.size()));
+        }
+
+        if app.active_window == Windows::RoutingTester {
+            app.routing_tester.draw(frame, Self::centered_rect(50, 70, frame.size()), &app.config.items);
+        }
     }
 
     fn draw_header<B: Backend>(&mut self, frame: &mut Frame<B>, app: &App, grid: &Vec<Rect>) {
         let content = match app.active_window {
-            Windows::Main => {
-                match app.mode {
-                    Mode::Normal => " ↑ Select | ↓ Select | → Width | ← Width | (Enter) select | (F)ilter | (L)ogs | (E)dit | (P)ause | (S)ave | (n) (Shift+P) Publish Message | (Esc) (Q)uit |",
-                    Mode::Scroll => " ↑ Scroll Up | ↓ Scroll Down | (Pg Up) Page Up | (Pg Dn) Page Down | → Width | ← Width | (L)ogs | (P)ause | (Esc) (Q)uit |"
-                }
-            }
-            Windows::Options => " ↑ Select | ↓ Select | (Esc) Close Window | (E)dit Value | (Enter) Apply Changes |",
-            Windows::OptionsStringInput => " (Esc) Close Window | (Enter) Change Value |",
-            Windows::SelectionFilter => " (Esc) Close Window | (Enter) Change Value |",
-            Windows::MultiSelectInput => " ↑ Select | ↓ Select | (Esc) Close Window | (Enter) Change Value |",
+            Windows::Main => self.main_hints(app),
+            _ => Self::popup_hints(app)
         };
 
         let block = Block::default().borders(Borders::TOP | Borders::BOTTOM);
         let paragraph = Paragraph::new(content)
             .block(block)
             .style(Style::default()
-                .fg(theme::FOREGROUND)
-                .bg(theme::BACKGROUND)
+                .fg(theme::current().foreground)
+                .bg(theme::current().background)
                 .add_modifier(Modifier::BOLD));
 
         frame.render_widget(paragraph, grid[0]);
     }
 
+    /// The keys of the popup window that is open, from the keymap.
+    fn popup_hints(app: &App) -> String {
+        let keymap = &app.keymap;
+        let text = |x: &str| Some(x.to_string());
+        let close = keymap.hint(Action::Close, "Close Window");
+
+        let hints = match app.active_window {
+            Windows::Options => vec![
+                keymap.hint(Action::Up, "Select"), keymap.hint(Action::Down, "Select"), close, keymap.hint(Action::Edit, "Edit Value"), text("(Enter) Apply Changes")
+            ],
+            Windows::OptionsStringInput | Windows::SelectionFilter => vec![close, text("(Enter) Change Value")],
+            Windows::MultiSelectInput => vec![keymap.hint(Action::Up, "Select"), keymap.hint(Action::Down, "Select"), close, text("(Enter) Change Value")],
+            Windows::ConfirmDelete | Windows::ConfirmOperation => vec![text("(Y)es"), text("(N)o"), close],
+            Windows::OperationInput => vec![
+                text("exchange declare|delete|bind|unbind ..."), text("queue declare|purge|delete|bind|unbind ..."), text("(Enter) Run"), close
+            ],
+            Windows::Browser => vec![
+                keymap.hint(Action::PreviousTab, "Tab"), keymap.hint(Action::NextTab, "Tab"), keymap.hint(Action::Up, "Select"), keymap.hint(Action::Down, "Select"),
+                text("(Enter) Add to Selector / Open Vhost"), keymap.hint(Action::Add, "Add to Selector"), keymap.hint(Action::Refresh, "Refresh"), close
+            ],
+            Windows::RoutingTester => vec![text("(Tab) Pattern / Routing Key"), text("(Enter) Add Routing Key"), text("(Backspace) Delete"), close],
+            Windows::Topology => vec![text("(Type) Routing Key"), keymap.hint(Action::Up, "Scroll"), keymap.hint(Action::Down, "Scroll"), text("(Enter) Refresh"), close],
+            Windows::MessageSearch => vec![text("(Type) Regex"), text("(Enter) Keep Search"), keymap.hint(Action::Close, "Clear Search")],
+            Windows::MessageDiff => vec![
+                keymap.hint(Action::Up, "Scroll"), keymap.hint(Action::Down, "Scroll"), keymap.hint_pair(Action::PageUp, Action::PageDown, "Page"), close
+            ],
+            Windows::Main => vec![]
+        };
+
+        format!(" {} |", hints.into_iter().flatten().join(" | "))
+    }
+
+    /// The main window's keys, from the keymap, for what is shown.
+    fn main_hints(&self, app: &App) -> String {
+        let keymap = &app.keymap;
+        let sort = Some("(1-9) Sort by Column".to_string());
+
+        let hints = match app.mode {
+            Mode::Normal if self.column_view.is_some() => vec![
+                keymap.hint(Action::Up, "Select"), keymap.hint(Action::Down, "Select"), keymap.hint(Action::GrowSelector, "Width"), keymap.hint(Action::ShrinkSelector, "Width"),
+                keymap.hint(Action::Subscribe, "Subscribe"), sort, keymap.hint(Action::Grid, "Grid Off"), keymap.hint(Action::Logs, "Logs"), keymap.hint(Action::Edit, "Edit"),
+                keymap.hint(Action::Operation, "Operation"), keymap.hint(Action::Pause, "Pause"), keymap.hint(Action::Save, "Save"), keymap.hint(Action::Publish, "Publish Message"),
+                keymap.hint(Action::Quit, "Quit")
+            ],
+            Mode::Normal => vec![
+                keymap.hint(Action::Up, "Select"), keymap.hint(Action::Down, "Select"), keymap.hint(Action::GrowSelector, "Width"), keymap.hint(Action::ShrinkSelector, "Width"),
+                keymap.hint(Action::Subscribe, "Subscribe"), keymap.hint(Action::Filter, "Filter"), keymap.hint(Action::Logs, "Logs"), keymap.hint(Action::Edit, "Edit"),
+                keymap.hint(Action::Add, "Add"), keymap.hint(Action::Clone, "Clone"), keymap.hint(Action::Delete, "Delete"), keymap.hint(Action::Browse, "Browse"),
+                keymap.hint(Action::Topology, "Topology"), keymap.hint(Action::RoutingTester, "Routing Tester"), keymap.hint(Action::Operation, "Operation"),
+                keymap.hint(Action::MessageList, "Message List"), keymap.hint(Action::Grid, "Grid"), keymap.hint(Action::Tiles, "Tiles"), keymap.hint(Action::Theme, "Theme"),
+                keymap.hint(Action::Pause, "Pause"), keymap.hint(Action::Save, "Save"), keymap.hint(Action::Publish, "Publish Message"), keymap.hint(Action::Quit, "Quit")
+            ],
+            Mode::Scroll if self.column_view.is_some() => vec![
+                keymap.hint(Action::Up, "Select Row"), keymap.hint(Action::Down, "Select Row"), keymap.hint_pair(Action::PageUp, Action::PageDown, "Page"),
+                keymap.hint(Action::GrowSelector, "Width"), keymap.hint(Action::ShrinkSelector, "Width"), sort, keymap.hint(Action::Grid, "Grid Off"),
+                keymap.hint(Action::Logs, "Logs"), keymap.hint(Action::Pause, "Pause"), keymap.hint(Action::Quit, "Quit")
+            ],
+            Mode::Scroll if self.show_message_list && self.messages.detail_focus => vec![
+                keymap.hint_pair(Action::Up, Action::Down, "Move"), keymap.hint(Action::Fold, "Fold"), keymap.hint(Action::FoldAll, "Fold All"), keymap.hint(Action::UnfoldAll, "Unfold All"),
+                keymap.hint_pair(Action::PageUp, Action::PageDown, "Scroll Message"), keymap.hint(Action::DetailFocus, "Message List"), keymap.hint(Action::Search, "Search"),
+                keymap.hint(Action::ViewMode, "View Mode"), keymap.hint(Action::Copy, "Copy Message"), keymap.hint(Action::Logs, "Logs"), keymap.hint(Action::Pause, "Pause"),
+                keymap.hint(Action::Quit, "Quit")
+            ],
+            Mode::Scroll if self.show_message_list => vec![
+                keymap.hint(Action::Up, "Older Message"), keymap.hint(Action::Down, "Newer Message"), keymap.hint_pair(Action::PageUp, Action::PageDown, "Scroll Message"),
+                keymap.hint(Action::GrowSelector, "Width"), keymap.hint(Action::ShrinkSelector, "Width"), keymap.hint(Action::Search, "Search"),
+                keymap.hint(Action::NextMatch, "Older Match"), keymap.hint(Action::PreviousMatch, "Newer Match"), keymap.hint(Action::FilterView, "Filter View"),
+                keymap.hint(Action::ViewMode, "View Mode"), keymap.hint(Action::Mark, "Mark"), keymap.hint(Action::Diff, "Diff"), keymap.hint(Action::DetailFocus, "Message Detail"),
+                keymap.hint(Action::MessageList, "Message Stream"), keymap.hint(Action::Copy, "Copy Message"), keymap.hint(Action::Logs, "Logs"), keymap.hint(Action::Pause, "Pause"),
+                keymap.hint(Action::Quit, "Quit")
+            ],
+            Mode::Scroll => vec![
+                keymap.hint(Action::Up, "Scroll Up"), keymap.hint(Action::Down, "Scroll Down"), keymap.hint(Action::PageUp, "Page Up"), keymap.hint(Action::PageDown, "Page Down"),
+                keymap.hint(Action::GrowSelector, "Width"), keymap.hint(Action::ShrinkSelector, "Width"), keymap.hint(Action::Search, "Search"),
+                keymap.hint(Action::NextMatch, "Older Match"), keymap.hint(Action::PreviousMatch, "Newer Match"), keymap.hint(Action::FilterView, "Filter View"),
+                keymap.hint(Action::MessageList, "Message List"), keymap.hint(Action::Tiles, "Tiles"), keymap.hint(Action::Logs, "Logs"), keymap.hint(Action::Pause, "Pause"),
+                keymap.hint(Action::Quit, "Quit")
+            ]
+        };
+
+        format!(" {} |", hints.into_iter().flatten().join(" | "))
+    }
+
     fn draw_selector<B: Backend>(&mut self, frame: &mut Frame<B>, app: &mut App, grid: &Vec<Rect>) {
-        let index = self.selector_index;
         let mut count = 0;
         let selection_filter = app.selection_filter.clone();
 
-        let filtered_items: Vec<(&str, SelectedState, Uuid)> = app
+        let filtered_items: Vec<(&str, SelectedState, Uuid, Option<Color>)> = app
             .config
             .items
             .iter()
-            .filter(|item| {
-                let name: &str;
-                if item.alias.len() > 0 {
-                    name = item.alias.as_str();
-                }
-                else {
-                    name = item.exchange_name.as_str();
-                }
-
-                name.to_lowercase().contains(selection_filter.to_lowercase().as_str())
-            })
-            .map(|item| {
-                let name;
-                if item.alias.len() > 0 {
-                    name = item.alias.as_str();
-                }
-                else {
-                    name = item.exchange_name.as_str();
-                }
-
-                (name, item.selected_state.clone(), item.id)
-            })
+            .filter(|item| item.display_name().to_lowercase().contains(selection_filter.to_lowercase().as_str()))
+            .map(|item| (item.display_name(), item.selected_state.clone(), item.id, item.display_color()))
             .sorted_by(|a, b| a.0.cmp(&b.0))
             .collect();
 
         self.selector_ids = filtered_items.iter().map(|x| x.2).collect();
 
+        //while composing a routing key, mark the subscribed items a publish with it would reach
+        let receivers = if app.active_window == Windows::RoutingTester { app.routing_tester.receivers(&app.config.items) } else { vec![] };
+
+        if let Some(pending_id) = self.pending_selection.take() {
+            if let Some(position) = self.selector_ids.iter().position(|x| *x == pending_id) {
+                self.selector_index = position;
+            }
+        }
+
+        //items can be deleted or filtered out from under the cursor
+        if self.selector_index >= self.selector_ids.len() {
+            self.selector_index = self.selector_ids.len().saturating_sub(1);
+        }
+        let index = self.selector_index;
+
+        let colorless = theme::colorless();
         let rows: Vec<Row> = filtered_items
             .iter()
             .map(|item| {
                 let row;
 
-                let name_style = match item.1 {
-                    SelectedState::Unselected => Style::default().fg(theme::FOREGROUND),
-                    SelectedState::PendingSubscription => Style::default().fg(theme::PENDING),
-                    SelectedState::Subscribed => Style::default().fg(theme::SELECTED)
+                //items with a colour, and every item without colours, show whether they are subscribed by weight instead
+                let item_style = match item.3 {
+                    Some(color) => Some(Style::default().fg(color)),
+                    None if colorless => Some(Style::default()),
+                    None => None
                 };
+                let mut name_style = match (&item.1, item_style) {
+                    (SelectedState::Unselected, None) => Style::default().fg(theme::current().foreground),
+                    (SelectedState::PendingSubscription, None) => Style::default().fg(theme::current().pending),
+                    (SelectedState::Subscribed, None) => Style::default().fg(theme::current().selected),
+                    (SelectedState::Unselected, Some(style)) => style,
+                    (SelectedState::PendingSubscription, Some(style)) => style.add_modifier(Modifier::DIM),
+                    (SelectedState::Subscribed, Some(style)) => style.add_modifier(Modifier::BOLD)
+                };
+                if receivers.contains(&item.2) {
+                    name_style = name_style.add_modifier(Modifier::BOLD | Modifier::REVERSED);
+                }
 
-                let name_cell = Cell::from(item.0).style(name_style);
+                let filtered_count = app.filtered_count(item.2);
+                let name = if filtered_count > 0 { format!("{} ({} filtered)", item.0, filtered_count) } else { item.0.to_string() };
+                let name_cell = Cell::from(name).style(name_style);
 
                 let indicator_cell;
                 if count == index {
-                    indicator_cell = Cell::from(">").style(Style::default().fg(theme::INPUT));
+                    indicator_cell = Cell::from(">").style(Style::default().fg(theme::current().input));
                 }
                 else {
-                    indicator_cell = Cell::from(" ").style(Style::default().fg(theme::INPUT));
+                    indicator_cell = Cell::from(" ").style(Style::default().fg(theme::current().input));
                 }
 
                 row = Row::new(vec![indicator_cell, name_cell]);
@@ -215,7 +392,7 @@ impl Ui {
 
         let mut top_len = 0;
         let mut render_filter = false;
-        let filter_color = if app.active_window == Windows::SelectionFilter { theme::INPUT } else { theme::FOREGROUND };
+        let filter_color = if app.active_window == Windows::SelectionFilter { theme::current().input } else { theme::current().foreground };
         if app.selection_filter.len() > 0 || app.active_window == Windows::SelectionFilter {
             top_len = 3;
             render_filter = true;
@@ -240,8 +417,8 @@ impl Ui {
         }
 
         let table = Table::new(rows[upper_scroll..lower_scroll].to_vec())
-            .style(Style::default().fg(theme::FOREGROUND).bg(theme::BACKGROUND))
-            .block(Block::default().borders(Borders::ALL).title(Span::styled("Selector", Style::default().fg(theme::ACCENT).add_modifier(Modifier::BOLD))))
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled("Selector", Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
             .widths(&table_constraints)
             .column_spacing(1);
 
@@ -265,8 +442,10 @@ impl Ui {
     fn draw_messages<B: Backend>(&mut self, frame: &mut Frame<B>, app: &mut App, grid: &Vec<Rect>) {
         self.messages_window_height = (grid[1].height - 2) as i32;
 
-        if let Ok(read_value) = app.message_receiver.try_recv() {
-            let mut selected_item = &mut app.config.items.iter_mut().find(|x| x.id == read_value.id).unwrap();
+        //deliveries still queued for an item that was deleted are dropped
+        let items = &app.config.items;
+        if let Some(read_value) = app.message_receiver.try_iter().find(|x| items.iter().any(|item| item.id == x.id)) {
+            let selected_item = app.config.items.iter_mut().find(|x| x.id == read_value.id).unwrap();
             if selected_item.selected_state == SelectedState::PendingSubscription {
                 selected_item.selected_state = SelectedState::Subscribed;
             }
@@ -274,77 +453,130 @@ impl Ui {
             let exchange = app.config
                 .items
                 .iter()
-                .find(|x| x.exchange_name == read_value.exchange_name)
+                .find(|x| x.id == read_value.id)
                 .unwrap();
 
-            let name: &str;
-            if exchange.alias.len() > 0 {
-                name = exchange.alias.as_str();
-            }
-            else {
-                name = exchange.exchange_name.as_str();
+            let name = exchange.display_name();
+            let projection = read_value.projection.as_deref();
+            let log_format = read_value.log_format;
+            let record = MessageRecord::new(&read_value, name, exchange.pretty, projection);
+            let fields = record.fields.clone();
+            self.message_count += 1;
+            self.messages.push(record);
+
+            let header_line = "-".repeat(grid[1].width as usize);
+            self.add_line(header_line.as_str(), LineKind::Text, app);
+
+            let time_stamp = read_value.timestamp.format("%Y/%m/%d %I:%M:%S%.6f %p").to_string();
+            let time_stamp_name = format!("{} | {}", name, time_stamp);
+            self.add_line(time_stamp_name.as_str(), LineKind::Header(exchange.display_color()), app);
+
+            //bodies that are not JSON or not text are shown as text or a hex dump instead
+            let view_mode = ViewMode::detect(&read_value.body, read_value.decoded.as_ref(), read_value.property("content_type"), exchange.pretty);
+            let json = payload::json_value(&read_value.body, read_value.decoded.as_ref());
+            let body_lines = payload::render(&read_value.body, read_value.decoded.as_ref(), view_mode);
+
+            //the whole body stays in the Message List
+            match &fields {
+                Some(res) => self.add_line(projection::summary(res).as_str(), LineKind::Text, app),
+                None => {
+                    let kind = if view_mode == ViewMode::Json && json.is_some() { LineKind::Json } else { LineKind::Text };
+                    for line in body_lines.iter() {
+                        self.add_line(line.as_str(), kind, app);
+                    }
+                }
             }
 
-            if exchange.pretty {
-                let json_value: Value = serde_json::from_str(&read_value.value).unwrap();
-                let pretty_json = serde_json::to_string_pretty(&json_value).unwrap();
-
-                let header_line = "-".repeat(grid[1].width as usize);
-                self.add_line(header_line.as_str(), app);
-
-                let time_stamp = read_value.timestamp.format("%Y/%m/%d %I:%M:%S%.6f %p").to_string();
-                let time_stamp_name = format!("{} | {}", name.to_string(), time_stamp);
-                self.add_line(time_stamp_name.as_str(), app);
+            if log_format == LogFormat::Text {
                 Self::add_log(&mut app.file_logger, exchange.log_file.as_str(), time_stamp_name.as_str());
-
-                for line in pretty_json.split("\n") {
-                    self.add_line(line, app);
-                    Self::add_log(&mut app.file_logger, exchange.log_file.as_str(), line);
+                for line in body_lines.iter() {
+                    Self::add_log(&mut app.file_logger, exchange.log_file.as_str(), line.as_str());
                 }
             }
-            else {
-                let header_line = "-".repeat(grid[1].width as usize).to_string();
-                self.add_line(header_line.as_str(), app);
-
-                let time_stamp = read_value.timestamp.format("%Y/%m/%d %I:%M:%S%.6f %p").to_string();
-                let time_stamp_name = format!("{} | {}", name.to_string(), time_stamp);
-                self.add_line(time_stamp_name.as_str(), app);
-                Self::add_log(&mut app.file_logger, exchange.log_file.as_str(), time_stamp_name.as_str());
+            else if !exchange.log_file.is_empty() {
+                let mut header = vec!["timestamp".to_string(), "exchange".to_string(), "routing_key".to_string()];
+                let mut row = vec![read_value.timestamp.to_rfc3339(), name.to_string(), read_value.routing_key.clone()];
+
+                match &projection {
+                    Some(res) => {
+                        header.extend(res.names());
+                        //bodies that are not JSON get empty cells
+                        match &fields {
+                            Some(values) => row.extend(values.iter().map(|x| x.1.clone().unwrap_or_default())),
+                            None => row.extend(res.names().iter().map(|_| "".to_string()))
+                        }
+                    }
+                    None => {
+                        header.push("body".to_string());
+                        row.push(json.map(|x| x.to_string()).unwrap_or_else(|| payload::text(&read_value.body)));
+                    }
+                }
 
-                let message_line = read_value.value.as_str();
-                self.add_line(message_line, app);
-                Self::add_log(&mut app.file_logger, exchange.log_file.as_str(), message_line);
+                app.file_logger.set_header(exchange.log_file.as_str(), log_format.row(&header));
+                Self::add_log(&mut app.file_logger, exchange.log_file.as_str(), log_format.row(&row).as_str());
             }
         }
 
+        if let Some(column_view) = self.column_view.as_mut() {
+            column_view.draw(frame, grid[1], self.messages.records());
+            return;
+        }
+
+        if self.show_message_list {
+            self.messages.draw(frame, grid[1], &self.search);
+            return;
+        }
+
+        if self.show_tiles {
+            let tiles: Vec<MessageTile> = app.config.items
+                .iter()
+                .filter(|x| x.selected_state != SelectedState::Unselected)
+                .sorted_by(|a, b| a.display_name().cmp(b.display_name()))
+                .map(|x| MessageTile { item_id: x.id, name: x.display_name().to_string(), color: x.display_color() })
+                .collect();
+            message_tiles::draw(frame, grid[1], &tiles, self.messages.records(), &self.search);
+            return;
+        }
+
         let mut spans: Vec<Spans> = vec![];
+        let window_height = self.messages_window_height as usize;
 
         match app.mode {
             Mode::Normal => {
-                for message_line in self.window_lines.iter() {
-                    spans.push(Spans::from(message_line.to_string()));
+                let visible = self.visible_lines();
+                for i in visible[visible.len().saturating_sub(window_height)..].iter() {
+                    spans.push(self.styled_line(&self.stream[*i], false));
                 }
             }
             Mode::Scroll => {
-                if self.line_buffer.len() > self.messages_window_height as usize {
-                    self.messages_upper_scroll = (self.line_buffer.len() - self.messages_window_height as usize) - self.scroll_position as usize;
-                    self.messages_lower_scroll = self.line_buffer.len() - self.scroll_position as usize;
-
-                    for message_line in self.line_buffer[self.messages_upper_scroll..self.messages_lower_scroll].iter() {
-                        spans.push(Spans::from(message_line.to_string()));
-                    }
+                let visible = self.visible_lines();
+                if visible.len() > window_height {
+                    //the filter view can leave fewer lines than were scrolled past
+                    self.scroll_position = self.scroll_position.clamp(0, (visible.len() - window_height) as i32);
+                    self.messages_upper_scroll = (visible.len() - window_height) - self.scroll_position as usize;
+                    self.messages_lower_scroll = visible.len() - self.scroll_position as usize;
                 }
                 else {
-                    for message_line in self.line_buffer.iter() {
-                        spans.push(Spans::from(message_line.to_string()));
-                    }
+                    self.messages_upper_scroll = 0;
+                    self.messages_lower_scroll = visible.len();
+                }
+
+                for i in visible[self.messages_upper_scroll..self.messages_lower_scroll].iter() {
+                    spans.push(self.styled_line(&self.stream[*i], self.search_line == Some(*i)));
                 }
             }
         }
 
-        let paragraph = Paragraph::new(spans.clone())
-            .style(Style::default().bg(theme::BACKGROUND).fg(theme::FOREGROUND))
-            .block(Block::default().borders(Borders::ALL).title(Span::styled("Messages", Style::default().fg(theme::ACCENT).add_modifier(Modifier::BOLD))))
+        let title = if self.search.filter_view && self.search.is_active() {
+            format!("Messages matching {}", self.search.query)
+        }
+        else {
+            "Messages".to_string()
+        };
+
+        let paragraph = Paragraph::new(spans)
+            .style(Style::default().bg(theme::current().background).fg(theme::current().foreground))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled(title, Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
             .alignment(Alignment::Left);
 
         frame.render_widget(paragraph, grid[1]);
@@ -356,23 +588,37 @@ impl Ui {
         }
     }
 
-    fn add_line(&mut self, line: &str, app: &App) {
+    fn add_line(&mut self, line: &str, kind: LineKind, app: &App) {
         if app.mode == Mode::Normal {
-            self.window_lines.push(line.to_string());
-
-            //remove what would render past the window
-            let window_overflow = (self.window_lines.len() as i32) - self.messages_window_height;
-            if window_overflow > 0 {
-                for _ in 0..window_overflow {
-                    self.window_lines.remove(0);
-                }
+            if self.stream.len() == self.stream_size {
+                self.stream.pop_front();
+                self.search_line = self.search_line.and_then(|x| x.checked_sub(1));
             }
+            self.stream.push_back(StreamLine { text: line.to_string(), kind, message_id: self.message_count });
+        }
+    }
 
-            if self.line_buffer.len() == self.line_buffer_size {
-                self.line_buffer.remove(0);
-            }
-            self.line_buffer.push(line.to_string());
+    /// A line of the message stream, coloured for its kind, with search matches marked.
+    fn styled_line(&self, line: &StreamLine, current: bool) -> Spans<'static> {
+        line.kind.spans(line.text.as_str(), &self.search, current)
+    }
+
+    /// The lines of `stream` that are shown: all of them, or in the filter view those of the
+    /// messages with a search match.
+    fn visible_lines(&self) -> Vec<usize> {
+        if !self.search.filter_view || !self.search.is_active() {
+            return (0..self.stream.len()).collect();
         }
+
+        let matching: HashSet<usize> = self.stream
+            .iter()
+            .filter(|x| self.search.is_match(x.text.as_str()))
+            .map(|x| x.message_id)
+            .collect();
+
+        (0..self.stream.len())
+            .filter(|i| matching.contains(&self.stream[*i].message_id))
+            .collect()
     }
 
     fn draw_logs<B: Backend>(&mut self, frame: &mut Frame<B>, app: &mut App, grid: &Vec<Rect>) {
@@ -386,8 +632,8 @@ impl Ui {
             .collect();
 
         let logs_list = List::new(items)
-            .style(Style::default().bg(theme::BACKGROUND).fg(theme::FOREGROUND))
-            .block(Block::default().borders(Borders::ALL).title(Span::styled("Logs", Style::default().fg(theme::ACCENT).add_modifier(Modifier::BOLD))))
+            .style(Style::default().bg(theme::current().background).fg(theme::current().foreground))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled("Logs", Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
             .start_corner(Corner::BottomLeft);
 
         frame.render_widget(logs_list, grid[2]);
@@ -403,10 +649,10 @@ impl Ui {
             .map(|item| {
                 let row;
                 if count == option_windows_index {
-                    row = Row::new(vec![item.0.to_string(), item.1.to_string()]).style(Style::default().fg(theme::INPUT))
+                    row = Row::new(vec![item.0.to_string(), item.1.to_string()]).style(Style::default().fg(theme::current().input))
                 }
                 else {
-                    row = Row::new(vec![item.0.to_string(), item.1.to_string()]).style(Style::default().fg(theme::FOREGROUND))
+                    row = Row::new(vec![item.0.to_string(), item.1.to_string()]).style(Style::default().fg(theme::current().foreground))
                 }
                 count += 1;
                 return row;
@@ -416,19 +662,23 @@ impl Ui {
         let width_constraints = [Constraint::Percentage(50), Constraint::Percentage(50)];
 
         let table = Table::new(rows)
-            .style(Style::default().fg(theme::FOREGROUND))
-            .block(Block::default().style(Style::default().fg(theme::ACCENT)).borders(Borders::ALL).title("Options"))
+            .style(Style::default().fg(theme::current().foreground))
+            .block(Block::default().style(Style::default().fg(theme::current().accent)).borders(Borders::ALL).title("Options"))
             .widths(&width_constraints)
             .column_spacing(1);
 
-        let area = Self::center_rect_absolute(55, 9, frame.size());
+        //on a short terminal the table scrolls to keep the selected option in view
+        let mut state = TableState::default();
+        state.select(Some(option_windows_index.max(0) as usize));
+
+        let area = Self::center_rect_absolute(55, self.options_count as u16 + 2, frame.size());
         frame.render_widget(Clear, area);
-        frame.render_widget(table, area);
+        frame.render_stateful_widget(table, area, &mut state);
     }
 
     fn draw_string_input<B: Backend>(&self, frame: &mut Frame<B>) {
         let input = Paragraph::new(self.string_input.as_ref())
-            .style(Style::default().fg(theme::INPUT))
+            .style(Style::default().fg(theme::current().input))
             .block(Block::default().borders(Borders::ALL).title("Input"));
 
         let area = Self::center_input(65, frame.size());
@@ -436,6 +686,37 @@ impl Ui {
         frame.render_widget(input, area);
     }
 
+    fn draw_operation_input<B: Backend>(&self, frame: &mut Frame<B>, app: &App) {
+        let input = Paragraph::new(app.operation_input.as_ref())
+            .style(Style::default().fg(theme::current().input))
+            .block(Block::default().borders(Borders::ALL).title("Operation, e.g. queue purge NAME | queue delete NAME --if-empty | queue bind QUEUE EXCHANGE KEY"));
+
+        let area = Self::center_input(80, frame.size());
+        let area = Rect { y: area.y.saturating_sub(4), ..area };
+        frame.render_widget(Clear, area);
+        frame.render_widget(input, area);
+    }
+
+    fn draw_search_input<B: Backend>(&self, frame: &mut Frame<B>) {
+        let input = Paragraph::new(self.search.query.as_ref())
+            .style(Style::default().fg(theme::current().input))
+            .block(Block::default().borders(Borders::ALL).title(self.search.title()));
+
+        let area = Self::center_input(65, frame.size());
+        frame.render_widget(Clear, area);
+        frame.render_widget(input, area);
+    }
+
+    fn draw_confirm<B: Backend>(&self, frame: &mut Frame<B>) {
+        let confirm = Paragraph::new(self.confirm_message.as_ref())
+            .style(Style::default().fg(theme::current().input))
+            .block(Block::default().borders(Borders::ALL).title("Confirm"));
+
+        let area = Self::center_input(50, frame.size());
+        frame.render_widget(Clear, area);
+        frame.render_widget(confirm, area);
+    }
+
     fn draw_multi_select_input<B: Backend>(&mut self, frame: &mut Frame<B>) {
         self.multi_select_list_items.clear();
         for value in ExchangeTypeSer::iterator() {
@@ -447,17 +728,17 @@ impl Ui {
         for value in self.multi_select_list_items.iter() {
             let item;
             if count == self.multi_select_index {
-                item = ListItem::new(value.clone()).style(Style::default().fg(theme::INPUT));
+                item = ListItem::new(value.clone()).style(Style::default().fg(theme::current().input));
             }
             else {
-                item = ListItem::new(value.clone()).style(Style::default().fg(theme::FOREGROUND));
+                item = ListItem::new(value.clone()).style(Style::default().fg(theme::current().foreground));
             }
             list_items.push(item);
             count += 1;
         }
 
         let input = List::new(list_items)
-            .style(Style::default().fg(theme::ACCENT))
+            .style(Style::default().fg(theme::current().accent))
             .block(Block::default().borders(Borders::ALL).title("Select"));
 
         let area = Self::centered_rect(12, 12, frame.size());
@@ -501,7 +782,7 @@ impl Ui {
     }
 
     pub fn main_index_down(&mut self) {
-        if self.selector_index < self.selector_length - 1 {
+        if self.selector_index + 1 < self.selector_length {
             self.selector_index += 1;
         }
     }
@@ -562,7 +843,22 @@ impl Ui {
     }
 
     pub fn get_selected_item_id(&mut self) -> Uuid {
-        self.selector_ids[self.selector_index]
+        self.selector_ids.get(self.selector_index).copied().unwrap_or_else(Uuid::nil)
+    }
+
+    /// Moves the selector to `id` on the next draw, once it is in the sorted list.
+    pub fn select_item(&mut self, id: Uuid) {
+        self.pending_selection = Some(id);
+    }
+
+    pub fn show_confirm_popup(&mut self, message: String) {
+        self.show_confirm = true;
+        self.confirm_message = message;
+    }
+
+    pub fn hide_confirm_popup(&mut self) {
+        self.show_confirm = false;
+        self.confirm_message = "".to_string();
     }
 
     pub fn show_string_input(&mut self, edit_string: String) {
@@ -602,28 +898,72 @@ impl Ui {
     }
 
     pub fn scroll_up(&mut self) {
+        if let Some(column_view) = self.column_view.as_mut() {
+            column_view.select_up(1);
+            return;
+        }
+        if self.show_message_list && self.messages.detail_focus {
+            self.messages.cursor_up();
+            return;
+        }
+        if self.show_message_list {
+            self.messages.select_up(&self.search);
+            return;
+        }
+
         if self.messages_upper_scroll >= 1 {
             self.scroll_position += 1;
         }
     }
 
     pub fn scroll_up_page(&mut self) {
+        if let Some(column_view) = self.column_view.as_mut() {
+            column_view.select_up(self.messages_window_height as usize);
+            return;
+        }
+        if self.show_message_list {
+            self.messages.detail_scroll_up(self.messages_window_height as usize / 2);
+            return;
+        }
+
         if self.messages_upper_scroll >= self.messages_window_height as usize {
             self.scroll_position += self.messages_window_height;
         }
         else {
-            self.scroll_position = self.line_buffer.len() as i32 - self.messages_window_height;
+            self.scroll_position = (self.visible_lines().len() as i32 - self.messages_window_height).max(0);
         }
     }
 
     pub fn scroll_down(&mut self) {
-        if self.messages_lower_scroll < self.line_buffer.len() {
+        if let Some(column_view) = self.column_view.as_mut() {
+            column_view.select_down(1, self.messages.records());
+            return;
+        }
+        if self.show_message_list && self.messages.detail_focus {
+            self.messages.cursor_down();
+            return;
+        }
+        if self.show_message_list {
+            self.messages.select_down(&self.search);
+            return;
+        }
+
+        if self.messages_lower_scroll < self.visible_lines().len() {
             self.scroll_position -= 1;
         }
     }
 
     pub fn scroll_down_page(&mut self) {
-        if self.messages_lower_scroll < self.line_buffer.len() - self.messages_window_height as usize {
+        if let Some(column_view) = self.column_view.as_mut() {
+            column_view.select_down(self.messages_window_height as usize, self.messages.records());
+            return;
+        }
+        if self.show_message_list {
+            self.messages.detail_scroll_down(self.messages_window_height as usize / 2);
+            return;
+        }
+
+        if self.messages_lower_scroll < self.visible_lines().len().saturating_sub(self.messages_window_height as usize) {
             self.scroll_position -= self.messages_window_height;
         }
         else {
@@ -631,6 +971,112 @@ impl Ui {
         }
     }
 
+    /// Remembers where the view was, for `cancel_search`.
+    pub fn start_search(&mut self) {
+        self.search_origin = self.scroll_position;
+    }
+
+    /// Searches again from where the view was when the search started, for every change to the query.
+    pub fn update_search(&mut self) {
+        self.scroll_position = self.search_origin;
+        self.search_line = None;
+        self.messages.follow();
+        self.search_jump(true);
+    }
+
+    pub fn cancel_search(&mut self) {
+        self.search.clear();
+        self.search_line = None;
+        self.scroll_position = self.search_origin;
+        self.messages.hold();
+    }
+
+    /// Switches between the stream of message lines and the list of messages with the selected
+    /// one in full. The list selection can only move while paused.
+    pub fn toggle_message_list(&mut self, paused: bool) {
+        self.show_message_list = !self.show_message_list;
+        if paused {
+            self.messages.hold();
+        }
+        else {
+            self.messages.follow();
+        }
+    }
+
+    /// Shows a pane for each subscribed item instead of the message stream, or goes back to it.
+    pub fn toggle_tiles(&mut self) {
+        self.show_tiles = !self.show_tiles;
+        if self.show_tiles {
+            self.show_message_list = false;
+        }
+    }
+
+    /// Shows `item`'s messages as columns, or goes back to the other views when they are shown.
+    pub fn toggle_column_view(&mut self, item: &ExchangeOptions, paused: bool) -> Result<()> {
+        if self.column_view.as_ref().map(|x| x.item_id == item.id).unwrap_or(false) {
+            self.column_view = None;
+            return Ok(());
+        }
+
+        let mut column_view = ColumnView::new(item)?;
+        if paused {
+            column_view.hold(self.messages.records());
+        }
+        self.column_view = Some(column_view);
+
+        Ok(())
+    }
+
+    pub fn toggle_filter_view(&mut self) {
+        self.search.filter_view = !self.search.filter_view;
+        self.search_line = None;
+        self.scroll_position = 0;
+    }
+
+    /// Moves to the next match above (`older`) or below the last one, starting from the bottom
+    /// of the view, wrapping around at the ends. False when nothing matches.
+    pub fn search_jump(&mut self, older: bool) -> bool {
+        if self.show_message_list {
+            return self.messages.search_jump(&self.search, older);
+        }
+
+        let visible = self.visible_lines();
+        let matches: Vec<usize> = (0..visible.len())
+            .filter(|x| self.search.is_match(self.stream[visible[*x]].text.as_str()))
+            .collect();
+
+        if matches.is_empty() {
+            self.search_line = None;
+            return false;
+        }
+
+        let window_height = self.messages_window_height.max(1) as usize;
+        let view_bottom = visible.len().saturating_sub(self.scroll_position.max(0) as usize);
+        let current = self.search_line.and_then(|line| visible.iter().position(|x| *x == line));
+
+        let next = if older {
+            let before = current.unwrap_or(view_bottom);
+            matches.iter().rev().find(|x| **x < before).or(matches.last())
+        }
+        else {
+            let after = current.map(|x| x as i64).unwrap_or(view_bottom as i64 - window_height as i64 - 1);
+            matches.iter().find(|x| **x as i64 > after).or(matches.first())
+        };
+        let position = *next.unwrap();
+        self.search_line = Some(visible[position]);
+
+        //centre the match unless it is already in view
+        if visible.len() > window_height {
+            let lower = visible.len() - self.scroll_position.max(0) as usize;
+            if position < lower.saturating_sub(window_height) || position >= lower {
+                let lower = (position + 1 + window_height / 2).clamp(window_height, visible.len());
+                self.scroll_position = (visible.len() - lower) as i32;
+            }
+        }
+
+        true
+    }
+
     fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         let popup_layout = Layout::default()
             .direction(Direction::Vertical)
@@ -657,7 +1103,11 @@ impl Ui {
             .split(popup_layout[1])[1]
     }
 
+    /// At most the size of `r`.
     fn center_rect_absolute(absolute_x: u16, absolute_y: u16, r: Rect) -> Rect {
+        let absolute_x = absolute_x.min(r.width);
+        let absolute_y = absolute_y.min(r.height);
+
         let popup_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
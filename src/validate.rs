@@ -0,0 +1,582 @@
+use std::fmt;
+use std::path::Path;
+use regex::Regex;
+use serde_json::{Map, Value};
+use crate::amqp::QUEUE_NAME_PLACEHOLDERS;
+use crate::config::ConfigFormat;
+use crate::compression::Compression;
+use crate::decoders::Decoder;
+use crate::file_logger::LogFormat;
+use crate::keymap::Keymap;
+use crate::message_filter::MessageFilter;
+use crate::models::enums::ExchangeTypeSer;
+use crate::projection::Projection;
+use crate::secrets;
+use crate::theme;
+
+pub struct ConfigError {
+    pub line: usize,
+    pub column: usize,
+    pub field: String,
+    pub message: String
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        }
+        else {
+            write!(f, "{}:{}: {}: {}", self.line, self.column, self.field, self.message)
+        }
+    }
+}
+
+pub fn parse_document(source: &str, format: ConfigFormat) -> Result<Map<String, Value>, ConfigError> {
+    let syntax_error = |line: usize, column: usize, message: String| ConfigError { line, column, field: "".to_string(), message };
+
+    match format {
+        ConfigFormat::Json => serde_json::from_str(source)
+            .map_err(|e| syntax_error(e.line(), e.column(), e.to_string())),
+        ConfigFormat::Toml => {
+            let value: toml::Table = toml::from_str(source).map_err(|e| {
+                let (line, column) = e.span().map(|span| line_column(source, span.start)).unwrap_or((1, 1));
+                syntax_error(line, column, e.message().trim().replace('\n', ": "))
+            })?;
+
+            match serde_json::to_value(value) {
+                Ok(Value::Object(res)) => Ok(res),
+                _ => Err(syntax_error(1, 1, "Cannot convert TOML document".to_string()))
+            }
+        }
+        ConfigFormat::Yaml => serde_yaml::from_str(source).map_err(|e| {
+            let (line, column) = e.location().map(|res| (res.line(), res.column())).unwrap_or((1, 1));
+            syntax_error(line, column, e.to_string())
+        }),
+    }
+}
+
+/// Checks the whole file and returns every problem found instead of stopping at the first one.
+pub fn validate(source: &str, format: ConfigFormat) -> Vec<ConfigError> {
+    let document = match parse_document(source, format) {
+        Ok(res) => res,
+        Err(e) => return vec![e]
+    };
+
+    let mut validator = Validator {
+        locator: Locator::new(source, format),
+        errors: vec![]
+    };
+
+    validator.validate_root(&document);
+
+    validator.errors
+}
+
+struct Validator<'a> {
+    locator: Locator<'a>,
+    errors: Vec<ConfigError>
+}
+
+impl<'a> Validator<'a> {
+    fn validate_root(&mut self, document: &Map<String, Value>) {
+        self.required_string(document, None, "host");
+        self.required_string(document, None, "username");
+
+        match document.get("port") {
+            None => self.error(None, "port", "is required"),
+            Some(value) => match value.as_u64() {
+                Some(port) if (1..=65535).contains(&port) => {}
+                _ => self.error(None, "port", format!("must be a number between 1 and 65535, found {}", value).as_str())
+            }
+        }
+
+        let password_fields = ["password", "password_env", "password_file", "password_command"];
+        for field in password_fields.iter() {
+            self.optional_string(document, None, field);
+        }
+
+        let password_count = password_fields.iter().filter(|x| matches!(document.get(**x), Some(Value::String(_)))).count();
+        if password_count > 1 {
+            self.error(None, "password", "only one of password, password_env, password_file or password_command may be set");
+        }
+
+        self.existing_file(document, None, "password_file");
+        self.existing_file(document, None, "pfx_path");
+        self.existing_file(document, None, "pem_file");
+
+        for field in ["vhost", "domain", "management_url"].iter() {
+            self.optional_string(document, None, field);
+        }
+
+        let has_pfx = matches!(document.get("pfx_path"), Some(Value::String(_)));
+        let has_pem = matches!(document.get("pem_file"), Some(Value::String(_)));
+        if has_pfx != has_pem {
+            self.error(None, if has_pfx { "pfx_path" } else { "pem_file" }, "pfx_path and pem_file must be set together for a secure connection");
+        }
+        if has_pfx && has_pem && !matches!(document.get("domain"), Some(Value::String(_))) {
+            self.error(None, "domain", "is required for a secure connection");
+        }
+
+        if let Some(value) = document.get("protocol") {
+            match value.as_str() {
+                None if value.is_null() => {}
+                Some("amqp") | Some("amqps") => {}
+                _ => self.error(None, "protocol", format!("must be \"amqp\" or \"amqps\", found {}", value).as_str())
+            }
+        }
+
+        self.optional_bool(document, None, "save_on_exit");
+        self.optional_bool(document, None, "server_named_queues");
+
+        self.validate_themes(document);
+
+        match document.get("keys") {
+            None | Some(Value::Null) => {}
+            Some(Value::Object(keys)) => {
+                if let Err(e) = Keymap::load(Some(keys)) {
+                    self.error(None, "keys", e.to_string().as_str());
+                }
+            }
+            Some(other) => self.error(None, "keys", format!("must be an object of actions and their keys, found {}", other).as_str())
+        }
+
+        if let Some(value) = document.get("queue_name_template") {
+            match value {
+                Value::Null => {}
+                Value::String(template) => {
+                    if !template.contains("{item}") {
+                        self.error(None, "queue_name_template", "must contain {item}, every subscription needs its own queue");
+                    }
+
+                    let unknown: Vec<&str> = Regex::new(r"\{[^{}]*\}")
+                        .unwrap()
+                        .find_iter(template)
+                        .map(|x| x.as_str())
+                        .filter(|x| !QUEUE_NAME_PLACEHOLDERS.contains(x))
+                        .collect();
+                    if !unknown.is_empty() {
+                        self.error(None, "queue_name_template", format!("unknown placeholder {}, expected {}", unknown.join(", "), QUEUE_NAME_PLACEHOLDERS.join(" ")).as_str());
+                    }
+                }
+                _ => self.error(None, "queue_name_template", format!("must be a string, found {}", value).as_str())
+            }
+        }
+
+        match document.get("items") {
+            None => self.error(None, "items", "is required"),
+            Some(Value::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    match item {
+                        Value::Object(item) => self.validate_item(item, i),
+                        _ => self.error(Some(i), "", "must be an object")
+                    }
+                }
+            }
+            Some(_) => self.error(None, "items", "must be a list")
+        }
+    }
+
+    fn validate_item(&mut self, item: &Map<String, Value>, index: usize) {
+        self.required_string(item, Some(index), "exchange_name");
+
+        match item.get("exchange_type") {
+            None => self.error(Some(index), "exchange_type", "is required"),
+            Some(value) => {
+                let is_known = value
+                    .as_str()
+                    .map(|name| ExchangeTypeSer::iterator().any(|x| format!("{:?}", x) == name))
+                    .unwrap_or(false);
+
+                if !is_known {
+                    let names = ExchangeTypeSer::iterator().map(|x| format!("{:?}", x)).collect::<Vec<String>>().join(", ");
+                    self.error(Some(index), "exchange_type", format!("unknown exchange type {}, expected one of {}", value, names).as_str());
+                }
+            }
+        }
+
+        for field in ["queue_routing_key", "alias", "log_file", "publish_file", "filter", "decoder", "schema_file", "content_encoding", "publish_encoding", "log_format", "color"].iter() {
+            self.optional_string(item, Some(index), field);
+        }
+
+        if let Some(Value::String(filter)) = item.get("filter") {
+            if let Err(e) = MessageFilter::parse(filter) {
+                self.error(Some(index), "filter", e.to_string().as_str());
+            }
+        }
+
+        self.optional_bool(item, Some(index), "pretty");
+        self.existing_file(item, Some(index), "publish_file");
+        self.existing_file(item, Some(index), "schema_file");
+
+        let decoder = item.get("decoder").and_then(|x| x.as_str()).unwrap_or_default();
+        let schema_file = item.get("schema_file").and_then(|x| x.as_str()).unwrap_or_default();
+        if schema_file.is_empty() || Path::new(schema_file).is_file() {
+            if let Err(e) = Decoder::from_options(decoder, schema_file) {
+                let message = e.to_string();
+                let field = if decoder.is_empty() || message.starts_with("Reading") || message.starts_with("Parsing") { "schema_file" } else { "decoder" };
+                self.error(Some(index), field, format!("{:#}", e).as_str());
+            }
+        }
+
+        for field in ["content_encoding", "publish_encoding"].iter() {
+            if let Some(Err(e)) = item.get(*field).and_then(|x| x.as_str()).map(Compression::parse) {
+                self.error(Some(index), field, e.to_string().as_str());
+            }
+        }
+
+        if let Some(Err(e)) = item.get("log_format").and_then(|x| x.as_str()).map(LogFormat::parse) {
+            self.error(Some(index), "log_format", e.to_string().as_str());
+        }
+
+        if let Some(Err(e)) = item.get("color").and_then(|x| x.as_str()).filter(|x| !x.trim().is_empty()).map(theme::parse_color) {
+            self.error(Some(index), "color", e.to_string().as_str());
+        }
+
+        match item.get("fields") {
+            None | Some(Value::Null) => {}
+            Some(Value::Array(fields)) => {
+                for field in fields {
+                    match field {
+                        Value::String(res) => {
+                            if let Err(e) = Projection::parse(std::slice::from_ref(res)) {
+                                self.error(Some(index), "fields", e.to_string().as_str());
+                            }
+                        }
+                        other => self.error(Some(index), "fields", format!("must be a list of strings, found {}", other).as_str())
+                    }
+                }
+            }
+            Some(value) => self.error(Some(index), "fields", format!("must be a list of strings, found {}", value).as_str())
+        }
+    }
+
+    fn validate_themes(&mut self, document: &Map<String, Value>) {
+        self.optional_string(document, None, "theme");
+        self.optional_string(document, None, "theme_file");
+        self.existing_file(document, None, "theme_file");
+
+        let themes = match document.get("themes") {
+            None | Some(Value::Null) => None,
+            Some(Value::Object(res)) => Some(res),
+            Some(other) => {
+                self.error(None, "themes", format!("must be an object of named themes, found {}", other).as_str());
+                return;
+            }
+        };
+
+        //a missing file is already reported
+        let theme_file = document.get("theme_file").and_then(|x| x.as_str());
+        if theme_file.map(|x| !Path::new(x).is_file()).unwrap_or(false) {
+            return;
+        }
+
+        match theme::Themes::load(theme_file, themes) {
+            Ok(loaded) => {
+                if let Some(name) = document.get("theme").and_then(|x| x.as_str()) {
+                    if !loaded.names().contains(&name) {
+                        self.error(None, "theme", format!("unknown theme {}, expected one of {}", name, loaded.names().join(", ")).as_str());
+                    }
+                }
+            }
+            Err(e) => {
+                let message = e.to_string();
+                let field = if message.starts_with("Theme file") || message.contains("theme file") { "theme_file" } else { "themes" };
+                self.error(None, field, message.as_str());
+            }
+        }
+    }
+
+    fn required_string(&mut self, map: &Map<String, Value>, item: Option<usize>, field: &str) {
+        match map.get(field) {
+            None => self.error(item, field, "is required"),
+            Some(Value::String(res)) if !res.is_empty() => {}
+            Some(Value::String(_)) => self.error(item, field, "must not be empty"),
+            Some(value) => self.error(item, field, format!("must be a string, found {}", value).as_str())
+        }
+    }
+
+    fn optional_string(&mut self, map: &Map<String, Value>, item: Option<usize>, field: &str) {
+        match map.get(field) {
+            None | Some(Value::Null) | Some(Value::String(_)) => {}
+            Some(value) => self.error(item, field, format!("must be a string, found {}", value).as_str())
+        }
+    }
+
+    fn optional_bool(&mut self, map: &Map<String, Value>, item: Option<usize>, field: &str) {
+        match map.get(field) {
+            None | Some(Value::Null) | Some(Value::Bool(_)) => {}
+            Some(value) => self.error(item, field, format!("must be true or false, found {}", value).as_str())
+        }
+    }
+
+    fn existing_file(&mut self, map: &Map<String, Value>, item: Option<usize>, field: &str) {
+        if let Some(Value::String(path)) = map.get(field) {
+            //only the password file is read with ~ expanded
+            let on_disk = if field == "password_file" { secrets::expand_home(path) } else { Path::new(path).to_path_buf() };
+            if !path.is_empty() && !on_disk.is_file() {
+                self.error(item, field, format!("file not found: {}", path).as_str());
+            }
+        }
+    }
+
+    fn error(&mut self, item: Option<usize>, field: &str, message: &str) {
+        let (line, column) = self.locator.locate(item, field);
+
+        let field = match item {
+            None => field.to_string(),
+            Some(index) if field.is_empty() => format!("items[{}]", index),
+            Some(index) => format!("items[{}].{}", index, field)
+        };
+
+        self.errors.push(ConfigError { line, column, field, message: message.to_string() });
+    }
+}
+
+/// Finds where a field is written in the original text. The parsers used for the three formats
+/// do not keep positions, so this scans the source the same way for each of them.
+struct Locator<'a> {
+    source: &'a str,
+    format: ConfigFormat,
+    item_offsets: Vec<usize>
+}
+
+impl<'a> Locator<'a> {
+    fn new(source: &'a str, format: ConfigFormat) -> Self {
+        let item_offsets = match format {
+            ConfigFormat::Json => json_item_offsets(source),
+            ConfigFormat::Toml => Regex::new(r"(?m)^[ \t]*\[\[[ \t]*items[ \t]*\]\]")
+                .unwrap()
+                .find_iter(source)
+                .map(|x| x.start())
+                .collect(),
+            ConfigFormat::Yaml => yaml_item_offsets(source),
+        };
+
+        Locator { source, format, item_offsets }
+    }
+
+    fn locate(&self, item: Option<usize>, field: &str) -> (usize, usize) {
+        let (start, end) = match item {
+            None => (0, self.source.len()),
+            Some(index) => match self.item_offsets.get(index) {
+                None => (0, self.source.len()),
+                Some(start) => (*start, self.item_offsets.get(index + 1).copied().unwrap_or(self.source.len()))
+            }
+        };
+
+        if field.is_empty() {
+            return line_column(self.source, start);
+        }
+
+        let key = regex::escape(field);
+        let pattern = match self.format {
+            ConfigFormat::Json => format!(r#""{}"\s*:"#, key),
+            ConfigFormat::Toml => format!(r"(?m)^[ \t]*{}[ \t]*=", key),
+            ConfigFormat::Yaml => format!(r"(?m)^[ \t]*(- )?{}[ \t]*:", key),
+        };
+
+        let offset = Regex::new(pattern.as_str())
+            .unwrap()
+            .find(&self.source[start..end])
+            .map(|x| start + x.start() + x.as_str().find(field).unwrap_or(0))
+            .unwrap_or(start);
+
+        line_column(self.source, offset)
+    }
+}
+
+fn json_item_offsets(source: &str) -> Vec<usize> {
+    let mut offsets = vec![];
+
+    let items_start = match Regex::new(r#""items"\s*:\s*\["#).unwrap().find(source) {
+        Some(res) => res.end(),
+        None => return offsets
+    };
+
+    let mut depth = 1;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in source[items_start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => {
+                if depth == 1 {
+                    offsets.push(items_start + i);
+                }
+                depth += 1;
+            }
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    offsets
+}
+
+fn yaml_item_offsets(source: &str) -> Vec<usize> {
+    let mut offsets = vec![];
+    let mut in_items = false;
+    let mut dash_indent: Option<usize> = None;
+    let mut offset = 0;
+
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if !in_items {
+            in_items = indent == 0 && trimmed.starts_with("items:");
+        }
+        else if !trimmed.trim().is_empty() && !trimmed.starts_with('#') {
+            if trimmed.starts_with("- ") || trimmed.trim_end() == "-" {
+                if dash_indent.is_none() {
+                    dash_indent = Some(indent);
+                }
+                if dash_indent == Some(indent) {
+                    offsets.push(offset + indent);
+                }
+            }
+            else if indent == 0 {
+                break;
+            }
+        }
+
+        offset += line.len();
+    }
+
+    offsets
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(source: &str, format: ConfigFormat) -> Vec<String> {
+        validate(source, format).iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_errors() {
+        let source = r#"{
+  "host": "localhost",
+  "port": 5672,
+  "username": "guest",
+  "items": [
+    { "exchange_name": "orders", "exchange_type": "Topic", "queue_routing_key": "order.*" }
+  ]
+}"#;
+        assert!(errors(source, ConfigFormat::Json).is_empty());
+    }
+
+    #[test]
+    fn json_errors_point_at_the_field() {
+        let source = r#"{
+  "host": "localhost",
+  "port": 70000,
+  "username": "guest",
+  "items": [
+    { "exchange_name": "orders", "exchange_type": "Topic" },
+    {
+      "exchange_name": "events",
+      "exchange_type": "Nope"
+    }
+  ]
+}"#;
+        let errors = errors(source, ConfigFormat::Json);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("3:4: port: must be a number between 1 and 65535"), "{}", errors[0]);
+        assert!(errors[1].starts_with("9:8: items[1].exchange_type: unknown exchange type"), "{}", errors[1]);
+    }
+
+    #[test]
+    fn toml_errors_point_at_the_field() {
+        let source = r#"host = "localhost"
+port = 5672
+username = "guest"
+
+[[items]]
+exchange_name = "orders"
+exchange_type = "Topic"
+
+[[items]]
+exchange_name = "events"
+exchange_type = "Topic"
+pretty = "yes"
+"#;
+        assert_eq!(errors(source, ConfigFormat::Toml), vec!["12:1: items[1].pretty: must be true or false, found \"yes\""]);
+    }
+
+    #[test]
+    fn yaml_errors_point_at_the_field() {
+        let source = r#"host: localhost
+port: 5672
+items:
+  - exchange_name: orders
+    exchange_type: Topic
+  - exchange_name: events
+    exchange_type: Topic
+    filter: "/(/"
+"#;
+        let errors = errors(source, ConfigFormat::Yaml);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("1:1: username: is required"), "{}", errors[0]);
+        assert!(errors[1].starts_with("8:5: items[1].filter:"), "{}", errors[1]);
+    }
+
+    #[test]
+    fn missing_item_field_points_at_the_item() {
+        let source = "host: localhost\nport: 5672\nusername: guest\nitems:\n  - exchange_type: Topic\n";
+        assert_eq!(errors(source, ConfigFormat::Yaml), vec!["5:3: items[0].exchange_name: is required"]);
+    }
+
+    #[test]
+    fn queue_name_template_needs_the_item() {
+        let source = "host = \"localhost\"\nport = 5672\nusername = \"guest\"\nqueue_name_template = \"tap.{exchange}.{who}\"\nitems = []\n";
+        let errors = errors(source, ConfigFormat::Toml);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("4:1: queue_name_template: must contain {item}"), "{}", errors[0]);
+        assert!(errors[1].starts_with("4:1: queue_name_template: unknown placeholder {who}"), "{}", errors[1]);
+    }
+
+    #[test]
+    fn syntax_errors_have_a_position() {
+        let errors = validate("{\n  \"host\": \n}", ConfigFormat::Json);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (3, 1));
+        assert!(errors[0].field.is_empty());
+
+        let errors = validate("host = \n", ConfigFormat::Toml);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 1);
+    }
+
+    #[test]
+    fn line_column_counts_characters() {
+        assert_eq!(line_column("ab\ncé d", 7), (2, 4));
+        assert_eq!(line_column("ab", 100), (1, 3));
+    }
+}
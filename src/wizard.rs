@@ -0,0 +1,439 @@
+use std::fs;
+use std::path::Path;
+use crossterm::event;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, Wrap};
+use tui::Terminal;
+use anyhow::{Result, anyhow};
+use crate::{Ampq, Config, theme};
+use crate::management::ManagementClient;
+use crate::models::enums::ExchangeTypeSer;
+use crate::models::exchange_options::ExchangeOptions;
+use crate::models::management::ExchangeInfo;
+use crate::secrets::PasswordSource;
+
+const HOST: usize = 0;
+const PORT: usize = 1;
+const USERNAME: usize = 2;
+const PASSWORD: usize = 3;
+const PASSWORD_ENV: usize = 4;
+const VHOST: usize = 5;
+const PROTOCOL: usize = 6;
+const MANAGEMENT_URL: usize = 7;
+const CONFIG_PATH: usize = 8;
+
+const ACTIONS: [&str; 3] = ["[ Test Connection ]", "[ Discover Exchanges ]", "[ Save and Start ]"];
+
+#[derive(Clone, Copy)]
+enum WizardAction {
+    TestConnection,
+    DiscoverExchanges,
+    Save
+}
+
+#[derive(PartialEq)]
+enum WizardFocus {
+    Form,
+    Exchanges
+}
+
+struct WizardField {
+    label: &'static str,
+    value: String,
+    masked: bool
+}
+
+/// First run setup, shown when no configuration file could be found.
+pub struct Wizard {
+    fields: Vec<WizardField>,
+    index: usize,
+    focus: WizardFocus,
+    exchanges: Vec<(ExchangeInfo, bool)>,
+    exchange_index: usize,
+    status: String,
+    pending_action: Option<WizardAction>
+}
+
+impl Wizard {
+    pub fn new(config_path: &Path) -> Self {
+        let field = |label: &'static str, value: &str, masked: bool| WizardField { label, value: value.to_string(), masked };
+
+        Wizard {
+            fields: vec![
+                field("Host", "127.0.0.1", false),
+                field("Port", "5672", false),
+                field("Username", "guest", false),
+                field("Password", "", true),
+                field("Password Env Var (optional)", "", false),
+                field("Vhost", "", false),
+                field("Protocol", "amqp", false),
+                field("Management URL (optional)", "http://127.0.0.1:15672", false),
+                field("Config File", config_path.to_string_lossy().as_ref(), false),
+            ],
+            index: 0,
+            focus: WizardFocus::Form,
+            exchanges: vec![],
+            exchange_index: 0,
+            status: "No configuration file was found. Enter the broker details to create one.".to_string(),
+            pending_action: None
+        }
+    }
+
+    /// Returns the saved config, or `None` when the user quits the setup.
+    pub fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<Option<Config>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            //actions run after a draw so the status shows what is being waited on
+            if let Some(action) = self.pending_action.take() {
+                if let Some(config) = self.run_action(action) {
+                    return Ok(Some(config));
+                }
+                continue;
+            }
+
+            if let Event::Key(key) = event::read()? {
+                let exit = self.handle_input(key);
+                if exit {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn handle_input(&mut self, key: KeyEvent) -> bool {
+        //checked before the fields so Ctrl+C quits instead of typing a 'c'
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return true;
+        }
+
+        match self.focus {
+            WizardFocus::Form => match key.code {
+                KeyCode::Esc => return true,
+                KeyCode::Up if self.index > 0 => self.index -= 1,
+                KeyCode::Down if self.index < self.fields.len() + ACTIONS.len() - 1 => self.index += 1,
+                KeyCode::Tab if !self.exchanges.is_empty() => self.focus = WizardFocus::Exchanges,
+                KeyCode::Enter => {
+                    if self.index < self.fields.len() {
+                        self.index += 1;
+                    }
+                    else {
+                        let action = match self.index - self.fields.len() {
+                            0 => WizardAction::TestConnection,
+                            1 => WizardAction::DiscoverExchanges,
+                            _ => WizardAction::Save
+                        };
+
+                        self.status = match action {
+                            WizardAction::TestConnection => "Connecting...".to_string(),
+                            WizardAction::DiscoverExchanges => "Requesting exchanges...".to_string(),
+                            WizardAction::Save => "Saving...".to_string()
+                        };
+                        self.pending_action = Some(action);
+                    }
+                }
+                KeyCode::Backspace => {
+                    if let Some(field) = self.fields.get_mut(self.index) {
+                        field.value.pop();
+                    }
+                }
+                KeyCode::Char(c) => {
+                    if let Some(field) = self.fields.get_mut(self.index) {
+                        field.value.push(c);
+                    }
+                }
+                _ => {}
+            }
+            WizardFocus::Exchanges => match key.code {
+                KeyCode::Esc => return true,
+                KeyCode::Tab => self.focus = WizardFocus::Form,
+                KeyCode::Up | KeyCode::Char('k') if self.exchange_index > 0 => self.exchange_index -= 1,
+                KeyCode::Down | KeyCode::Char('j') if self.exchange_index < self.exchanges.len() - 1 => self.exchange_index += 1,
+                KeyCode::Char(' ') | KeyCode::Enter => {
+                    if let Some(exchange) = self.exchanges.get_mut(self.exchange_index) {
+                        exchange.1 = !exchange.1;
+                    }
+                }
+                KeyCode::Char('a') => {
+                    let select = self.exchanges.iter().any(|x| !x.1);
+                    for exchange in self.exchanges.iter_mut() {
+                        exchange.1 = select;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    fn run_action(&mut self, action: WizardAction) -> Option<Config> {
+        match action {
+            WizardAction::TestConnection => {
+                self.status = match self.test_connection() {
+                    Ok(_) => "Connection successful.".to_string(),
+                    Err(e) => format!("Connection failed: {}", e)
+                };
+            }
+            WizardAction::DiscoverExchanges => {
+                match self.discover_exchanges() {
+                    Ok(exchanges) => {
+                        self.status = format!("Found {} exchanges. Press Tab to select the ones to add.", exchanges.len());
+                        self.exchanges = exchanges.into_iter().map(|x| (x, false)).collect();
+                        self.exchange_index = 0;
+                    }
+                    Err(e) => self.status = format!("Discovery failed: {}", e)
+                }
+            }
+            WizardAction::Save => {
+                match self.save() {
+                    Ok(config) => return Some(config),
+                    Err(e) => self.status = format!("Error saving config: {}", e)
+                }
+            }
+        }
+
+        None
+    }
+
+    fn build_config(&self) -> Result<Config> {
+        let value = |index: usize| self.fields[index].value.trim().to_string();
+
+        let port = value(PORT).parse::<u64>().map_err(|_| anyhow!("Port must be a number"))?;
+        let protocol = value(PROTOCOL);
+        if protocol != "amqp" && protocol != "amqps" {
+            return Err(anyhow!("Protocol must be amqp or amqps"));
+        }
+
+        let mut config = Config::new(Path::new(value(CONFIG_PATH).as_str()));
+        config.host = value(HOST);
+        config.port = port;
+        config.username = value(USERNAME);
+        config.password = self.fields[PASSWORD].value.clone();
+        config.protocol = protocol;
+
+        let management_url = value(MANAGEMENT_URL);
+        if !management_url.is_empty() {
+            config.management_url = Some(management_url);
+        }
+
+        let vhost = value(VHOST);
+        if !vhost.is_empty() {
+            config.vhost = Some(vhost);
+        }
+
+        //the typed password is only used for this session, it is not written to the file
+        let password_env = value(PASSWORD_ENV);
+        if !password_env.is_empty() {
+            config.password_source = PasswordSource::Env(password_env);
+        }
+
+        Ok(config)
+    }
+
+    fn test_connection(&self) -> Result<()> {
+        let config = self.build_config()?;
+        let connection = Ampq::connect(&config)?;
+        connection.close()?;
+
+        Ok(())
+    }
+
+    fn discover_exchanges(&self) -> Result<Vec<ExchangeInfo>> {
+        let management_url = self.fields[MANAGEMENT_URL].value.trim();
+        if management_url.is_empty() {
+            return Err(anyhow!("Management URL is not set"));
+        }
+
+        let config = self.build_config()?;
+        let client = ManagementClient::new(management_url, config.username.as_str(), config.password.as_str(), config.vhost.as_deref())?;
+
+        let mut exchanges: Vec<ExchangeInfo> = client
+            .exchanges()?
+            .into_iter()
+            .filter(|x| !x.is_builtin() && x.exchange_type_ser().is_some())
+            .collect();
+        exchanges.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(exchanges)
+    }
+
+    fn save(&self) -> Result<Config> {
+        let mut config = self.build_config()?;
+
+        for (exchange, _) in self.exchanges.iter().filter(|x| x.1) {
+            let exchange_type = exchange.exchange_type_ser().unwrap_or(ExchangeTypeSer::Direct);
+
+            config.items.push(ExchangeOptions {
+                exchange_name: exchange.name.clone(),
+                exchange_type,
+                queue_routing_key: match exchange_type {
+                    ExchangeTypeSer::Topic => "#".to_string(),
+                    _ => "".to_string()
+                },
+                ..ExchangeOptions::default()
+            });
+        }
+
+        if let Some(parent) = Path::new(config.path.as_str()).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        config.save_config()?;
+
+        Ok(config)
+    }
+
+    fn draw<B: Backend>(&mut self, frame: &mut Frame<B>) {
+        let vertical_grid = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0), Constraint::Length(4)].as_ref())
+            .split(frame.size());
+
+        let horizontal_grid = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .split(vertical_grid[1]);
+
+        let header = match self.focus {
+            WizardFocus::Form => " ↑ Select | ↓ Select | (Enter) Next / Run | (Tab) Exchanges | (Esc) Quit |",
+            WizardFocus::Exchanges => " ↑ Select | ↓ Select | (Space) Toggle | (A)ll | (Tab) Form | (Esc) Quit |"
+        };
+
+        let paragraph = Paragraph::new(header)
+            .block(Block::default().borders(Borders::TOP | Borders::BOTTOM))
+            .style(Style::default()
+                .fg(theme::current().foreground)
+                .bg(theme::current().background)
+                .add_modifier(Modifier::BOLD));
+        frame.render_widget(paragraph, vertical_grid[0]);
+
+        let mut rows: Vec<Row> = vec![];
+        for (i, field) in self.fields.iter().enumerate() {
+            let value = if field.masked { "*".repeat(field.value.chars().count()) } else { field.value.clone() };
+            let style = if i == self.index && self.focus == WizardFocus::Form { Style::default().fg(theme::current().input) } else { Style::default().fg(theme::current().foreground) };
+
+            rows.push(Row::new(vec![Cell::from(field.label), Cell::from(value)]).style(style));
+        }
+
+        rows.push(Row::new(vec![""]));
+        for (i, action) in ACTIONS.iter().enumerate() {
+            let style = if i + self.fields.len() == self.index && self.focus == WizardFocus::Form { Style::default().fg(theme::current().input) } else { Style::default().fg(theme::current().accent) };
+
+            rows.push(Row::new(vec![Cell::from(*action), Cell::from("")]).style(style));
+        }
+
+        let width_constraints = [Constraint::Length(30), Constraint::Percentage(100)];
+        let table = Table::new(rows)
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled("Setup", Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))))
+            .widths(&width_constraints)
+            .column_spacing(1);
+        frame.render_widget(table, horizontal_grid[0]);
+
+        let list_items: Vec<ListItem> = self.exchanges
+            .iter()
+            .enumerate()
+            .map(|(i, (exchange, selected))| {
+                let indicator = if i == self.exchange_index && self.focus == WizardFocus::Exchanges { ">" } else { " " };
+                let check = if *selected { "[x]" } else { "[ ]" };
+                let style = if *selected { Style::default().fg(theme::current().selected) } else { Style::default().fg(theme::current().foreground) };
+
+                ListItem::new(Spans::from(vec![
+                    Span::styled(indicator, Style::default().fg(theme::current().input)),
+                    Span::styled(format!(" {} {} ({})", check, exchange.name, exchange.exchange_type), style),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(list_items)
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title(Span::styled("Exchanges", Style::default().fg(theme::current().accent).add_modifier(Modifier::BOLD))));
+        let mut list_state = ListState::default();
+        list_state.select(Some(self.exchange_index));
+        frame.render_stateful_widget(list, horizontal_grid[1], &mut list_state);
+
+        let status = Paragraph::new(self.status.as_str())
+            .style(Style::default().fg(theme::current().foreground).bg(theme::current().background))
+            .block(Block::default().borders(Borders::ALL).title("Status"))
+            .wrap(Wrap { trim: true });
+        frame.render_widget(status, vertical_grid[2]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wizard(values: &[(usize, &str)]) -> Wizard {
+        let mut wizard = Wizard::new(Path::new("/tmp/amqp-client-cli-wizard.toml"));
+        for (index, value) in values {
+            wizard.fields[*index].value = value.to_string();
+        }
+        wizard
+    }
+
+    fn type_text(wizard: &mut Wizard, text: &str) {
+        for c in text.chars() {
+            wizard.handle_input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn config_is_built_from_the_fields() {
+        let wizard = wizard(&[(HOST, " rabbit.local "), (PORT, "5671"), (USERNAME, "admin"), (PASSWORD, " secret "), (VHOST, "staging"), (PROTOCOL, "amqps")]);
+        let config = wizard.build_config().unwrap();
+
+        assert_eq!(config.path, "/tmp/amqp-client-cli-wizard.toml");
+        assert_eq!((config.host.as_str(), config.port, config.username.as_str()), ("rabbit.local", 5671, "admin"));
+        assert_eq!(config.password, " secret ");
+        assert_eq!(config.protocol, "amqps");
+        assert_eq!(config.vhost.as_deref(), Some("staging"));
+        assert_eq!(config.management_url.as_deref(), Some("http://127.0.0.1:15672"));
+        assert_eq!(config.password_source, PasswordSource::Prompt);
+    }
+
+    #[test]
+    fn optional_fields_are_left_out_when_empty() {
+        let config = wizard(&[(MANAGEMENT_URL, " "), (PASSWORD_ENV, "RABBIT_PASSWORD")]).build_config().unwrap();
+
+        assert_eq!(config.vhost, None);
+        assert_eq!(config.management_url, None);
+        assert_eq!(config.password_source, PasswordSource::Env("RABBIT_PASSWORD".to_string()));
+    }
+
+    #[test]
+    fn invalid_fields_fail() {
+        assert_eq!(wizard(&[(PORT, "56x2")]).build_config().err().unwrap().to_string(), "Port must be a number");
+        assert_eq!(wizard(&[(PROTOCOL, "http")]).build_config().err().unwrap().to_string(), "Protocol must be amqp or amqps");
+    }
+
+    #[test]
+    fn typing_edits_the_selected_field() {
+        let mut wizard = wizard(&[(HOST, "")]);
+        type_text(&mut wizard, "localhostx");
+        wizard.handle_input(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        wizard.handle_input(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        type_text(&mut wizard, "0");
+
+        let config = wizard.build_config().unwrap();
+        assert_eq!(config.host, "localhost");
+        assert_eq!(config.port, 56720);
+    }
+
+    #[test]
+    fn ctrl_c_quits() {
+        let mut wizard = wizard(&[(HOST, "")]);
+
+        assert!(!wizard.handle_input(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE)));
+        assert!(wizard.handle_input(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+        assert_eq!(wizard.fields[HOST].value, "c");
+    }
+}